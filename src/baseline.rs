@@ -0,0 +1,393 @@
+use crate::nan_safe_float::Float;
+use crate::object3d::{Object3d, SubMesh};
+use crate::vertex::{VertexAttribute, VertexData, VertexFormat};
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct BaselineTolerances {
+    pub position: f64,
+    pub normal: f64,
+    // also used for the second UV set and the optional w of `vt`, the w of `v` uses position
+    pub tex_coord: f64,
+    pub joint_weight: f64,
+}
+
+impl Default for BaselineTolerances {
+    fn default() -> Self {
+        BaselineTolerances {
+            position: 1e-6,
+            normal: 1e-6,
+            tex_coord: 1e-6,
+            joint_weight: 1e-6,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum BaselineMismatch {
+    ObjectCount { expected: usize, actual: usize },
+    ObjectName { object: usize, expected: String, actual: String },
+    VertexFormat { object: String, expected: VertexFormat, actual: VertexFormat },
+    VertexCount { object: String, expected: usize, actual: usize },
+    IndexCount { object: String, expected: usize, actual: usize },
+    Material { object: String, expected: Option<String>, actual: Option<String> },
+    SubMeshes { object: String, expected: Vec<SubMesh>, actual: Vec<SubMesh> },
+    // a vertex has an optional attribute (a normal, UV set, or w) the baseline vertex lacks or the
+    // other way around, `expected` is whether the baseline has it
+    AttributePresence { object: String, vertex: usize, attribute: VertexAttribute, expected: bool },
+    VertexDrift { object: String, vertex: usize, attribute: VertexAttribute, drift: f64 },
+    Index { object: String, index: usize, expected: u64, actual: u64 },
+    SkinCount { object: String, expected: usize, actual: usize },
+    JointIndices { object: String, vertex: usize, expected: [u16; 4], actual: [u16; 4] },
+    JointWeightDrift { object: String, vertex: usize, drift: f64 },
+}
+
+// Compares compiled objects against a known good ("golden") compile of the same asset, object by
+// object in order. Compared are names, vertex formats, materials and sub meshes, buffer counts,
+// indices, joint indices, and every vertex attribute including the second UV set and the w
+// components. Floats are compared component-wise within the given tolerances. Source locations,
+// smoothing groups, polylines, and lightmap UVs are not compared, they are derived data or
+// diagnostics rather than what gets rendered
+pub fn assert_matches_baseline(
+    objects: &[Object3d],
+    baseline: &[Object3d],
+    tolerances: &BaselineTolerances,
+) -> Result<(), Vec<BaselineMismatch>> {
+    let mut mismatches = Vec::new();
+
+    if objects.len() != baseline.len() {
+        mismatches.push(BaselineMismatch::ObjectCount { expected: baseline.len(), actual: objects.len() });
+    }
+
+    for (i, (actual, expected)) in objects.iter().zip(baseline.iter()).enumerate() {
+        if actual.name != expected.name {
+            mismatches.push(BaselineMismatch::ObjectName {
+                object: i,
+                expected: expected.name.clone(),
+                actual: actual.name.clone(),
+            });
+        }
+
+        compare_object(actual, expected, tolerances, &mut mismatches);
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+fn compare_object(
+    actual: &Object3d,
+    expected: &Object3d,
+    tolerances: &BaselineTolerances,
+    mismatches: &mut Vec<BaselineMismatch>,
+) {
+    let name = &expected.name;
+
+    if actual.format != expected.format {
+        mismatches.push(BaselineMismatch::VertexFormat {
+            object: name.clone(),
+            expected: expected.format,
+            actual: actual.format,
+        });
+    }
+
+    if actual.vertex_buffer.len() != expected.vertex_buffer.len() {
+        mismatches.push(BaselineMismatch::VertexCount {
+            object: name.clone(),
+            expected: expected.vertex_buffer.len(),
+            actual: actual.vertex_buffer.len(),
+        });
+    }
+
+    if actual.material != expected.material {
+        mismatches.push(BaselineMismatch::Material {
+            object: name.clone(),
+            expected: expected.material.clone(),
+            actual: actual.material.clone(),
+        });
+    }
+
+    if actual.sub_meshes != expected.sub_meshes {
+        mismatches.push(BaselineMismatch::SubMeshes {
+            object: name.clone(),
+            expected: expected.sub_meshes.clone(),
+            actual: actual.sub_meshes.clone(),
+        });
+    }
+
+    if actual.index_buffer.len() != expected.index_buffer.len() {
+        mismatches.push(BaselineMismatch::IndexCount {
+            object: name.clone(),
+            expected: expected.index_buffer.len(),
+            actual: actual.index_buffer.len(),
+        });
+    }
+
+    // vertex by vertex comparisons are meaningless once the layout or counts diverge
+    if actual.format == expected.format && actual.vertex_buffer.len() == expected.vertex_buffer.len() {
+        for (i, (a, e)) in actual.vertex_buffer.iter().zip(expected.vertex_buffer.iter()).enumerate() {
+            compare_vertex(name, i, a, e, tolerances, mismatches);
+        }
+    }
+
+    compare_skin(actual, expected, tolerances, mismatches);
+
+    if actual.index_buffer.len() == expected.index_buffer.len() {
+        for (i, (a, e)) in actual.index_buffer.iter().zip(expected.index_buffer.iter()).enumerate() {
+            if a != e {
+                mismatches.push(BaselineMismatch::Index { object: name.clone(), index: i, expected: *e, actual: *a });
+            }
+        }
+    }
+}
+
+fn compare_vertex(
+    name: &str,
+    vertex: usize,
+    actual: &VertexData,
+    expected: &VertexData,
+    tolerances: &BaselineTolerances,
+    mismatches: &mut Vec<BaselineMismatch>,
+) {
+    let optional = [
+        (VertexAttribute::Normal, tolerances.normal, actual.normal.map(tuple_3d), expected.normal.map(tuple_3d)),
+        (VertexAttribute::TexCoord, tolerances.tex_coord, actual.tex_coord.map(tuple_2d), expected.tex_coord.map(tuple_2d)),
+        (VertexAttribute::TexCoord2, tolerances.tex_coord, actual.tex_coord2.map(tuple_2d), expected.tex_coord2.map(tuple_2d)),
+        // the w components are reported against the attribute they extend
+        (VertexAttribute::Position, tolerances.position, actual.pos_w.map(|w| vec!(*w)), expected.pos_w.map(|w| vec!(*w))),
+        (VertexAttribute::TexCoord, tolerances.tex_coord, actual.tex_coord_w.map(|w| vec!(*w)), expected.tex_coord_w.map(|w| vec!(*w))),
+    ];
+
+    let mut drifts = vec!(
+        (VertexAttribute::Position, tolerances.position, max_drift(&tuple_3d(actual.pos), &tuple_3d(expected.pos))),
+    );
+    for (attribute, tolerance, a, e) in optional {
+        match (a, e) {
+            (Some(a), Some(e)) => drifts.push((attribute, tolerance, max_drift(&a, &e))),
+            (None, None) => {},
+            (_, e) => mismatches.push(BaselineMismatch::AttributePresence {
+                object: String::from(name),
+                vertex,
+                attribute,
+                expected: e.is_some(),
+            }),
+        }
+    }
+
+    for (attribute, tolerance, drift) in drifts {
+        if drift > tolerance {
+            mismatches.push(BaselineMismatch::VertexDrift { object: String::from(name), vertex, attribute, drift });
+        }
+    }
+}
+
+fn compare_skin(
+    actual: &Object3d,
+    expected: &Object3d,
+    tolerances: &BaselineTolerances,
+    mismatches: &mut Vec<BaselineMismatch>,
+) {
+    let name = &expected.name;
+
+    if actual.joint_indices.len() != expected.joint_indices.len() {
+        mismatches.push(BaselineMismatch::SkinCount {
+            object: name.clone(),
+            expected: expected.joint_indices.len(),
+            actual: actual.joint_indices.len(),
+        });
+        return;
+    }
+
+    for (vertex, (a, e)) in actual.joint_indices.iter().zip(expected.joint_indices.iter()).enumerate() {
+        if a != e {
+            mismatches.push(BaselineMismatch::JointIndices { object: name.clone(), vertex, expected: *e, actual: *a });
+        }
+    }
+
+    for (vertex, (a, e)) in actual.joint_weights.iter().zip(expected.joint_weights.iter()).enumerate() {
+        let drift = a.iter().zip(e.iter()).map(|(a, e)| (a - e).abs() as f64).fold(0.0, f64::max);
+        if drift > tolerances.joint_weight {
+            mismatches.push(BaselineMismatch::JointWeightDrift { object: name.clone(), vertex, drift });
+        }
+    }
+}
+
+fn tuple_3d(t: (Float, Float, Float)) -> Vec<f64> {
+    vec!(*t.0, *t.1, *t.2)
+}
+
+fn tuple_2d(t: (Float, Float)) -> Vec<f64> {
+    vec!(*t.0, *t.1)
+}
+
+fn max_drift(actual: &[f64], expected: &[f64]) -> f64 {
+    actual.iter().zip(expected.iter()).map(|(a, e)| (a - e).abs()).fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::test_fixtures::positions;
+    use super::*;
+
+    #[test]
+    fn assert_matches_baseline_accepts_identical_objects() {
+        let result = assert_matches_baseline(
+            &[triangle("Object1", 0.0)],
+            &[triangle("Object1", 0.0)],
+            &BaselineTolerances::default()
+        );
+
+        assert_eq!(Ok(()), result, "Identical objects match their baseline");
+    }
+
+    #[test]
+    fn assert_matches_baseline_accepts_drift_within_tolerance() {
+        let result = assert_matches_baseline(
+            &[triangle("Object1", 0.0000001)],
+            &[triangle("Object1", 0.0)],
+            &BaselineTolerances::default()
+        );
+
+        assert_eq!(Ok(()), result, "Vertex drift within tolerance matches the baseline");
+    }
+
+    #[test]
+    fn assert_matches_baseline_reports_vertex_drift() {
+        let result = assert_matches_baseline(
+            &[triangle("Object1", 0.5)],
+            &[triangle("Object1", 0.0)],
+            &BaselineTolerances::default()
+        );
+
+        assert_eq!(
+            Err(vec!(
+                BaselineMismatch::VertexDrift {
+                    object: String::from("Object1"),
+                    vertex: 0,
+                    attribute: VertexAttribute::Position,
+                    drift: 0.5
+                },
+            )),
+            result,
+            "Vertex drift beyond tolerance is reported per vertex and attribute"
+        );
+    }
+
+    #[test]
+    fn assert_matches_baseline_reports_count_changes() {
+        let mut changed = triangle("Renamed", 0.0);
+        changed.index_buffer.push(0);
+
+        let result = assert_matches_baseline(
+            &[changed],
+            &[triangle("Object1", 0.0), triangle("Object2", 0.0)],
+            &BaselineTolerances::default()
+        );
+
+        assert_eq!(
+            Err(vec!(
+                BaselineMismatch::ObjectCount { expected: 2, actual: 1 },
+                BaselineMismatch::ObjectName { object: 0, expected: String::from("Object1"), actual: String::from("Renamed") },
+                BaselineMismatch::IndexCount { object: String::from("Object1"), expected: 3, actual: 4 },
+            )),
+            result,
+            "Object, name, and buffer count changes are reported"
+        );
+    }
+
+    #[test]
+    fn assert_matches_baseline_compares_materials_and_sub_meshes() {
+        let mut changed = triangle("Object1", 0.0);
+        changed.material = Some(String::from("Red"));
+        changed.sub_meshes = vec!(SubMesh { material: Some(String::from("Red")), index_start: 0, index_count: 3 });
+
+        let result = assert_matches_baseline(&[changed], &[triangle("Object1", 0.0)], &BaselineTolerances::default());
+
+        assert_eq!(
+            Err(vec!(
+                BaselineMismatch::Material { object: String::from("Object1"), expected: None, actual: Some(String::from("Red")) },
+                BaselineMismatch::SubMeshes {
+                    object: String::from("Object1"),
+                    expected: Vec::new(),
+                    actual: vec!(SubMesh { material: Some(String::from("Red")), index_start: 0, index_count: 3 }),
+                },
+            )),
+            result,
+            "A changed material is a mismatch"
+        );
+    }
+
+    #[test]
+    fn assert_matches_baseline_compares_second_uvs_and_w() {
+        let mut changed = triangle("Object1", 0.0);
+        changed.vertex_buffer[0].pos_w = Some(f!(0.5));
+        changed.vertex_buffer[1].tex_coord2 = Some((f!(0.25), f!(0.0)));
+        let mut baseline = triangle("Object1", 0.0);
+        baseline.vertex_buffer[0].pos_w = Some(f!(1.0));
+
+        let result = assert_matches_baseline(&[changed], &[baseline], &BaselineTolerances::default());
+
+        assert_eq!(
+            Err(vec!(
+                BaselineMismatch::VertexDrift {
+                    object: String::from("Object1"),
+                    vertex: 0,
+                    attribute: VertexAttribute::Position,
+                    drift: 0.5
+                },
+                BaselineMismatch::AttributePresence {
+                    object: String::from("Object1"),
+                    vertex: 1,
+                    attribute: VertexAttribute::TexCoord2,
+                    expected: false
+                },
+            )),
+            result,
+            "The w components and the second UV set are compared"
+        );
+    }
+
+    #[test]
+    fn assert_matches_baseline_compares_joints() {
+        let mut changed = triangle("Object1", 0.0);
+        changed.joint_indices = vec!([0, 0, 0, 0], [1, 0, 0, 0], [0, 0, 0, 0]);
+        changed.joint_weights = vec!([1.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [0.5, 0.5, 0.0, 0.0]);
+        let mut baseline = triangle("Object1", 0.0);
+        baseline.joint_indices = vec!([0, 0, 0, 0]; 3);
+        baseline.joint_weights = vec!([1.0, 0.0, 0.0, 0.0]; 3);
+
+        let result = assert_matches_baseline(&[changed], &[baseline], &BaselineTolerances::default());
+
+        assert_eq!(
+            Err(vec!(
+                BaselineMismatch::JointIndices {
+                    object: String::from("Object1"),
+                    vertex: 1,
+                    expected: [0, 0, 0, 0],
+                    actual: [1, 0, 0, 0]
+                },
+                BaselineMismatch::JointWeightDrift { object: String::from("Object1"), vertex: 2, drift: 0.5 },
+            )),
+            result,
+            "Joint indices must match exactly and weights within tolerance"
+        );
+
+        let unskinned = assert_matches_baseline(
+            &[triangle("Object1", 0.0)],
+            &[Object3d { joint_indices: vec!([0, 0, 0, 0]; 3), ..triangle("Object1", 0.0) }],
+            &BaselineTolerances::default()
+        );
+        assert_eq!(
+            Err(vec!(BaselineMismatch::SkinCount { object: String::from("Object1"), expected: 3, actual: 0 })),
+            unskinned,
+            "Losing the skin is a mismatch"
+        );
+    }
+
+    fn triangle(name: &str, offset: f64) -> Object3d {
+        positions(name, &[[-1.0 + offset, -1.0, 0.0], [0.0, 1.0, 0.0], [1.0, -1.0, 0.0]], &[0, 1, 2])
+    }
+}
@@ -1,50 +1,303 @@
+use std::time::{Duration, Instant};
+use crate::f;
 use crate::statement::{Statement, StatementType, StatementDataType};
-use crate::object3d::{Object3d};
-use crate::vertex::{VertexData, VertexFormat};
+use crate::object3d::{FaceSource, Object3d, Polyline, SubMesh};
+use crate::vertex::{VertexData, VertexSource};
 use crate::nan_safe_float::Float;
+use crate::diagnostic::Diagnostic;
+use crate::error::WfoError;
+use crate::prepass::StatementCounts;
 
-struct Compiler {
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct CompilerOptions {
+    // Glob patterns ('*' and '?') matched against object names. When include_objects is empty
+    // every object is included
+    pub include_objects: Vec<String>,
+    pub exclude_objects: Vec<String>,
+    // Keep the authored polygons on each object alongside the triangulated buffers
+    pub retain_faces: bool,
+    pub index_base: IndexBase,
+    pub index_range_policy: IndexRangePolicy,
+    pub split_by: ObjectSplit,
+}
+
+// Which statements start a new output object
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ObjectSplit {
+    // `o` only, `g` is ignored
+    #[default]
+    Objects,
+    // `g` only, named after the group
+    Groups,
+    // both, named "object/group" when both are set
+    ObjectsAndGroups,
+}
+
+// What a face's v/vt/vn indices count from. Some exporters write 0 based indices even though the
+// format is 1 based
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IndexBase {
+    Auto,
+    #[default]
+    One,
+    Zero,
+}
+
+// What to do with a face that references a v/vt/vn index outside the data defined so far
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IndexRangePolicy {
+    #[default]
+    Error,
+    // Skip the face and record a warning
+    DropFace,
+    // Replace the index with the nearest defined one and record a warning
+    Clamp,
+    // Replace the corner with another corner of the same face, collapsing the triangle, and
+    // record a warning
+    Degenerate,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum IgnoredReason {
+    Comment,
+    ObjectExcluded,
+    // an `o` or `g` that doesn't start a new object under the configured ObjectSplit
+    NotASplitPoint,
+}
+
+// A statement the compiler dropped without it affecting any compiled object
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct IgnoredContent {
+    pub statement_type: StatementType,
+    pub line_number: u64,
+    pub reason: IgnoredReason,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum FixupKind {
+    FaceDropped,
+    IndexClamped,
+    CornerCollapsed,
+}
+
+// A change compile made to the input under a lenient option, with the face's (v, vt, vn) corners
+// as written and as compiled (none when the face was dropped), so imports can be audited
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Fixup {
+    pub kind: FixupKind,
+    pub line_number: u64,
+    pub line_position: u64,
+    pub before: Vec<[u64; 3]>,
+    pub after: Option<Vec<[u64; 3]>>,
+}
+
+pub(crate) struct Compiler {
     default_name: String,
+    options: CompilerOptions,
     cur_obj: Option<Object3d>,
+    object_name: Option<String>,
+    group_name: Option<String>,
     position_buffer: Vec<(Float, Float, Float)>,
     normal_buffer: Vec<(Float, Float, Float)>,
     tex_coord_buffer: Vec<(Float, Float)>,
+    tex_coord2_buffer: Vec<(Float, Float)>,
+    position_w_buffer: Vec<Option<Float>>,
+    tex_coord_w_buffer: Vec<Option<Float>>,
+    smoothing_group: u32,
+    material: Option<String>,
+    ignored: Vec<IgnoredContent>,
+    warnings: Vec<Diagnostic>,
+    fixups: Vec<Fixup>,
+    material_libraries: Vec<(String, u64, u64)>,
+    zero_based: bool,
+    // when the last object was finished (or compiling started), to time each object
+    object_started: Option<Instant>,
+    object_timings: Vec<(String, Duration)>,
 }
 
 impl Compiler {
-    fn from_default_name(new_default_name: &String) -> Self {
+    pub(crate) fn from_default_name(new_default_name: &String) -> Self {
+        Compiler::from_default_name_and_options(new_default_name, CompilerOptions::default())
+    }
+    
+    pub(crate) fn from_default_name_and_options(new_default_name: &String, options: CompilerOptions) -> Self {
         Compiler {
             default_name: new_default_name.clone(),
+            options,
             cur_obj: None,
+            object_name: None,
+            group_name: None,
             position_buffer: Vec::new(),
             normal_buffer: Vec::new(),
             tex_coord_buffer: Vec::new(),
+            tex_coord2_buffer: Vec::new(),
+            position_w_buffer: Vec::new(),
+            tex_coord_w_buffer: Vec::new(),
+            smoothing_group: 0,
+            material: None,
+            ignored: Vec::new(),
+            warnings: Vec::new(),
+            fixups: Vec::new(),
+            material_libraries: Vec::new(),
+            zero_based: false,
+            object_started: None,
+            object_timings: Vec::new(),
         }
     }
     
-    fn compile(&mut self, statements: &Vec<Statement>) -> Result<Vec<Object3d>, String> {
+    // Sizes the attribute buffers up front from a count_statements pre-pass
+    pub(crate) fn reserve(&mut self, counts: &StatementCounts) {
+        self.position_buffer.reserve(counts.vertices);
+        self.position_w_buffer.reserve(counts.vertices);
+        self.normal_buffer.reserve(counts.normals);
+        self.tex_coord_buffer.reserve(counts.tex_coords);
+        self.tex_coord_w_buffer.reserve(counts.tex_coords);
+        self.tex_coord2_buffer.reserve(counts.tex_coords2);
+    }
+    
+    pub(crate) fn compile(&mut self, statements: &[Statement]) -> Result<Vec<Object3d>, WfoError> {
         let mut results: Vec<Object3d> = Vec::new();
+        self.resolve_index_base(statements);
         
         for statement in statements {
-            match statement.statement_type {
-                StatementType::COMMENT => {/*comments don't have side effects ... so ignore?*/}
-                StatementType::MTLLIB => {/*ignore these*/}
-                StatementType::OBJECT => {self.handle_object_statement(statement, &mut results)?}
-                StatementType::VERTEX => {self.handle_vertex_statement(statement)?}
-                StatementType::NORMAL => {self.handle_normal_statement(statement)?}
-                StatementType::TEXCOORD => {self.handle_tex_coord_statement(statement)?}
-                StatementType::USEMTL => {/*TODO: implement material support*/}
-                StatementType::FACE => {self.handle_face_statement(statement)?}
-                StatementType::ILLUM => {/*ignore these*/}
-            }
+            self.compile_statement(statement, &mut results).map_err(|message| Compiler::error_at(message, statement))?;
         }
-        self.clean_up(&mut results)?;
+        self.clean_up(&mut results);
         
         Ok(results)
     }
     
+    // Unlike compile, a failure doesn't discard everything: the objects finished before the
+    // failing statement are returned along with the error for that statement
+    pub(crate) fn compile_with_salvage(&mut self, statements: &[Statement]) -> (Vec<Object3d>, Option<WfoError>) {
+        let mut results: Vec<Object3d> = Vec::new();
+        self.resolve_index_base(statements);
+        
+        for statement in statements {
+            if let Err(message) = self.compile_statement(statement, &mut results) {
+                return (results, Some(Compiler::error_at(message, statement)));
+            }
+        }
+        self.finish_object(&mut results);
+        
+        (results, None)
+    }
+    
+    fn error_at(message: String, statement: &Statement) -> WfoError {
+        WfoError::CompileError {
+            message,
+            line_number: statement.line_number,
+            line_position: statement.line_position,
+            statement: statement.statement_type,
+        }
+    }
+    
+    fn resolve_index_base(&mut self, statements: &[Statement]) {
+        self.object_started = Some(Instant::now());
+        self.zero_based = match self.options.index_base {
+            IndexBase::Auto => Compiler::detect_index_base(statements) == IndexBase::Zero,
+            index_base => index_base == IndexBase::Zero,
+        };
+    }
+    
+    fn compile_statement(&mut self, statement: &Statement, results: &mut Vec<Object3d>) -> Result<(), String> {
+        match statement.statement_type {
+            StatementType::COMMENT => {self.ignore(statement, IgnoredReason::Comment)}
+            StatementType::MTLLIB => {self.handle_material_library_statement(statement)?}
+            StatementType::OBJECT => {self.handle_object_statement(statement, results)?}
+            StatementType::VERTEX => {self.handle_vertex_statement(statement)?}
+            StatementType::NORMAL => {self.handle_normal_statement(statement)?}
+            StatementType::TEXCOORD => {self.handle_tex_coord_statement(statement)?}
+            StatementType::TEXCOORD2 => {self.handle_tex_coord2_statement(statement)?}
+            StatementType::USEMTL => {self.handle_use_material_statement(statement)?}
+            StatementType::FACE => {self.handle_face_statement(statement)?}
+            StatementType::SMOOTHING => {self.handle_smoothing_group_statement(statement)?}
+            StatementType::GROUP => {self.handle_group_statement(statement, results)?}
+            StatementType::LINE => {self.handle_line_statement(statement)?}
+        }
+        
+        Ok(())
+    }
+    
+    // A file is taken to be 0 based when some face references position 0 (never valid in a 1 based
+    // file) and no face references past the last position, which a 0 based file can't either
+    pub(crate) fn detect_index_base(statements: &[Statement]) -> IndexBase {
+        let position_count = statements.iter()
+            .filter(|s| s.statement_type == StatementType::VERTEX)
+            .count() as u64;
+        // relative indices resolve to 1 based ones, so they say nothing about the base
+        let positions: Vec<u64> = statements.iter()
+            .flat_map(|s| {
+                s.data.face_corners().unwrap_or_default().into_iter()
+                    .enumerate()
+                    .filter(|(i, _)| !s.is_relative_index(i * 3))
+                    .map(|(_, corner)| corner[0])
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        
+        if positions.contains(&0) && positions.iter().all(|p| *p < position_count) {
+            IndexBase::Zero
+        } else {
+            IndexBase::One
+        }
+    }
+    
+    // Everything compile skipped so far, in statement order
+    pub(crate) fn ignored_content(&self) -> &[IgnoredContent] {
+        &self.ignored
+    }
+    
+    // Non fatal problems compile worked around, e.g. faces repaired under an IndexRangePolicy
+    pub(crate) fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+    
+    // How long each returned object took to compile, including the v/vt/vn statements read
+    // since the previous object
+    pub(crate) fn object_timings(&self) -> &[(String, Duration)] {
+        &self.object_timings
+    }
+    
+    // Every face rewritten by a lenient option, one per warning
+    pub(crate) fn fixups(&self) -> &[Fixup] {
+        &self.fixups
+    }
+    
+    // File names from mtllib statements in the order they appear, for the caller to resolve, with
+    // the line number and position of the statement that first named each
+    pub(crate) fn material_libraries(&self) -> &[(String, u64, u64)] {
+        &self.material_libraries
+    }
+    
+    fn ignore(&mut self, statement: &Statement, reason: IgnoredReason) {
+        self.ignored.push(IgnoredContent {
+            statement_type: statement.statement_type,
+            line_number: statement.line_number,
+            reason,
+        });
+    }
+    
+    fn handle_material_library_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        let name = match &statement.data {
+            StatementDataType::String(x) => x,
+            _ => {return Err(String::from("Material library statement did not have a file name"))},
+        };
+        if !self.material_libraries.iter().any(|(x, _, _)| x == name) {
+            self.material_libraries.push((name.clone(), statement.line_number, statement.line_position));
+        }
+        
+        Ok(())
+    }
+    
     fn handle_vertex_statement(&mut self, statement: &Statement) -> Result<(), String> {
-        self.position_buffer.push(statement.data.number_3d_as_tuple().expect("Expected conversion"));
+        let (position, w) = match statement.data {
+            StatementDataType::Number3D(x, y, z) => ((x, y, z), None),
+            StatementDataType::Number4D(x, y, z, w) => ((x, y, z), Some(w)),
+            _ => {return Err(String::from("Vertex statement did not have 3 or 4 numbers"))},
+        };
+        self.position_buffer.push(position);
+        self.position_w_buffer.push(w);
         
         Ok(())
     }
@@ -56,7 +309,47 @@ impl Compiler {
     }
     
     fn handle_tex_coord_statement(&mut self, statement: &Statement) -> Result<(), String> {
-        self.tex_coord_buffer.push(statement.data.number_2d_as_tuple().expect("Expected conversion"));
+        // a missing v defaults to 0
+        let (tex_coord, w) = match statement.data {
+            StatementDataType::Number(u) => ((u, f!(0.0)), None),
+            StatementDataType::Number2D(u, v) => ((u, v), None),
+            StatementDataType::Number3D(u, v, w) => ((u, v), Some(w)),
+            _ => {return Err(String::from("Texture coordinate statement did not have 1 to 3 numbers"))},
+        };
+        self.tex_coord_buffer.push(tex_coord);
+        self.tex_coord_w_buffer.push(w);
+        
+        Ok(())
+    }
+    
+    fn handle_tex_coord2_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        self.tex_coord2_buffer.push(statement.data.number_2d_as_tuple().expect("Expected conversion"));
+        
+        Ok(())
+    }
+    
+    fn handle_smoothing_group_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        let group = match statement.data {
+            StatementDataType::Number(x) => x,
+            _ => {return Err(String::from("Smoothing group statement did not have a number"))},
+        };
+        if *group < 0.0 || group.fract() != 0.0 {
+            return Err(format!("Smoothing group must be a non-negative integer but found {}", group));
+        }
+        
+        self.smoothing_group = *group as u32;
+        
+        Ok(())
+    }
+    
+    // The material stays active across `o` and `g` until the next usemtl
+    fn handle_use_material_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        let name = match &statement.data {
+            StatementDataType::String(x) => x,
+            _ => {return Err(String::from("Use material statement did not have a material name"))},
+        };
+        
+        self.material = if name.is_empty() { None } else { Some(name.clone()) };
         
         Ok(())
     }
@@ -67,49 +360,293 @@ impl Compiler {
             _ => {return Err(String::from("Object statement did not have string name"))},
         };
         
-        let current_obj = self.cur_obj.take();
-        if let Some(x) = current_obj {
-            results.push(x);
+        self.object_name = Some(name.clone());
+        self.group_name = None;
+        if self.options.split_by == ObjectSplit::Groups {
+            self.ignore(statement, IgnoredReason::NotASplitPoint);
+            return Ok(());
         }
         
-        self.cur_obj = Some(Object3d::from(name.clone()));
+        self.finish_object(results);
+        
+        self.cur_obj = Some(Object3d::from(self.current_name()));
         
         Ok(())
     }
     
+    fn handle_group_statement(&mut self, statement: &Statement, results: &mut Vec<Object3d>) -> Result<(), String> {
+        let name = match &statement.data {
+            StatementDataType::String(x) => x,
+            _ => {return Err(String::from("Group statement did not have string name"))},
+        };
+        
+        self.group_name = if name.is_empty() { None } else { Some(name.clone()) };
+        if self.options.split_by == ObjectSplit::Objects {
+            self.ignore(statement, IgnoredReason::NotASplitPoint);
+            return Ok(());
+        }
+        
+        self.finish_object(results);
+        
+        self.cur_obj = Some(Object3d::from(self.current_name()));
+        
+        Ok(())
+    }
+    
+    fn current_name(&self) -> String {
+        let object_name = self.object_name.as_ref();
+        let group_name = self.group_name.as_ref();
+        let name = match self.options.split_by {
+            ObjectSplit::Objects => object_name.cloned(),
+            ObjectSplit::Groups => group_name.cloned(),
+            ObjectSplit::ObjectsAndGroups => match (object_name, group_name) {
+                (Some(o), Some(g)) => Some(format!("{}/{}", o, g)),
+                (o, g) => o.or(g).cloned(),
+            },
+        };
+        
+        name.unwrap_or_else(|| self.default_name.clone())
+    }
+    
     fn handle_face_statement(&mut self, statement: &Statement) -> Result<(), String> {
-        let current_obj = self.cur_obj.get_or_insert(Object3d::from(self.default_name.clone()));
-        let face_indices = statement.data.face_as_index_tuples().expect("Expected conversion");
+        let name = self.current_name();
+        let current_obj = self.cur_obj.get_or_insert_with(|| Object3d::from(name));
+        if !Compiler::is_object_included(&self.options, &current_obj.name) {
+            self.ignore(statement, IgnoredReason::ObjectExcluded);
+            return Ok(());
+        }
+        
+        let face_data = if self.zero_based {
+            Compiler::rebase_face(statement, !self.tex_coord_buffer.is_empty(), !self.normal_buffer.is_empty())
+        } else {
+            statement.data.clone()
+        };
+        let lengths = [
+            self.position_buffer.len() as u64,
+            self.tex_coord_buffer.len() as u64,
+            self.normal_buffer.len() as u64,
+        ];
+        let face_data = match Compiler::repair_face(
+            &face_data, lengths, self.options.index_range_policy, statement, &mut self.warnings, &mut self.fixups
+        )? {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        if current_obj.triangle_sources.is_empty() {
+            current_obj.material = self.material.clone();
+        }
+        let face_indices = face_data.face_as_index_tuples().expect("Expected conversion");
         let pos_buffer = &self.position_buffer;
         let normal_buffer = &self.normal_buffer;
         let tex_coord_buffer = &self.tex_coord_buffer;
+        let mut face_source = FaceSource::from(statement.line_number);
+        let mut corners = Vec::new();
         
         for vertex_indices in face_indices {
-            let vertex = VertexData::compile(vertex_indices, pos_buffer, &normal_buffer, &tex_coord_buffer).expect("Expected vertex compilation");
-            
-            let add_vertex_result = current_obj.add_vertex(vertex);
-            if add_vertex_result.is_err() {
-                return Err(add_vertex_result.err().unwrap());
+            let source = VertexSource::from(&vertex_indices, statement.line_number);
+            face_source.indices.push((source.pos_index, source.tex_coord_index, source.normal_index));
+            let mut vertex = VertexData::compile(vertex_indices, pos_buffer, normal_buffer, tex_coord_buffer)?;
+            vertex.pos_w = self.position_w_buffer[source.pos_index as usize - 1];
+            if source.tex_coord_index > 0 {
+                vertex.tex_coord2 = self.tex_coord2_buffer.get(source.tex_coord_index as usize - 1).copied();
+                vertex.tex_coord_w = self.tex_coord_w_buffer[source.tex_coord_index as usize - 1];
             }
+            corners.push((vertex, source));
+        }
+        
+        // fan triangulation, so a quad abcd becomes abc and acd
+        for i in 1..corners.len() - 1 {
+            for corner in [0, i, i + 1] {
+                let (vertex, source) = &corners[corner];
+                current_obj.add_vertex(vertex.clone(), *source)?;
+            }
+            current_obj.triangle_sources.push(face_source.clone());
+            current_obj.smoothing_groups.push(self.smoothing_group);
+        }
+        SubMesh::extend_ranges(&mut current_obj.sub_meshes, &self.material, (corners.len() - 2) * 3);
+        if self.options.retain_faces {
+            current_obj.faces.push(face_source);
         }
         
         Ok(())
     }
     
-    fn clean_up(&mut self, results: &mut Vec<Object3d>) -> Result<(), String> {
+    // Applies the IndexRangePolicy to a face's (v, vt, vn) corners given how many of each are
+    // defined. Returns None when the face should be dropped
+    fn repair_face(
+        data: &StatementDataType,
+        lengths: [u64; 3],
+        policy: IndexRangePolicy,
+        statement: &Statement,
+        warnings: &mut Vec<Diagnostic>,
+        fixups: &mut Vec<Fixup>,
+    ) -> Result<Option<StatementDataType>, String> {
+        let mut corners = match data.face_corners() {
+            Some(x) => x,
+            None => return Ok(Some(data.clone())),
+        };
+        // position is required so 0 is out of range, a 0 vt or vn just means the face has none
+        let in_range = |corner: &[u64; 3]| {
+            (1..=lengths[0]).contains(&corner[0]) && (1..3).all(|i| corner[i] <= lengths[i])
+        };
+        if corners.iter().all(in_range) {
+            return Ok(Some(data.clone()));
+        }
+        
+        let before = corners.clone();
+        let mut fixup = |kind, after| fixups.push(Fixup {
+            kind,
+            line_number: statement.line_number,
+            line_position: statement.line_position,
+            before: before.clone(),
+            after,
+        });
+        let names = ["position", "texture coordinate", "normal"];
+        let (corner, component) = corners.iter()
+            .flat_map(|c| (0..3).map(move |i| (c, i)))
+            .find(|(c, i)| if *i == 0 { !(1..=lengths[0]).contains(&c[0]) } else { c[*i] > lengths[*i] })
+            .expect("An out of range corner exists");
+        let message = format!(
+            "Face references {} index {} but only {} are defined", names[component], corner[component], lengths[component]
+        );
+        
+        match policy {
+            IndexRangePolicy::Error => return Err(message),
+            IndexRangePolicy::DropFace => {
+                warnings.push(Diagnostic::from(format!("{}, face dropped", message), statement.line_number, statement.line_position));
+                fixup(FixupKind::FaceDropped, None);
+                return Ok(None);
+            },
+            IndexRangePolicy::Clamp => {
+                if lengths[0] == 0 {
+                    return Err(message);
+                }
+                for corner in corners.iter_mut() {
+                    corner[0] = corner[0].clamp(1, lengths[0]);
+                    for i in 1..3 {
+                        corner[i] = corner[i].min(lengths[i]);
+                    }
+                }
+                warnings.push(Diagnostic::from(format!("{}, index clamped", message), statement.line_number, statement.line_position));
+                fixup(FixupKind::IndexClamped, Some(corners.clone()));
+            },
+            IndexRangePolicy::Degenerate => {
+                let replacement = match corners.iter().find(|c| in_range(c)) {
+                    Some(x) => *x,
+                    None => return Err(message),
+                };
+                for corner in corners.iter_mut() {
+                    if !in_range(corner) {
+                        *corner = replacement;
+                    }
+                }
+                warnings.push(Diagnostic::from(format!("{}, corner collapsed", message), statement.line_number, statement.line_position));
+                fixup(FixupKind::CornerCollapsed, Some(corners.clone()));
+            },
+        }
+        
+        Ok(StatementDataType::from_face_corners(&corners))
+    }
+    
+    fn handle_line_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        let indices = match &statement.data {
+            StatementDataType::Polyline(x) => x,
+            _ => {return Err(String::from("Line statement did not have position indices"))},
+        };
+        let name = self.current_name();
+        let current_obj = self.cur_obj.get_or_insert_with(|| Object3d::from(name));
+        if !Compiler::is_object_included(&self.options, &current_obj.name) {
+            self.ignore(statement, IgnoredReason::ObjectExcluded);
+            return Ok(());
+        }
+        
+        let mut points = Vec::new();
+        for (i, index) in indices.iter().enumerate() {
+            let index = if self.zero_based && !statement.is_relative_index(i) { index + 1 } else { *index };
+            let position = index.checked_sub(1)
+                .and_then(|i| self.position_buffer.get(i as usize))
+                .ok_or_else(|| format!(
+                    "Line references position index {} but only {} are defined", index, self.position_buffer.len()
+                ))?;
+            points.push(*position);
+        }
+        current_obj.polylines.push(Polyline { points, line_number: statement.line_number });
+        
+        Ok(())
+    }
+    
+    // Shifts 0 based indices to 1 based. A missing vt or vn lexes to 0 as well, so those are only
+    // shifted when the file has any tex coords or normals to reference. Indices the parser
+    // resolved from relative ones are 1 based already
+    fn rebase_face(statement: &Statement, has_tex_coords: bool, has_normals: bool) -> StatementDataType {
+        let shifts = [1, has_tex_coords as u64, has_normals as u64];
+        let corners: Vec<[u64; 3]> = match statement.data.face_corners() {
+            Some(x) => x.iter().enumerate()
+                .map(|(i, c)| [0, 1, 2].map(|k| c[k] + if statement.is_relative_index(i * 3 + k) { 0 } else { shifts[k] }))
+                .collect(),
+            None => return statement.data.clone(),
+        };
+        
+        StatementDataType::from_face_corners(&corners).expect("Rebased face keeps its vertex count")
+    }
+    
+    fn clean_up(&mut self, results: &mut Vec<Object3d>) {
+        self.finish_object(results);
+    }
+    
+    fn finish_object(&mut self, results: &mut Vec<Object3d>) {
         let current_obj = self.cur_obj.take();
         
         if let Some(x) = current_obj {
-            results.push(x);
+            if Compiler::is_object_included(&self.options, &x.name) {
+                let elapsed = self.object_started.map(|t| t.elapsed()).unwrap_or_default();
+                self.object_timings.push((x.name.clone(), elapsed));
+                results.push(x);
+            }
         }
+        self.object_started = Some(Instant::now());
+    }
+    
+    fn is_object_included(options: &CompilerOptions, name: &str) -> bool {
+        let included = options.include_objects.is_empty() ||
+            options.include_objects.iter().any(|pattern| Compiler::matches_pattern(pattern, name));
+        let excluded = options.exclude_objects.iter().any(|pattern| Compiler::matches_pattern(pattern, name));
         
-        Ok(())
+        included && !excluded
+    }
+    
+    fn matches_pattern(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        let (mut p, mut n) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+        
+        while n < name.len() {
+            if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+                p += 1;
+                n += 1;
+            } else if p < pattern.len() && pattern[p] == '*' {
+                backtrack = Some((p, n));
+                p += 1;
+            } else if let Some((star_p, star_n)) = backtrack {
+                // let the last '*' swallow one more character and try again
+                backtrack = Some((star_p, star_n + 1));
+                p = star_p + 1;
+                n = star_n + 1;
+            } else {
+                return false;
+            }
+        }
+        
+        pattern[p..].iter().all(|c| *c == '*')
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::f;
+    use crate::prepass::count_statements;
+    use crate::vertex::VertexFormat;
     use super::*;
     
     #[test]
@@ -125,6 +662,7 @@ mod tests {
                     VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(1.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             }
         );
         
@@ -152,6 +690,7 @@ mod tests {
                     VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(-1.0)),
                 ),
                 index_buffer: vec!(0, 1, 2, 2, 3, 0),
+                ..Object3d::from(String::new())
             }
         );
         
@@ -180,6 +719,7 @@ mod tests {
                     VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(1.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             }
         );
         
@@ -209,6 +749,7 @@ mod tests {
                     VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(1.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             },
             Object3d {
                 name: String::from(object_2_name),
@@ -219,6 +760,7 @@ mod tests {
                     VertexData::vertex_p_from_floats(f!(-1.0), f!(0.0), f!(-1.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             },
         );
         
@@ -248,6 +790,7 @@ mod tests {
                     VertexData::vertex_pn_from_floats(f!(1.0), f!(0.0), f!(1.0), f!(0.0), f!(0.0), f!(1.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             }
         );
         
@@ -275,6 +818,7 @@ mod tests {
                     VertexData::vertex_pt_from_floats(f!(1.0), f!(0.0), f!(1.0), f!(1.0), f!(0.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             }
         );
         
@@ -310,6 +854,7 @@ mod tests {
                     ),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             }
         );
         
@@ -343,6 +888,7 @@ mod tests {
                     VertexData::vertex_p_from_floats(f!(1.0), f!(-1.0), f!(0.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             },
             Object3d {
                 name: object_2_name.clone(),
@@ -353,6 +899,7 @@ mod tests {
                     VertexData::vertex_p_from_floats(f!(-1.0), f!(-1.0), f!(0.0)),
                 ),
                 index_buffer: vec!(0, 1, 2),
+                ..Object3d::from(String::new())
             }
         );
         
@@ -369,6 +916,597 @@ mod tests {
         compile_generates_objects(String::from(file_name), expected_object_list, statements);
     }
     
+    #[test]
+    fn compile_maps_compiled_vertices_back_to_source_indices() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 1),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 2, 1),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 3, 1),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(-1.0)), 4, 1),
+            Statement::from(StatementType::NORMAL, StatementDataType::Number3D(f!(0.0), f!(1.0), f!(0.0)), 5, 1),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 1, 2, 0, 1, 3, 0, 1), 6, 1),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(3, 0, 1, 4, 0, 1, 1, 0, 1), 7, 1),
+        );
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+
+        assert_eq!(
+            vec!(
+                VertexSource { pos_index: 1, tex_coord_index: 0, normal_index: 1, line_number: 6 },
+                VertexSource { pos_index: 2, tex_coord_index: 0, normal_index: 1, line_number: 6 },
+                VertexSource { pos_index: 3, tex_coord_index: 0, normal_index: 1, line_number: 6 },
+                VertexSource { pos_index: 4, tex_coord_index: 0, normal_index: 1, line_number: 7 },
+            ),
+            objects[0].vertex_sources,
+            "Compile maps each compiled vertex to the indices and line that first produced it"
+        );
+    }
+
+    #[test]
+    fn compile_maps_triangles_back_to_face_statements() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 1),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 2, 1),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 3, 1),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(-1.0)), 4, 1),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 5, 1),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(3, 0, 0, 4, 0, 0, 1, 0, 0), 6, 1),
+        );
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+
+        assert_eq!(
+            Some(&FaceSource { line_number: 6, indices: vec!((3, 0, 0), (4, 0, 0), (1, 0, 0)) }),
+            objects[0].triangle_source(1),
+            "Compile maps each triangle to the line and indices of its face statement"
+        );
+        assert_eq!(
+            None,
+            objects[0].triangle_source(2),
+            "Triangle source is none for triangles that don't exist"
+        );
+    }
+
+    #[test]
+    fn compile_skips_objects_matching_exclude_patterns() {
+        let options = CompilerOptions {
+            exclude_objects: vec!(String::from("UCX_*")),
+            ..CompilerOptions::default()
+        };
+        
+        let objects = compile_named_objects(options, vec!("UCX_Crate", "Crate", "Crate_LOD1"));
+        
+        assert_eq!(
+            vec!("Crate", "Crate_LOD1"),
+            objects.iter().map(|o| o.name.as_str()).collect::<Vec<&str>>(),
+            "Compile skips objects matching an exclude pattern"
+        );
+    }
+    
+    #[test]
+    fn compile_keeps_only_objects_matching_include_patterns() {
+        let options = CompilerOptions {
+            include_objects: vec!(String::from("*_LOD?")),
+            exclude_objects: vec!(String::from("*_LOD1")),
+            ..CompilerOptions::default()
+        };
+        
+        let objects = compile_named_objects(options, vec!("Crate_LOD0", "Crate_LOD1", "Crate", "Barrel_LOD0"));
+        
+        assert_eq!(
+            vec!("Crate_LOD0", "Barrel_LOD0"),
+            objects.iter().map(|o| o.name.as_str()).collect::<Vec<&str>>(),
+            "Compile keeps objects matching an include pattern unless they are excluded"
+        );
+    }
+    
+    #[test]
+    fn compile_retains_authored_faces_when_requested() {
+        let options = CompilerOptions {
+            retain_faces: true,
+            ..CompilerOptions::default()
+        };
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(-1.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(0.0), f!(1.0), f!(0.0)), 2, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(-1.0), f!(0.0)), 3, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 4, 0),
+        );
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+
+        assert_eq!(
+            vec!(FaceSource { line_number: 4, indices: vec!((1, 0, 0), (2, 0, 0), (3, 0, 0)) }),
+            objects[0].faces,
+            "Compile retains each authored face when requested"
+        );
+        assert_eq!(3, objects[0].faces[0].arity(), "Retained face reports its arity");
+    }
+    
+    #[test]
+    fn compile_does_not_retain_faces_by_default() {
+        let objects = compile_named_objects(CompilerOptions::default(), vec!("Object1"));
+        
+        assert!(objects[0].faces.is_empty(), "Compile does not retain faces by default");
+    }
+    
+    fn compile_named_objects(options: CompilerOptions, names: Vec<&str>) -> Vec<Object3d> {
+        let mut statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(-1.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(0.0), f!(1.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(-1.0), f!(0.0)), 1, 0),
+        );
+        for name in names {
+            statements.push(Statement::from(StatementType::OBJECT, StatementDataType::String(String::from(name)), 1, 0));
+            statements.push(Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 1, 0));
+        }
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        
+        c.compile(&statements).expect("Compile returns successful result when given valid data")
+    }
+
+    #[test]
+    fn compile_records_active_smoothing_group_per_triangle() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 1, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 1, 0),
+            Statement::from(StatementType::SMOOTHING, StatementDataType::Number(f!(2.0)), 1, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(3, 0, 0, 2, 0, 0, 1, 0, 0), 1, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 3, 0, 0, 2, 0, 0), 1, 0),
+            Statement::from(StatementType::SMOOTHING, StatementDataType::Number(f!(0.0)), 1, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(2, 0, 0, 1, 0, 0, 3, 0, 0), 1, 0),
+        );
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+
+        assert_eq!(
+            vec!(0u32, 2u32, 2u32, 0u32),
+            objects[0].smoothing_groups,
+            "Compile records the active smoothing group for each triangle"
+        );
+    }
+
+    #[test]
+    fn compile_reports_ignored_content() {
+        let statements = vec!(
+            Statement::from(StatementType::COMMENT, StatementDataType::String(String::from("exported")), 1, 0),
+            Statement::from(StatementType::MTLLIB, StatementDataType::String(String::from("scene.mtl")), 2, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 3, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 4, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 5, 0),
+            Statement::from(StatementType::OBJECT, StatementDataType::String(String::from("UCX_Hull")), 6, 0),
+            Statement::from(StatementType::USEMTL, StatementDataType::String(String::from("Stone")), 7, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 8, 0),
+        );
+        let options = CompilerOptions {
+            exclude_objects: vec!(String::from("UCX_*")),
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        
+        c.compile(&statements).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(
+            &[
+                IgnoredContent { statement_type: StatementType::COMMENT, line_number: 1, reason: IgnoredReason::Comment },
+                IgnoredContent { statement_type: StatementType::FACE, line_number: 8, reason: IgnoredReason::ObjectExcluded },
+            ],
+            c.ignored_content(),
+            "Compile lists every statement it dropped and why"
+        );
+        assert_eq!(&[(String::from("scene.mtl"), 2, 0)], c.material_libraries(), "mtllib is kept for the caller to load");
+    }
+    
+    #[test]
+    fn compile_attaches_the_active_material() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 2, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 3, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 4, 0),
+            Statement::from(StatementType::OBJECT, StatementDataType::String(String::from("Wall")), 5, 0),
+            Statement::from(StatementType::USEMTL, StatementDataType::String(String::from("Stone")), 6, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 7, 0),
+            Statement::from(StatementType::OBJECT, StatementDataType::String(String::from("Floor")), 8, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(3, 0, 0, 2, 0, 0, 1, 0, 0), 9, 0),
+        );
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(None, objects[0].material, "Faces before any usemtl have no material");
+        assert_eq!(Some(String::from("Stone")), objects[1].material);
+        assert_eq!(Some(String::from("Stone")), objects[2].material, "usemtl carries over into the next object");
+    }
+    
+    #[test]
+    fn compile_records_sub_meshes_for_material_changes() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 2, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 3, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(-1.0)), 4, 0),
+            Statement::from(StatementType::USEMTL, StatementDataType::String(String::from("Stone")), 5, 0),
+            Statement::from(StatementType::FACE, StatementDataType::QuadPTN(1, 0, 0, 2, 0, 0, 3, 0, 0, 4, 0, 0), 6, 0),
+            Statement::from(StatementType::USEMTL, StatementDataType::String(String::from("Moss")), 7, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 8, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(3, 0, 0, 2, 0, 0, 1, 0, 0), 9, 0),
+            Statement::from(StatementType::USEMTL, StatementDataType::String(String::from("Stone")), 10, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 3, 0, 0, 4, 0, 0), 11, 0),
+        );
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(
+            vec!(
+                SubMesh { material: Some(String::from("Stone")), index_start: 0, index_count: 6 },
+                SubMesh { material: Some(String::from("Moss")), index_start: 6, index_count: 6 },
+                SubMesh { material: Some(String::from("Stone")), index_start: 12, index_count: 3 },
+            ),
+            objects[0].sub_meshes,
+            "Each usemtl change starts a new range"
+        );
+        assert_eq!(15, objects[0].index_buffer.len());
+    }
+    
+    #[test]
+    fn compile_reads_zero_based_indices_when_configured() {
+        let options = CompilerOptions {
+            index_base: IndexBase::Zero,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        
+        let objects = c.compile(&zero_based_triangle()).expect("Compile accepts 0 based indices in zero mode");
+        
+        assert_eq!(
+            vec!(
+                VertexData::vertex_pn_from_floats(f!(-1.0), f!(0.0), f!(-1.0), f!(0.0), f!(1.0), f!(0.0)),
+                VertexData::vertex_pn_from_floats(f!(-1.0), f!(0.0), f!(1.0), f!(0.0), f!(1.0), f!(0.0)),
+                VertexData::vertex_pn_from_floats(f!(1.0), f!(0.0), f!(1.0), f!(0.0), f!(1.0), f!(0.0)),
+            ),
+            objects[0].vertex_buffer,
+            "Positions and normals are shifted but the missing tex coord stays missing"
+        );
+    }
+    
+    #[test]
+    fn compile_detects_zero_based_indices_in_auto_mode() {
+        assert_eq!(IndexBase::Zero, Compiler::detect_index_base(&zero_based_triangle()));
+        
+        let options = CompilerOptions {
+            index_base: IndexBase::Auto,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        
+        let objects = c.compile(&zero_based_triangle()).expect("Compile detects 0 based indices");
+        
+        assert_eq!(3, objects[0].vertex_buffer.len(), "Detected 0 based file compiles");
+    }
+    
+    #[test]
+    fn detect_index_base_keeps_one_based_files() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 1, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 1, 0),
+        );
+        
+        assert_eq!(IndexBase::One, Compiler::detect_index_base(&statements), "Files referencing the last position are 1 based");
+    }
+    
+    fn zero_based_triangle() -> Vec<Statement> {
+        vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 1, 0),
+            Statement::from(StatementType::NORMAL, StatementDataType::Number3D(f!(0.0), f!(1.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(0, 0, 0, 1, 0, 0, 2, 0, 0), 1, 0),
+        )
+    }
+    
+    #[test]
+    fn compile_errors_on_out_of_range_index_by_default() {
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let result = c.compile(&out_of_range_faces());
+        
+        assert_eq!(
+            Err(String::from("Face references position index 7 but only 3 are defined")),
+            result.map(|_| ()).map_err(|e| e.message()),
+            "Out of range indices are an error by default"
+        );
+    }
+    
+    #[test]
+    fn compile_errors_locate_the_vertex_format_change() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(0.0), f!(0.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::TEXCOORD, StatementDataType::Number2D(f!(0.0), f!(0.0)), 2, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 1, 0, 0, 1, 0, 0), 3, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 1, 0, 1, 1, 0, 1, 1, 0), 4, 2),
+        );
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let error = c.compile(&statements).expect_err("Objects can't mix vertex formats");
+        
+        assert_eq!(
+            "4:2 in face statement: Compilation error: Unexpected vertex format change from VertexP to VertexPT",
+            error.to_string()
+        );
+    }
+    
+    #[test]
+    fn compile_errors_carry_the_statement_location() {
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let error = c.compile(&out_of_range_faces()).expect_err("Out of range indices are an error by default");
+        
+        assert_eq!(Some((5, 0)), error.location());
+        assert!(matches!(error, WfoError::CompileError { statement: StatementType::FACE, .. }), "{:?}", error);
+    }
+    
+    #[test]
+    fn compile_drops_out_of_range_faces_when_configured() {
+        let (objects, warnings) = compile_with_range_policy(IndexRangePolicy::DropFace);
+        
+        assert_eq!(vec!(0u64, 1, 2), objects[0].index_buffer, "Only the valid face is compiled");
+        assert_eq!(1, warnings.len(), "Dropped face is reported");
+        assert_eq!(5, warnings[0].line_number, "Warning points at the dropped face");
+    }
+    
+    #[test]
+    fn compile_clamps_out_of_range_indices_when_configured() {
+        let (objects, warnings) = compile_with_range_policy(IndexRangePolicy::Clamp);
+        
+        assert_eq!(3, objects[0].vertex_buffer.len(), "Clamped index reuses the last position");
+        assert_eq!(vec!(0u64, 1, 2, 0, 1, 2), objects[0].index_buffer);
+        assert_eq!(1, warnings.len(), "Clamped face is reported");
+    }
+    
+    #[test]
+    fn compile_collapses_out_of_range_corners_when_configured() {
+        let (objects, warnings) = compile_with_range_policy(IndexRangePolicy::Degenerate);
+        
+        assert_eq!(vec!(0u64, 1, 2, 0, 1, 0), objects[0].index_buffer, "Bad corner is replaced by the first valid corner");
+        assert_eq!(1, warnings.len(), "Collapsed face is reported");
+    }
+    
+    #[test]
+    fn compile_records_before_and_after_of_each_fixup() {
+        let options = CompilerOptions {
+            index_range_policy: IndexRangePolicy::Clamp,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        c.compile(&out_of_range_faces()).expect("Compile repairs out of range faces");
+        
+        assert_eq!(
+            vec!(Fixup {
+                kind: FixupKind::IndexClamped,
+                line_number: 5,
+                line_position: 0,
+                before: vec!([1, 0, 0], [2, 0, 0], [7, 0, 0]),
+                after: Some(vec!([1, 0, 0], [2, 0, 0], [3, 0, 0])),
+            }),
+            c.fixups()
+        );
+    }
+    
+    #[test]
+    fn compile_records_dropped_faces_as_fixups_without_after() {
+        let options = CompilerOptions {
+            index_range_policy: IndexRangePolicy::DropFace,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        c.compile(&out_of_range_faces()).expect("Compile drops out of range faces");
+        
+        assert_eq!(FixupKind::FaceDropped, c.fixups()[0].kind);
+        assert_eq!(None, c.fixups()[0].after, "A dropped face has nothing after");
+    }
+    
+    #[test]
+    fn compile_with_salvage_returns_objects_finished_before_failure() {
+        let mut statements = vec!(
+            Statement::from(StatementType::OBJECT, StatementDataType::String(String::from("Good")), 1, 0),
+        );
+        statements.append(&mut out_of_range_faces());
+        statements.insert(5, Statement::from(StatementType::OBJECT, StatementDataType::String(String::from("Broken")), 4, 0));
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let (objects, error) = c.compile_with_salvage(&statements);
+        
+        assert_eq!(1, objects.len(), "Objects finished before the failure are returned");
+        assert_eq!("Good", objects[0].name);
+        assert_eq!(Some((5, 0)), error.expect("Failure is reported").location(), "The error points at the failing statement");
+    }
+    
+    #[test]
+    fn compile_with_salvage_returns_every_object_without_failure() {
+        let mut statements = out_of_range_faces();
+        statements.pop();
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let (objects, error) = c.compile_with_salvage(&statements);
+        
+        assert_eq!(1, objects.len(), "The trailing object is finished");
+        assert!(error.is_none(), "Valid input reports no failure");
+    }
+    
+    #[test]
+    fn compile_attaches_second_uv_set_by_tex_coord_index() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 1, 0),
+            Statement::from(StatementType::TEXCOORD, StatementDataType::Number2D(f!(0.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::TEXCOORD, StatementDataType::Number2D(f!(1.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::TEXCOORD2, StatementDataType::Number2D(f!(0.5), f!(0.5)), 1, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 1, 0, 2, 2, 0, 3, 2, 0), 1, 0),
+        );
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+        
+        let uv2: Vec<Option<[f32; 2]>> = objects[0].vertices().map(|v| v.uv2()).collect();
+        assert_eq!(vec!(Some([0.5, 0.5]), None, None), uv2, "vt2 entries pair with vt entries of the same index");
+    }
+    
+    #[test]
+    fn reserve_sizes_every_attribute_buffer() {
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        c.reserve(&count_statements(b"v 0 0 0\nv 1 0 0\nvn 0 1 0\nvt 0 0\nvt2 0 0\nvt2 1 1\n"));
+        
+        assert!(c.position_buffer.capacity() >= 2);
+        assert!(c.normal_buffer.capacity() >= 1);
+        assert!(c.tex_coord_buffer.capacity() >= 1);
+        assert!(c.tex_coord2_buffer.capacity() >= 2, "vt2 lines are counted for the second uv set");
+    }
+    
+    #[test]
+    fn compile_fan_triangulates_quads() {
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 2, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 3, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(-1.0)), 4, 0),
+            Statement::from(StatementType::FACE, StatementDataType::QuadPTN(1, 0, 0, 2, 0, 0, 3, 0, 0, 4, 0, 0), 5, 0),
+        );
+        let options = CompilerOptions {
+            retain_faces: true,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(vec!(0u64, 1, 2, 0, 2, 3), objects[0].index_buffer, "Quad becomes two triangles sharing a diagonal");
+        assert_eq!(2, objects[0].triangle_sources.len(), "Both triangles point back at the quad");
+        assert_eq!(4, objects[0].triangle_source(1).unwrap().arity());
+        assert_eq!(1, objects[0].faces.len(), "The authored quad is retained once");
+    }
+    
+    #[test]
+    fn compile_ignores_groups_by_default() {
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        
+        let objects = c.compile(&grouped_statements()).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(vec!("Car"), objects.iter().map(|o| o.name.as_str()).collect::<Vec<&str>>());
+        assert_eq!(2, c.ignored_content().iter().filter(|i| i.reason == IgnoredReason::NotASplitPoint).count());
+    }
+    
+    #[test]
+    fn compile_splits_by_groups_when_configured() {
+        let options = CompilerOptions {
+            split_by: ObjectSplit::Groups,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        
+        let objects = c.compile(&grouped_statements()).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(vec!("Body", "Wheels"), objects.iter().map(|o| o.name.as_str()).collect::<Vec<&str>>());
+    }
+    
+    #[test]
+    fn compile_splits_by_objects_and_groups_when_configured() {
+        let options = CompilerOptions {
+            split_by: ObjectSplit::ObjectsAndGroups,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        
+        let objects = c.compile(&grouped_statements()).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(
+            vec!("Car", "Car/Body", "Car/Wheels"),
+            objects.iter().map(|o| o.name.as_str()).collect::<Vec<&str>>(),
+            "The object is split at each group"
+        );
+        assert!(objects[0].index_buffer.is_empty(), "Nothing was drawn before the first group");
+    }
+    
+    #[test]
+    fn compile_keeps_lines_as_polylines() {
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(0.0), f!(0.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(0.0)), 2, 0),
+            Statement::from(StatementType::LINE, StatementDataType::Polyline(vec!(1, 2, 1)), 3, 0),
+        );
+        
+        let objects = c.compile(&statements).expect("Compile returns successful result when given valid data");
+        
+        assert_eq!(
+            vec!(Polyline {
+                points: vec!((f!(0.0), f!(0.0), f!(0.0)), (f!(1.0), f!(0.0), f!(0.0)), (f!(0.0), f!(0.0), f!(0.0))),
+                line_number: 3,
+            }),
+            objects[0].polylines
+        );
+        assert!(objects[0].index_buffer.is_empty(), "Lines don't produce triangles");
+    }
+    
+    #[test]
+    fn compile_rejects_lines_past_the_last_position() {
+        let mut c = Compiler::from_default_name(&String::from("test.obj"));
+        let statements = vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(0.0), f!(0.0), f!(0.0)), 1, 0),
+            Statement::from(StatementType::LINE, StatementDataType::Polyline(vec!(1, 2)), 2, 0),
+        );
+        
+        assert_eq!(
+            Some(String::from("Line references position index 2 but only 1 are defined")),
+            c.compile(&statements).err().map(|e| e.message())
+        );
+    }
+    
+    fn grouped_statements() -> Vec<Statement> {
+        vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 2, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 3, 0),
+            Statement::from(StatementType::OBJECT, StatementDataType::String(String::from("Car")), 4, 0),
+            Statement::from(StatementType::GROUP, StatementDataType::String(String::from("Body")), 5, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 6, 0),
+            Statement::from(StatementType::GROUP, StatementDataType::String(String::from("Wheels")), 7, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(3, 0, 0, 2, 0, 0, 1, 0, 0), 8, 0),
+        )
+    }
+    
+    fn out_of_range_faces() -> Vec<Statement> {
+        vec!(
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0), f!(-1.0)), 1, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(-1.0), f!(0.0),  f!(1.0)), 2, 0),
+            Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(0.0), f!(1.0)), 3, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 4, 0),
+            Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 7, 0, 0), 5, 0),
+        )
+    }
+    
+    fn compile_with_range_policy(policy: IndexRangePolicy) -> (Vec<Object3d>, Vec<Diagnostic>) {
+        let options = CompilerOptions {
+            index_range_policy: policy,
+            ..Default::default()
+        };
+        let mut c = Compiler::from_default_name_and_options(&String::from("test.obj"), options);
+        let objects = c.compile(&out_of_range_faces()).expect("Compile repairs out of range faces");
+        
+        (objects, c.warnings().to_vec())
+    }
+    
     fn compile_generates_objects(
         file_name: String, 
         expected_object_list: Vec<Object3d>, 
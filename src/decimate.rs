@@ -0,0 +1,300 @@
+use std::collections::{HashMap, HashSet};
+use crate::geometry::{dot, normalize, sub, triangle_cross};
+use crate::object3d::{Object3d, SubMesh};
+
+// How close two triangles must be to count as lying in the same plane. CAD tessellations are flat
+// up to float rounding, so the defaults are tight
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct PlanarTolerance {
+    // smallest cosine allowed between the unit normals
+    pub normal_cosine: f32,
+    // largest distance of a vertex from the region's plane
+    pub distance: f32,
+}
+
+impl Default for PlanarTolerance {
+    fn default() -> Self {
+        PlanarTolerance {
+            normal_cosine: 0.9999,
+            distance: 1e-5,
+        }
+    }
+}
+
+impl Object3d {
+    // Merges edge connected coplanar triangles into polygons and triangulates each polygon again
+    // with as few triangles as its outline allows. Only edges that share both vertex indices count,
+    // so uv and normal seams are never merged across, and triangles in different smoothing groups or
+    // sub meshes stay apart. Outline vertices used by triangles outside the region are kept so no T-junctions
+    // appear. Regions with holes or pinched outlines are left as they are. Vertices that end up
+    // unused stay in the vertex buffer. Returns how many triangles were removed
+    pub fn merge_coplanar_faces(&mut self, tolerance: &PlanarTolerance) -> usize {
+        let positions: Vec<[f32; 3]> = self.vertices().map(|v| v.pos()).collect();
+        let triangles: Vec<[u64; 3]> = self.index_buffer.chunks(3).map(|t| [t[0], t[1], t[2]]).collect();
+        let normals: Vec<[f32; 3]> = triangles.iter()
+            .map(|t| normalize(triangle_cross(positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize])))
+            .collect();
+        let mut edges: HashMap<(u64, u64), usize> = HashMap::new();
+        for (triangle, t) in triangles.iter().enumerate() {
+            for corner in 0..3 {
+                edges.insert((t[corner], t[(corner + 1) % 3]), triangle);
+            }
+        }
+        let mut uses = vec![0usize; positions.len()];
+        for index in &self.index_buffer {
+            uses[*index as usize] += 1;
+        }
+
+        let mut region_of = vec![usize::MAX; triangles.len()];
+        let mut index_buffer = Vec::with_capacity(self.index_buffer.len());
+        let mut triangle_sources = Vec::new();
+        let mut smoothing_groups = Vec::new();
+        let materials: Vec<Option<String>> = SubMesh::triangle_materials(&self.sub_meshes).into_iter().cloned().collect();
+        let mut sub_meshes = Vec::new();
+        for seed in 0..triangles.len() {
+            if region_of[seed] != usize::MAX {
+                continue;
+            }
+
+            let region = self.coplanar_region(seed, &triangles, &normals, &positions, &edges, &materials, tolerance, &mut region_of);
+            let merged = match region.len() {
+                1 => None,
+                _ => outline(&region, &triangles)
+                    .map(|loop_| drop_collinear(loop_, &region, &triangles, &uses, &positions))
+                    .and_then(|loop_| ear_clip(&loop_, &positions, normals[seed]))
+                    .filter(|merged| merged.len() < region.len()),
+            };
+            let new_triangles = merged.unwrap_or_else(|| region.iter().map(|t| triangles[*t]).collect());
+
+            for t in &new_triangles {
+                index_buffer.extend_from_slice(t);
+                if let Some(source) = self.triangle_sources.get(seed) {
+                    triangle_sources.push(source.clone());
+                }
+                if let Some(group) = self.smoothing_groups.get(seed) {
+                    smoothing_groups.push(*group);
+                }
+                if let Some(material) = materials.get(seed) {
+                    SubMesh::extend_ranges(&mut sub_meshes, material, 3);
+                }
+            }
+        }
+
+        let removed = triangles.len() - index_buffer.len() / 3;
+        self.index_buffer = index_buffer;
+        self.triangle_sources = triangle_sources;
+        self.smoothing_groups = smoothing_groups;
+        self.sub_meshes = sub_meshes;
+
+        removed
+    }
+
+    // Flood fills across shared edges from seed, in triangle order
+    #[allow(clippy::too_many_arguments)]
+    fn coplanar_region(
+        &self,
+        seed: usize,
+        triangles: &[[u64; 3]],
+        normals: &[[f32; 3]],
+        positions: &[[f32; 3]],
+        edges: &HashMap<(u64, u64), usize>,
+        materials: &[Option<String>],
+        tolerance: &PlanarTolerance,
+        region_of: &mut [usize],
+    ) -> Vec<usize> {
+        region_of[seed] = seed;
+        let normal = normals[seed];
+        // zero area triangles have no plane to share
+        if normal == [0.0; 3] {
+            return vec!(seed);
+        }
+        let origin = positions[triangles[seed][0] as usize];
+        let group = self.smoothing_groups.get(seed);
+
+        let mut region = vec!(seed);
+        let mut next = 0;
+        while next < region.len() {
+            let t = triangles[region[next]];
+            next += 1;
+            for corner in 0..3 {
+                let neighbor = match edges.get(&(t[(corner + 1) % 3], t[corner])) {
+                    Some(x) if region_of[*x] == usize::MAX => *x,
+                    _ => continue,
+                };
+                let in_plane = triangles[neighbor].iter()
+                    .all(|i| dot(sub(positions[*i as usize], origin), normal).abs() <= tolerance.distance);
+                if dot(normals[neighbor], normal) >= tolerance.normal_cosine && in_plane
+                    && self.smoothing_groups.get(neighbor) == group && materials.get(neighbor) == materials.get(seed) {
+                    region_of[neighbor] = seed;
+                    region.push(neighbor);
+                }
+            }
+        }
+        region.sort();
+
+        region
+    }
+}
+
+// The single closed loop of edges that only one triangle of the region uses, in winding order.
+// None for regions with holes or outlines that touch themselves
+fn outline(region: &[usize], triangles: &[[u64; 3]]) -> Option<Vec<u64>> {
+    let region_edges: HashSet<(u64, u64)> = region.iter()
+        .flat_map(|t| (0..3).map(move |corner| (triangles[*t][corner], triangles[*t][(corner + 1) % 3])))
+        .collect();
+    let mut next: HashMap<u64, u64> = HashMap::new();
+    for (a, b) in &region_edges {
+        if !region_edges.contains(&(*b, *a)) && next.insert(*a, *b).is_some() {
+            return None;
+        }
+    }
+
+    let start = *next.keys().min()?;
+    let mut loop_ = vec!(start);
+    let mut current = next[&start];
+    while current != start {
+        loop_.push(current);
+        current = *next.get(&current)?;
+        if loop_.len() > next.len() {
+            return None;
+        }
+    }
+
+    (loop_.len() == next.len()).then_some(loop_)
+}
+
+// Removes outline vertices that sit on a straight run and aren't used outside the region
+fn drop_collinear(loop_: Vec<u64>, region: &[usize], triangles: &[[u64; 3]], uses: &[usize], positions: &[[f32; 3]]) -> Vec<u64> {
+    let mut region_uses: HashMap<u64, usize> = HashMap::new();
+    for t in region {
+        for index in triangles[*t] {
+            *region_uses.entry(index).or_insert(0) += 1;
+        }
+    }
+
+    let mut kept = loop_;
+    let mut i = 0;
+    while i < kept.len() && kept.len() > 3 {
+        let prev = positions[kept[(i + kept.len() - 1) % kept.len()] as usize];
+        let current = positions[kept[i] as usize];
+        let next = positions[kept[(i + 1) % kept.len()] as usize];
+        let straight = dot(normalize(sub(current, prev)), normalize(sub(next, current))) > 0.99999;
+        if straight && uses[kept[i] as usize] == region_uses[&kept[i]] {
+            kept.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    kept
+}
+
+// Triangulates a simple polygon by repeatedly cutting off convex corners that contain no other
+// outline vertex. None when no such corner is left, which only happens for invalid outlines
+fn ear_clip(loop_: &[u64], positions: &[[f32; 3]], normal: [f32; 3]) -> Option<Vec<[u64; 3]>> {
+    // project onto the plane the normal points out of the most, keeping counter-clockwise winding
+    let axis = (0..3).max_by(|a, b| normal[*a].abs().total_cmp(&normal[*b].abs()))?;
+    let (u, v) = if normal[axis] > 0.0 { ((axis + 1) % 3, (axis + 2) % 3) } else { ((axis + 2) % 3, (axis + 1) % 3) };
+    let flat = |i: u64| [positions[i as usize][u], positions[i as usize][v]];
+    let turn = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+
+    let mut remaining = loop_.to_vec();
+    let mut result = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|i| {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[*i], remaining[(i + 1) % n]);
+            let (pa, pb, pc) = (flat(a), flat(b), flat(c));
+            turn(pa, pb, pc) > 0.0 && remaining.iter()
+                .filter(|x| ![a, b, c].contains(x))
+                .all(|x| {
+                    let p = flat(*x);
+                    turn(pa, pb, p) < 0.0 || turn(pb, pc, p) < 0.0 || turn(pc, pa, p) < 0.0
+                })
+        })?;
+        result.push([remaining[(ear + n - 1) % n], remaining[ear], remaining[(ear + 1) % n]]);
+        remaining.remove(ear);
+    }
+    result.push([remaining[0], remaining[1], remaining[2]]);
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{float, object};
+    use crate::vertex::VertexData;
+    use super::*;
+
+    // Adds the quads of a grid given its corners row by row, columns squares wide
+    fn grid(obj: &mut Object3d, corners: &[[f32; 3]], columns: usize) {
+        let first = obj.vertex_buffer.len() as u64;
+        for c in corners {
+            obj.vertex_buffer.push(VertexData::vertex_p_from_floats(float(c[0] as f64), float(c[1] as f64), float(c[2] as f64)));
+        }
+        let rows = corners.len() / (columns + 1) - 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + (row * (columns + 1) + column) as u64;
+                let (b, c, d) = (a + 1, a + columns as u64 + 2, a + columns as u64 + 1);
+                obj.index_buffer.extend_from_slice(&[a, b, c, a, c, d]);
+            }
+        }
+    }
+
+    fn triangle_normals(obj: &Object3d) -> Vec<[f32; 3]> {
+        let positions: Vec<[f32; 3]> = obj.vertices().map(|v| v.pos()).collect();
+
+        obj.index_buffer.chunks(3)
+            .map(|t| normalize(triangle_cross(positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize])))
+            .collect()
+    }
+
+    fn empty() -> Object3d {
+        object("Plate", Vec::new(), &[])
+    }
+
+    #[test]
+    fn merge_coplanar_faces_collapses_flat_grids() {
+        let mut obj = empty();
+        let corners: Vec<[f32; 3]> = (0..3).flat_map(|y| (0..3).map(move |x| [x as f32, y as f32, 0.0])).collect();
+        grid(&mut obj, &corners, 2);
+        obj.smoothing_groups = vec![0; 8];
+
+        assert_eq!(6, obj.merge_coplanar_faces(&PlanarTolerance::default()));
+        assert_eq!(6, obj.index_buffer.len(), "The 2x2 grid becomes one quad");
+        assert_eq!(2, obj.smoothing_groups.len(), "Per triangle data follows the new triangles");
+        assert!(triangle_normals(&obj).iter().all(|n| *n == [0.0, 0.0, 1.0]), "Winding is preserved");
+        assert_eq!(0, obj.merge_coplanar_faces(&PlanarTolerance::default()), "Nothing is left to merge");
+    }
+
+    #[test]
+    fn merge_coplanar_faces_keeps_vertices_shared_with_other_regions() {
+        let mut obj = empty();
+        // a floor and a wall meeting along y = 0, both two squares wide
+        grid(&mut obj, &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [2.0, 0.0, 1.0]], 2);
+        grid(&mut obj, &[[0.0, -1.0, 0.0], [1.0, -1.0, 0.0], [2.0, -1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]], 2);
+        // weld the floor's far edge onto the wall's bottom edge
+        for index in obj.index_buffer.iter_mut() {
+            if *index >= 9 {
+                *index -= 9;
+            }
+        }
+
+        assert_eq!(2, obj.merge_coplanar_faces(&PlanarTolerance::default()));
+        assert!(obj.index_buffer.chunks(3).filter(|t| t.contains(&1)).count() >= 2, "The shared midpoint is still used on both sides");
+        assert!(!obj.index_buffer.contains(&4), "The wall's top midpoint is dropped");
+        assert!(!obj.index_buffer.contains(&7), "The floor's near midpoint is dropped");
+    }
+
+    #[test]
+    fn merge_coplanar_faces_leaves_folds_beyond_the_tolerance_alone() {
+        let mut obj = empty();
+        grid(&mut obj, &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.001], [2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.001], [2.0, 1.0, 0.0]], 2);
+        let index_buffer = obj.index_buffer.clone();
+
+        assert_eq!(0, obj.merge_coplanar_faces(&PlanarTolerance::default()), "Halves of one quad can't get any smaller");
+        assert_eq!(index_buffer, obj.index_buffer);
+        assert_eq!(2, obj.merge_coplanar_faces(&PlanarTolerance { normal_cosine: 0.99, distance: 0.01 }), "A looser tolerance flattens the fold");
+    }
+}
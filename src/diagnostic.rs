@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum DiagnosticKind {
+    Error,
+    // The input looks cut off, e.g. an interrupted download, rather than malformed
+    TruncatedInput,
+    // Nothing was wrong with the input but some of it was ignored, e.g. an unknown keyword
+    Warning,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub message: String,
+    pub line_number: u64,
+    pub line_position: u64,
+    // the file the diagnostic is about when it isn't the input itself, e.g. a material library
+    // that couldn't be loaded. The location is still in the input
+    pub path: Option<PathBuf>,
+}
+
+impl Diagnostic {
+    pub(crate) fn from(
+        message: String,
+        line_number: u64,
+        line_position: u64,
+    ) -> Diagnostic {
+        Diagnostic {
+            kind: DiagnosticKind::Error,
+            message,
+            line_number,
+            line_position,
+            path: None,
+        }
+    }
+    
+    pub(crate) fn truncated_input(
+        message: String,
+        line_number: u64,
+        line_position: u64,
+    ) -> Diagnostic {
+        Diagnostic {
+            kind: DiagnosticKind::TruncatedInput,
+            message,
+            line_number,
+            line_position,
+            path: None,
+        }
+    }
+
+    pub(crate) fn warning(
+        message: String,
+        line_number: u64,
+        line_position: u64,
+    ) -> Diagnostic {
+        Diagnostic {
+            kind: DiagnosticKind::Warning,
+            message,
+            line_number,
+            line_position,
+            path: None,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{Read, Write};
+use crate::compiler::Compiler;
+use crate::error::WfoError;
+use crate::lexer::Lexer;
+use crate::object3d::Object3d;
+use crate::parser::Parser;
+use crate::statement::{Statement, StatementDataType};
+use crate::token::{Token, TokenDataType};
+
+// Writes a canonical, line oriented dump of every pipeline stage (tokens, statements, compiled
+// objects) for snapshot testing. The output only depends on the input bytes so it can be diffed
+// against a checked in golden file
+pub fn debug_dump<R: Read, W: Write>(stream: &mut R, writer: &mut W) -> Result<(), WfoError> {
+    let write_error = |source| WfoError::IoError { path: None, source };
+    let tokens = Lexer::new().lex_input(stream)?;
+    write_tokens(&tokens, writer).map_err(write_error)?;
+
+    let statements = Parser::new().parse_tokens(&tokens)?;
+    write_statements(&statements, writer).map_err(write_error)?;
+
+    let objects = Compiler::from_default_name(&String::from("default")).compile(&statements)?;
+    write_objects(&objects, writer).map_err(write_error)
+}
+
+fn write_tokens<W: Write>(tokens: &[Token], writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "tokens {}", tokens.len())?;
+    for token in tokens {
+        write!(writer, "  {}:{} {}", token.line_number, token.line_position, token.token_type)?;
+        match &token.data {
+            TokenDataType::String(s) => writeln!(writer, " {:?}", s)?,
+            TokenDataType::Number(n) => writeln!(writer, " {}", n)?,
+            TokenDataType::VertexPTN(p, t, n) => writeln!(writer, " {}/{}/{}", p, t, n)?,
+            TokenDataType::None() => writeln!(writer)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn write_statements<W: Write>(statements: &[Statement], writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "statements {}", statements.len())?;
+    for statement in statements {
+        write!(writer, "  {}:{} {}", statement.line_number, statement.line_position, statement.statement_type)?;
+        match &statement.data {
+            StatementDataType::String(s) => writeln!(writer, " {:?}", s)?,
+            StatementDataType::Number4D(x, y, z, w) => writeln!(writer, " {} {} {} {}", x, y, z, w)?,
+            StatementDataType::Number3D(x, y, z) => writeln!(writer, " {} {} {}", x, y, z)?,
+            StatementDataType::Number2D(x, y) => writeln!(writer, " {} {}", x, y)?,
+            StatementDataType::Number(x) => writeln!(writer, " {}", x)?,
+            StatementDataType::FacePTN(..) | StatementDataType::QuadPTN(..) => {
+                for corner in statement.data.face_corners().unwrap_or_default() {
+                    write!(writer, " {}/{}/{}", corner[0], corner[1], corner[2])?;
+                }
+                writeln!(writer)?
+            },
+            StatementDataType::Polyline(indices) => {
+                for index in indices {
+                    write!(writer, " {}", index)?;
+                }
+                writeln!(writer)?
+            },
+            StatementDataType::None() => writeln!(writer)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn write_objects<W: Write>(objects: &[Object3d], writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "objects {}", objects.len())?;
+    for object in objects {
+        writeln!(
+            writer,
+            "  object {:?} {:?} vertices {} indices {}",
+            object.name, object.format, object.vertex_buffer.len(), object.index_buffer.len()
+        )?;
+        if let Some(material) = &object.material {
+            writeln!(writer, "    material {:?}", material)?;
+        }
+        for (i, vertex) in object.vertices().enumerate() {
+            write!(writer, "    v{} pos {:?}", i, vertex.pos())?;
+            if let Some(normal) = vertex.normal() {
+                write!(writer, " normal {:?}", normal)?;
+            }
+            if let Some(uv) = vertex.uv() {
+                write!(writer, " uv {:?}", uv)?;
+            }
+            writeln!(writer)?;
+        }
+        for triangle in object.index_buffer.chunks(3) {
+            writeln!(writer, "    tri {:?}", triangle)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_dump_writes_every_pipeline_stage() {
+        let mut output: Vec<u8> = Vec::new();
+
+        debug_dump(&mut "o Tri\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1/ 2/1/ 3/1/\n".as_bytes(), &mut output)
+            .expect("Dump succeeds for valid input");
+        let dump = String::from_utf8(output).expect("Dump is valid utf-8");
+
+        assert!(dump.starts_with("tokens "), "Dump starts with the token stage");
+        assert!(dump.contains("statements 6\n"), "Dump counts statements");
+        assert!(dump.contains("  1:1 object \"Tri\"\n"), "Statements carry their positions");
+        assert!(dump.contains("objects 1\n  object \"Tri\" VertexPT vertices 3 indices 3\n"), "Dump summarizes objects");
+        assert!(dump.ends_with("    tri [0, 1, 2]\n"), "Dump ends with the compiled triangles");
+    }
+
+    #[test]
+    fn debug_dump_is_deterministic() {
+        let input = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n";
+        let mut first: Vec<u8> = Vec::new();
+        let mut second: Vec<u8> = Vec::new();
+
+        debug_dump(&mut input.as_bytes(), &mut first).expect("Dump succeeds");
+        debug_dump(&mut input.as_bytes(), &mut second).expect("Dump succeeds");
+
+        assert_eq!(first, second, "Dumping the same input twice produces identical output");
+    }
+
+    #[test]
+    fn debug_dump_reports_parse_errors() {
+        let mut output: Vec<u8> = Vec::new();
+
+        let result = debug_dump(&mut "v 1.0\n".as_bytes(), &mut output);
+
+        assert!(matches!(result, Err(WfoError::ParseError { .. })), "Parse errors are returned");
+        assert!(String::from_utf8(output).unwrap().starts_with("tokens "), "Tokens are dumped before the error");
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::path::PathBuf;
+use crate::statement::StatementType;
+
+// Errors from loading OBJ input, by the pipeline stage that failed. Locations are 1 based lines
+// and byte positions within the line, like Diagnostic. Line 0 means the error isn't tied to a line
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug)]
+pub enum WfoError {
+    LexError {
+        message: String,
+        line_number: u64,
+        line_position: u64,
+    },
+    ParseError {
+        message: String,
+        line_number: u64,
+        line_position: u64,
+        // the statement being parsed, none when the error is at the start of a line
+        statement: Option<StatementType>,
+    },
+    CompileError {
+        message: String,
+        line_number: u64,
+        line_position: u64,
+        statement: StatementType,
+    },
+    // The input ends in the middle of a statement, e.g. an interrupted download
+    TruncatedInput {
+        message: String,
+        line_number: u64,
+        line_position: u64,
+        statement: Option<StatementType>,
+    },
+    // An error in a material library. The location is a line of the library, not of the OBJ input,
+    // and path is the library file when it was loaded from one
+    MtlError {
+        message: String,
+        line_number: u64,
+        line_position: u64,
+        path: Option<PathBuf>,
+    },
+    // An argument or a plugged in component rejected the request, e.g. lines outside the input
+    InvalidArgument {
+        message: String,
+    },
+    // path is none when reading from a stream rather than opening a file failed
+    IoError {
+        path: Option<PathBuf>,
+        source: std::io::Error,
+    },
+}
+
+impl WfoError {
+    // The error without its location
+    pub fn message(&self) -> String {
+        match self {
+            WfoError::LexError { message, .. } => message.clone(),
+            WfoError::ParseError { message, .. } => message.clone(),
+            WfoError::CompileError { message, .. } => message.clone(),
+            WfoError::TruncatedInput { message, .. } => message.clone(),
+            WfoError::MtlError { message, .. } => message.clone(),
+            WfoError::InvalidArgument { message } => message.clone(),
+            WfoError::IoError { path: Some(path), source } => format!("Could not open {}: {}", path.display(), source),
+            WfoError::IoError { path: None, source } => format!("Could not read input: {}", source),
+        }
+    }
+
+    // Line number and position, none for errors that aren't about a place in the input
+    pub fn location(&self) -> Option<(u64, u64)> {
+        let location = match self {
+            WfoError::LexError { line_number, line_position, .. } => (*line_number, *line_position),
+            WfoError::ParseError { line_number, line_position, .. } => (*line_number, *line_position),
+            WfoError::CompileError { line_number, line_position, .. } => (*line_number, *line_position),
+            WfoError::TruncatedInput { line_number, line_position, .. } => (*line_number, *line_position),
+            WfoError::MtlError { line_number, line_position, .. } => (*line_number, *line_position),
+            WfoError::InvalidArgument { .. } | WfoError::IoError { .. } => return None,
+        };
+
+        Some(location).filter(|(line_number, _)| *line_number > 0)
+    }
+}
+
+impl fmt::Display for WfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let statement = match self {
+            WfoError::ParseError { statement: Some(x), .. } => Some(x),
+            WfoError::TruncatedInput { statement: Some(x), .. } => Some(x),
+            WfoError::CompileError { statement, .. } => Some(statement),
+            _ => None,
+        };
+        if let WfoError::MtlError { path: Some(path), .. } = self {
+            write!(f, "{}:", path.display())?;
+        }
+        match (self.location(), statement) {
+            (Some((line, position)), Some(statement)) => write!(f, "{}:{} in {} statement: {}", line, position, statement, self.message()),
+            (Some((line, position)), None) => write!(f, "{}:{}: {}", line, position, self.message()),
+            (None, _) => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for WfoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WfoError::IoError { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wfo_error_display_includes_location_and_statement() {
+        let error = WfoError::CompileError {
+            message: String::from("Bad position index"),
+            line_number: 12,
+            line_position: 0,
+            statement: StatementType::FACE,
+        };
+
+        assert_eq!("12:0 in face statement: Bad position index", error.to_string());
+        assert_eq!("Bad position index", error.message());
+    }
+
+    #[test]
+    fn wfo_error_exposes_io_source() {
+        let error = WfoError::IoError {
+            path: Some(PathBuf::from("missing.obj")),
+            source: std::io::Error::new(std::io::ErrorKind::NotFound, "not found"),
+        };
+
+        assert_eq!("Could not open missing.obj: not found", error.to_string());
+        assert!(std::error::Error::source(&error).is_some());
+        assert_eq!(None, error.location());
+    }
+
+    #[test]
+    fn wfo_error_display_names_the_material_library() {
+        let error = WfoError::MtlError {
+            message: String::from("Expected 1 or 3 numbers after Kd"),
+            line_number: 4,
+            line_position: 1,
+            path: Some(PathBuf::from("scene.mtl")),
+        };
+
+        assert_eq!("scene.mtl:4:1: Expected 1 or 3 numbers after Kd", error.to_string());
+        assert_eq!(Some((4, 1)), error.location(), "The location is a line of the library");
+    }
+}
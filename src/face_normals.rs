@@ -0,0 +1,73 @@
+use crate::geometry::{add, normalize, triangle_cross};
+use crate::object3d::Object3d;
+
+// Where face_normals takes each triangle's normal from
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum FaceNormalSource {
+    // from the triangle's winding, what STL export and physics cooking expect
+    #[default]
+    Geometric,
+    // the average of the corners' vertex normals, keeps authored normals for flat shading.
+    // Triangles with a corner without a normal fall back to the geometric normal
+    VertexAverage,
+}
+
+impl Object3d {
+    // One unit normal per triangle as a flat x, y, z array, in index buffer order. Degenerate
+    // triangles get a zero normal
+    pub fn face_normals(&self, source: FaceNormalSource) -> Vec<f32> {
+        let vertices: Vec<_> = self.vertices().collect();
+        let mut normals = Vec::with_capacity(self.index_buffer.len());
+
+        for triangle in self.index_buffer.chunks_exact(3) {
+            let corners = [&vertices[triangle[0] as usize], &vertices[triangle[1] as usize], &vertices[triangle[2] as usize]];
+            let vertex_normals = match source {
+                FaceNormalSource::Geometric => None,
+                FaceNormalSource::VertexAverage => corners.iter().map(|v| v.normal()).collect::<Option<Vec<_>>>(),
+            };
+            let normal = match vertex_normals {
+                Some(x) => normalize(x.into_iter().fold([0.0; 3], add)),
+                None => normalize(triangle_cross(corners[0].pos(), corners[1].pos(), corners[2].pos())),
+            };
+            normals.extend_from_slice(&normal);
+        }
+
+        normals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::test_fixtures::object;
+    use crate::vertex::VertexData;
+    use super::*;
+
+    fn tilted_triangle() -> Object3d {
+        let n = (f!(0.0), f!(0.6), f!(0.8));
+        object("Tilted", vec!(
+            VertexData::vertex_pn_from_floats(f!(0.0), f!(0.0), f!(0.0), n.0, n.1, n.2),
+            VertexData::vertex_pn_from_floats(f!(2.0), f!(0.0), f!(0.0), n.0, n.1, n.2),
+            VertexData::vertex_pn_from_floats(f!(0.0), f!(2.0), f!(0.0), n.0, n.1, n.2),
+            VertexData::vertex_pn_from_floats(f!(5.0), f!(0.0), f!(0.0), n.0, n.1, n.2),
+        ), &[0, 1, 2, 0, 1, 3])
+    }
+
+    #[test]
+    fn face_normals_come_from_the_winding_by_default() {
+        let normals = tilted_triangle().face_normals(FaceNormalSource::default());
+
+        assert_eq!(vec!(0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0), normals, "The second triangle is degenerate");
+    }
+
+    #[test]
+    fn face_normals_can_average_the_vertex_normals() {
+        let normals = tilted_triangle().face_normals(FaceNormalSource::VertexAverage);
+
+        assert_eq!(6, normals.len());
+        for (actual, expected) in normals.iter().zip([0.0f32, 0.6, 0.8, 0.0, 0.6, 0.8]) {
+            assert!((actual - expected).abs() < 1e-6, "Vertex normals are kept: {:?}", normals);
+        }
+    }
+}
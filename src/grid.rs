@@ -0,0 +1,137 @@
+use crate::geometry::sub;
+use crate::object3d::Object3d;
+
+// Uniform grid over an object's bounds bucketing triangles by the cells their bounding boxes
+// overlap. Built once and shared by anything that needs spatial queries (ray casts, voxelizing,
+// decimation)
+#[derive(PartialEq, Debug, Clone)]
+pub struct UniformGrid {
+    pub resolution: [usize; 3],
+    pub origin: [f32; 3],
+    pub cell_size: [f32; 3],
+    // triangle indices per cell, x varies fastest, then y, then z
+    pub cells: Vec<Vec<u32>>,
+}
+
+impl UniformGrid {
+    // Sizes the grid so an evenly spread mesh averages triangles_per_cell triangles per cell
+    pub fn from_object(obj: &Object3d, triangles_per_cell: usize) -> Option<Self> {
+        let positions: Vec<[f32; 3]> = obj.vertices().map(|v| v.pos()).collect();
+        let triangles: Vec<[[f32; 3]; 3]> = obj.index_buffer.chunks_exact(3)
+            .map(|t| [positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize]])
+            .collect();
+        if triangles.is_empty() {
+            return None;
+        }
+
+        let (min, max) = bounds(triangles.iter().flatten());
+        let extent = sub(max, min);
+        let cell_count = (triangles.len() as f32 / triangles_per_cell.max(1) as f32).max(1.0);
+        let per_axis = cell_count.cbrt().ceil() as usize;
+        let resolution = extent.map(|e| if e > 0.0 { per_axis } else { 1 });
+        let cell_size = [0, 1, 2].map(|axis| {
+            if extent[axis] > 0.0 { extent[axis] / resolution[axis] as f32 } else { 1.0 }
+        });
+
+        let mut grid = UniformGrid {
+            resolution,
+            origin: min,
+            cell_size,
+            cells: vec![Vec::new(); resolution[0] * resolution[1] * resolution[2]],
+        };
+        for (i, triangle) in triangles.iter().enumerate() {
+            let (t_min, t_max) = bounds(triangle.iter());
+            let (lo, hi) = (grid.cell_of(t_min), grid.cell_of(t_max));
+            for z in lo[2]..=hi[2] {
+                for y in lo[1]..=hi[1] {
+                    for x in lo[0]..=hi[0] {
+                        let cell = grid.cell_index([x, y, z]);
+                        grid.cells[cell].push(i as u32);
+                    }
+                }
+            }
+        }
+
+        Some(grid)
+    }
+
+    // Cell containing point, clamped to the grid
+    pub fn cell_of(&self, point: [f32; 3]) -> [usize; 3] {
+        [0, 1, 2].map(|axis| {
+            let cell = ((point[axis] - self.origin[axis]) / self.cell_size[axis]).floor();
+            (cell.max(0.0) as usize).min(self.resolution[axis] - 1)
+        })
+    }
+
+    pub fn triangles_in_cell(&self, cell: [usize; 3]) -> &[u32] {
+        &self.cells[self.cell_index(cell)]
+    }
+
+    // Every triangle whose cells overlap the box, each listed once in ascending order
+    pub fn query_box(&self, min: [f32; 3], max: [f32; 3]) -> Vec<u32> {
+        let (lo, hi) = (self.cell_of(min), self.cell_of(max));
+        let mut triangles = Vec::new();
+        for z in lo[2]..=hi[2] {
+            for y in lo[1]..=hi[1] {
+                for x in lo[0]..=hi[0] {
+                    triangles.extend_from_slice(self.triangles_in_cell([x, y, z]));
+                }
+            }
+        }
+        triangles.sort_unstable();
+        triangles.dedup();
+
+        triangles
+    }
+
+    fn cell_index(&self, cell: [usize; 3]) -> usize {
+        cell[0] + self.resolution[0] * (cell[1] + self.resolution[1] * cell[2])
+    }
+}
+
+fn bounds<'a>(points: impl Iterator<Item = &'a [f32; 3]>) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for p in points {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::positions;
+    use super::*;
+
+    #[test]
+    fn grid_buckets_triangles_by_overlapping_cells() {
+        let grid = UniformGrid::from_object(&two_triangles(), 1).expect("Grid builds for object with triangles");
+
+        assert_eq!([2, 2, 1], grid.resolution, "Flat object gets a single layer of cells");
+        assert_eq!(&[0], grid.triangles_in_cell([0, 0, 0]), "Near triangle is in the first cell");
+        assert_eq!(&[1], grid.triangles_in_cell([1, 1, 0]), "Far triangle is in the last cell");
+    }
+
+    #[test]
+    fn grid_query_box_returns_each_triangle_once() {
+        let grid = UniformGrid::from_object(&two_triangles(), 1).expect("Grid builds");
+
+        assert_eq!(vec!(0, 1), grid.query_box([-10.0, -10.0, -10.0], [10.0, 10.0, 10.0]));
+        assert_eq!(vec!(1), grid.query_box([3.5, 3.5, 0.0], [3.9, 3.9, 0.0]));
+    }
+
+    #[test]
+    fn grid_is_none_without_triangles() {
+        assert_eq!(None, UniformGrid::from_object(&Object3d::from(String::from("Empty")), 1));
+    }
+
+    fn two_triangles() -> Object3d {
+        let points = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [3.0, 3.0, 0.0], [4.0, 3.0, 0.0], [4.0, 4.0, 0.0]];
+
+        positions("Test", &points, &[0, 1, 2, 3, 4, 5])
+    }
+}
@@ -0,0 +1,101 @@
+use crate::object3d::Object3d;
+use crate::vertex::VertexFormat;
+
+// Checks for the guarantees the compiler gives about every Object3d it returns, so code that
+// edits compiled objects can assert it hasn't broken them
+
+// Every index addresses a vertex and the index buffer is made of whole triangles
+pub fn indices_in_range(obj: &Object3d) -> bool {
+    obj.index_buffer.len().is_multiple_of(3) &&
+        obj.index_buffer.iter().all(|i| (*i as usize) < obj.vertex_buffer.len())
+}
+
+// Every vertex has the object's format, carries exactly the attributes that format names and
+// per vertex / per triangle side data is either absent or has one entry per vertex / triangle
+pub fn format_consistent(obj: &Object3d) -> bool {
+    if obj.format == VertexFormat::Unknown {
+        return obj.vertex_buffer.is_empty();
+    }
+
+    let (has_normal, has_tex_coord) = match obj.format {
+        VertexFormat::VertexP => (false, false),
+        VertexFormat::VertexPN => (true, false),
+        VertexFormat::VertexPT => (false, true),
+        VertexFormat::VertexPNT => (true, true),
+        VertexFormat::Unknown => unreachable!(),
+    };
+    let vertices_match = obj.vertex_buffer.iter().all(|v| {
+        v.format == obj.format && v.normal.is_some() == has_normal && v.tex_coord.is_some() == has_tex_coord
+    });
+
+    let vertex_count = obj.vertex_buffer.len();
+    let triangle_count = obj.index_buffer.len() / 3;
+    let per_vertex = [
+        obj.vertex_sources.len(),
+        obj.lightmap_tex_coords.len(),
+        obj.joint_indices.len(),
+        obj.joint_weights.len(),
+    ];
+    let per_triangle = [obj.triangle_sources.len(), obj.smoothing_groups.len()];
+
+    vertices_match &&
+        per_vertex.iter().all(|n| *n == 0 || *n == vertex_count) &&
+        per_triangle.iter().all(|n| *n == 0 || *n == triangle_count)
+}
+
+// Positions and attributes are NotNan by construction, this covers the plain float side data
+pub fn no_nan(obj: &Object3d) -> bool {
+    obj.lightmap_tex_coords.iter().flatten().all(|x| !x.is_nan()) &&
+        obj.joint_weights.iter().flatten().all(|x| !x.is_nan())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::test_fixtures::positions;
+    use crate::vertex::VertexData;
+    use super::*;
+
+    #[test]
+    fn compiled_objects_hold_all_invariants() {
+        let objects = crate::parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1 2/1 3/1\n", "default").unwrap();
+
+        assert!(indices_in_range(&objects[0]));
+        assert!(format_consistent(&objects[0]));
+        assert!(no_nan(&objects[0]));
+    }
+
+    #[test]
+    fn indices_in_range_rejects_dangling_and_partial_triangles() {
+        let mut obj = triangle();
+        obj.index_buffer = vec!(0, 1, 3);
+        assert!(!indices_in_range(&obj), "Index 3 has no vertex");
+
+        obj.index_buffer = vec!(0, 1);
+        assert!(!indices_in_range(&obj), "Two indices are not a triangle");
+    }
+
+    #[test]
+    fn format_consistent_rejects_mixed_vertices_and_short_side_data() {
+        let mut obj = triangle();
+        obj.vertex_buffer[1] = VertexData::vertex_pt_from_floats(f!(0.0), f!(0.0), f!(1.0), f!(0.0), f!(1.0));
+        assert!(!format_consistent(&obj), "A PT vertex in a P object");
+
+        let mut obj = triangle();
+        obj.lightmap_tex_coords = vec!([0.0, 0.0]);
+        assert!(!format_consistent(&obj), "One lightmap UV for three vertices");
+    }
+
+    #[test]
+    fn no_nan_rejects_nan_side_data() {
+        let mut obj = triangle();
+        obj.joint_weights = vec![[1.0, 0.0, 0.0, f32::NAN]; 3];
+
+        assert!(!no_nan(&obj));
+    }
+
+    fn triangle() -> Object3d {
+        positions("Test", &[[-1.0, 0.0, -1.0], [0.0, 0.0, 1.0], [1.0, 0.0, 1.0]], &[0, 1, 2])
+    }
+}
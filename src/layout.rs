@@ -0,0 +1,438 @@
+use std::ops::Range;
+use crate::object3d::Object3d;
+use crate::vertex::{AttributeDesc, VertexAttribute, VertexData};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Precision {
+    F32,
+    F64,
+}
+
+impl Precision {
+    pub fn component_size(&self) -> u32 {
+        match self {
+            Precision::F32 => 4,
+            Precision::F64 => 8,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum IndexFormat {
+    U16,
+    U32,
+}
+
+impl IndexFormat {
+    // The narrowest index type able to address every vertex
+    pub fn for_vertex_count(vertex_count: usize) -> Self {
+        if vertex_count <= u16::MAX as usize + 1 {
+            IndexFormat::U16
+        } else {
+            IndexFormat::U32
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        match self {
+            IndexFormat::U16 => 2,
+            IndexFormat::U32 => 4,
+        }
+    }
+}
+
+// Byte order of the components in written buffers. Layouts default to little endian; big endian
+// targets can either write with a big endian layout or convert an existing buffer
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+// Exact sizes of the interleaved vertex buffer and index buffer an object produces, so GPU memory
+// can be allocated before converting
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MemoryLayout {
+    pub precision: Precision,
+    pub endianness: Endianness,
+    pub attributes: Vec<AttributeDesc>,
+    pub vertex_stride: u32,
+    pub vertex_count: usize,
+    pub vertex_buffer_size: usize,
+    pub index_format: IndexFormat,
+    pub index_count: usize,
+    pub index_buffer_size: usize,
+}
+
+impl Object3d {
+    pub fn memory_layout(&self, precision: Precision) -> MemoryLayout {
+        let attributes: Vec<AttributeDesc> = self.attributes()
+            .iter()
+            .map(|a| AttributeDesc {
+                semantic: a.semantic,
+                component_count: a.component_count,
+                byte_offset: a.byte_offset / 4 * precision.component_size(),
+                byte_size: a.component_count * precision.component_size(),
+            })
+            .collect();
+        let vertex_stride: u32 = attributes.iter().map(|a| a.byte_size).sum();
+        let index_format = IndexFormat::for_vertex_count(self.vertex_buffer.len());
+
+        MemoryLayout {
+            precision,
+            endianness: Endianness::default(),
+            attributes,
+            vertex_stride,
+            vertex_count: self.vertex_buffer.len(),
+            vertex_buffer_size: vertex_stride as usize * self.vertex_buffer.len(),
+            index_format,
+            index_count: self.index_buffer.len(),
+            index_buffer_size: index_format.size() as usize * self.index_buffer.len(),
+        }
+    }
+}
+
+impl MemoryLayout {
+    pub fn with_endianness(self, endianness: Endianness) -> Self {
+        MemoryLayout { endianness, ..self }
+    }
+
+    // Rewrites buffers produced with this layout in place to the other byte order (e.g. a cache
+    // built on an x86 farm consumed by a big endian target) and updates the layout to match
+    pub fn convert_endianness(&mut self, vertices: &mut [u8], indices: &mut [u8], to: Endianness) -> Result<(), String> {
+        if vertices.len() < self.vertex_buffer_size {
+            return Err(format!("Vertex data needs {} bytes but only {} were provided", self.vertex_buffer_size, vertices.len()));
+        }
+        if indices.len() < self.index_buffer_size {
+            return Err(format!("Index data needs {} bytes but only {} were provided", self.index_buffer_size, indices.len()));
+        }
+        if self.endianness == to {
+            return Ok(());
+        }
+
+        // every vertex component and every index is a single scalar, so swapping each one is enough
+        vertices[..self.vertex_buffer_size]
+            .chunks_exact_mut(self.precision.component_size() as usize)
+            .for_each(|c| c.reverse());
+        indices[..self.index_buffer_size]
+            .chunks_exact_mut(self.index_format.size() as usize)
+            .for_each(|c| c.reverse());
+        self.endianness = to;
+
+        Ok(())
+    }
+}
+
+impl Object3d {
+    // Serializes the interleaved vertex buffer described by layout straight into dest (e.g. a
+    // mapped staging buffer) as floats in the layout's byte order. Returns the number of bytes written
+    pub fn write_vertices_into(&self, dest: &mut [u8], layout: &MemoryLayout) -> Result<usize, String> {
+        self.check_layout(layout)?;
+        if dest.len() < layout.vertex_buffer_size {
+            return Err(format!("Vertex data needs {} bytes but only {} were provided", layout.vertex_buffer_size, dest.len()));
+        }
+
+        self.write_vertex_range_into(dest, layout, 0..self.vertex_buffer.len())?;
+
+        Ok(layout.vertex_buffer_size)
+    }
+
+    // Yields the interleaved vertex buffer in pieces of at most max_chunk_bytes that never split a
+    // vertex (a chunk holds at least one vertex), for uploading through ring or staging buffers
+    pub fn vertex_chunks<'a>(&'a self, layout: &'a MemoryLayout, max_chunk_bytes: usize) -> VertexChunks<'a> {
+        let vertices_per_chunk = (max_chunk_bytes / layout.vertex_stride.max(1) as usize).max(1);
+
+        VertexChunks { object: self, layout, vertices_per_chunk, next_vertex: 0 }
+    }
+
+    pub fn write_indices_into(&self, dest: &mut [u8], layout: &MemoryLayout) -> Result<usize, String> {
+        self.check_layout(layout)?;
+        if dest.len() < layout.index_buffer_size {
+            return Err(format!("Index data needs {} bytes but only {} were provided", layout.index_buffer_size, dest.len()));
+        }
+
+        for (i, index) in self.index_buffer.iter().enumerate() {
+            match layout.index_format {
+                IndexFormat::U16 => {
+                    let index = u16::try_from(*index).map_err(|_| format!("Index {} does not fit in 16 bits", index))?;
+                    let bytes = match layout.endianness {
+                        Endianness::Little => index.to_le_bytes(),
+                        Endianness::Big => index.to_be_bytes(),
+                    };
+                    dest[i * 2..i * 2 + 2].copy_from_slice(&bytes);
+                },
+                IndexFormat::U32 => {
+                    let index = u32::try_from(*index).map_err(|_| format!("Index {} does not fit in 32 bits", index))?;
+                    let bytes = match layout.endianness {
+                        Endianness::Little => index.to_le_bytes(),
+                        Endianness::Big => index.to_be_bytes(),
+                    };
+                    dest[i * 4..i * 4 + 4].copy_from_slice(&bytes);
+                },
+            }
+        }
+
+        Ok(layout.index_buffer_size)
+    }
+
+    // A layout made for another object would write past the end of dest or leave it partly unwritten
+    fn check_layout(&self, layout: &MemoryLayout) -> Result<(), String> {
+        if layout.vertex_count != self.vertex_buffer.len() || layout.index_count != self.index_buffer.len() {
+            return Err(format!(
+                "Layout describes {} vertices and {} indices but the object has {} and {}",
+                layout.vertex_count, layout.index_count, self.vertex_buffer.len(), self.index_buffer.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn write_vertex_range_into(&self, dest: &mut [u8], layout: &MemoryLayout, range: Range<usize>) -> Result<(), String> {
+        let component_size = layout.precision.component_size() as usize;
+        let first = range.start;
+        for i in range {
+            let vertex = &self.vertex_buffer[i];
+            let vertex_start = (i - first) * layout.vertex_stride as usize;
+            for attribute in &layout.attributes {
+                let components = attribute_components(vertex, attribute.semantic)
+                    .ok_or_else(|| format!("Vertex {} has no {:?} attribute", i, attribute.semantic))?;
+                for (c, component) in components.iter().enumerate() {
+                    let start = vertex_start + attribute.byte_offset as usize + c * component_size;
+                    match (layout.precision, layout.endianness) {
+                        (Precision::F32, Endianness::Little) => dest[start..start + 4].copy_from_slice(&(*component as f32).to_le_bytes()),
+                        (Precision::F32, Endianness::Big) => dest[start..start + 4].copy_from_slice(&(*component as f32).to_be_bytes()),
+                        (Precision::F64, Endianness::Little) => dest[start..start + 8].copy_from_slice(&component.to_le_bytes()),
+                        (Precision::F64, Endianness::Big) => dest[start..start + 8].copy_from_slice(&component.to_be_bytes()),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct VertexChunks<'a> {
+    object: &'a Object3d,
+    layout: &'a MemoryLayout,
+    vertices_per_chunk: usize,
+    next_vertex: usize,
+}
+
+impl<'a> Iterator for VertexChunks<'a> {
+    type Item = Result<Vec<u8>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let vertex_count = self.object.vertex_buffer.len();
+        if self.next_vertex >= vertex_count {
+            return None;
+        }
+
+        let end = (self.next_vertex + self.vertices_per_chunk).min(vertex_count);
+        let mut chunk = vec![0u8; (end - self.next_vertex) * self.layout.vertex_stride as usize];
+        let result = self.object.write_vertex_range_into(&mut chunk, self.layout, self.next_vertex..end);
+        self.next_vertex = end;
+
+        Some(result.map(|_| chunk))
+    }
+}
+
+fn attribute_components(vertex: &VertexData, semantic: VertexAttribute) -> Option<Vec<f64>> {
+    match semantic {
+        VertexAttribute::Position => Some(vec!(*vertex.pos.0, *vertex.pos.1, *vertex.pos.2)),
+        VertexAttribute::Normal => vertex.normal.map(|n| vec!(*n.0, *n.1, *n.2)),
+        VertexAttribute::TexCoord => vertex.tex_coord.map(|t| vec!(*t.0, *t.1)),
+        VertexAttribute::TexCoord2 => vertex.tex_coord2.map(|t| vec!(*t.0, *t.1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::test_fixtures::object;
+    use crate::vertex::{VertexAttribute, VertexData};
+    use super::*;
+
+    #[test]
+    fn memory_layout_reports_f32_buffer_sizes() {
+        let layout = triangle().memory_layout(Precision::F32);
+
+        assert_eq!(20, layout.vertex_stride, "VertexPT has a 20 byte f32 stride");
+        assert_eq!(60, layout.vertex_buffer_size, "Vertex buffer size is stride times vertex count");
+        assert_eq!(IndexFormat::U16, layout.index_format, "Small objects use 16 bit indices");
+        assert_eq!(6, layout.index_buffer_size, "Index buffer size is index size times index count");
+    }
+
+    #[test]
+    fn memory_layout_scales_offsets_with_precision() {
+        let layout = triangle().memory_layout(Precision::F64);
+
+        assert_eq!(
+            vec!(
+                AttributeDesc { semantic: VertexAttribute::Position, component_count: 3, byte_offset: 0, byte_size: 24 },
+                AttributeDesc { semantic: VertexAttribute::TexCoord, component_count: 2, byte_offset: 24, byte_size: 16 },
+            ),
+            layout.attributes,
+            "Attribute offsets and sizes follow the chosen precision"
+        );
+        assert_eq!(40, layout.vertex_stride);
+        assert_eq!(120, layout.vertex_buffer_size);
+    }
+
+    #[test]
+    fn memory_layout_appends_the_second_uv_set() {
+        let mut obj = triangle();
+        for vertex in obj.vertex_buffer.iter_mut() {
+            vertex.tex_coord2 = Some((f!(0.5), f!(0.25)));
+        }
+        let layout = obj.memory_layout(Precision::F32);
+        let mut dest = vec![0u8; layout.vertex_buffer_size];
+
+        obj.write_vertices_into(&mut dest, &layout).expect("Buffer is large enough");
+
+        assert_eq!(
+            Some(&AttributeDesc { semantic: VertexAttribute::TexCoord2, component_count: 2, byte_offset: 20, byte_size: 8 }),
+            layout.attributes.last(),
+            "uv2 follows the format's attributes"
+        );
+        assert_eq!(0.25, f32::from_le_bytes(dest[24..28].try_into().unwrap()));
+    }
+
+    #[test]
+    fn index_format_widens_for_large_vertex_counts() {
+        assert_eq!(IndexFormat::U16, IndexFormat::for_vertex_count(65536));
+        assert_eq!(IndexFormat::U32, IndexFormat::for_vertex_count(65537));
+    }
+
+    #[test]
+    fn write_vertices_into_interleaves_attributes() {
+        let obj = triangle();
+        let layout = obj.memory_layout(Precision::F32);
+        let mut dest = vec![0xFFu8; layout.vertex_buffer_size];
+
+        let written = obj.write_vertices_into(&mut dest, &layout).expect("Buffer is large enough");
+
+        assert_eq!(60, written);
+        let floats: Vec<f32> = dest.chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        assert_eq!(&[0.0, 0.0, 1.0, 0.0, 1.0], &floats[5..10], "Second vertex is position then tex coord");
+    }
+
+    #[test]
+    fn write_vertices_into_supports_f64() {
+        let obj = triangle();
+        let layout = obj.memory_layout(Precision::F64);
+        let mut dest = vec![0u8; layout.vertex_buffer_size];
+
+        obj.write_vertices_into(&mut dest, &layout).expect("Buffer is large enough");
+
+        assert_eq!(-1.0, f64::from_le_bytes(dest[0..8].try_into().unwrap()));
+    }
+
+    #[test]
+    fn write_into_rejects_small_buffers() {
+        let obj = triangle();
+        let layout = obj.memory_layout(Precision::F32);
+
+        assert_eq!(
+            Err(String::from("Vertex data needs 60 bytes but only 59 were provided")),
+            obj.write_vertices_into(&mut [0u8; 59], &layout)
+        );
+        assert_eq!(
+            Err(String::from("Index data needs 6 bytes but only 4 were provided")),
+            obj.write_indices_into(&mut [0u8; 4], &layout)
+        );
+    }
+
+    #[test]
+    fn write_into_rejects_layouts_of_other_objects() {
+        let obj = triangle();
+        let mut larger = triangle();
+        larger.vertex_buffer.push(larger.vertex_buffer[0].clone());
+        larger.index_buffer.extend_from_slice(&[3, 1, 2]);
+        let layout = larger.memory_layout(Precision::F32);
+        let expected = Err(String::from("Layout describes 4 vertices and 6 indices but the object has 3 and 3"));
+
+        assert_eq!(expected, obj.write_vertices_into(&mut vec![0u8; layout.vertex_buffer_size], &layout));
+        assert_eq!(expected, obj.write_indices_into(&mut vec![0u8; layout.index_buffer_size], &layout));
+        assert!(
+            larger.write_vertices_into(&mut [0u8; 100], &obj.memory_layout(Precision::F32)).is_err(),
+            "A smaller layout is rejected instead of panicking"
+        );
+    }
+
+    #[test]
+    fn vertex_chunks_split_on_vertex_boundaries() {
+        let obj = triangle();
+        let layout = obj.memory_layout(Precision::F32);
+        let mut whole = vec![0u8; layout.vertex_buffer_size];
+        obj.write_vertices_into(&mut whole, &layout).unwrap();
+
+        let chunks: Vec<Vec<u8>> = obj.vertex_chunks(&layout, 45).collect::<Result<_, _>>().expect("Chunks serialize");
+
+        assert_eq!(vec!(40, 20), chunks.iter().map(|c| c.len()).collect::<Vec<usize>>(), "A 45 byte budget fits two 20 byte vertices");
+        assert_eq!(whole, chunks.concat(), "Chunks concatenate to the whole buffer");
+    }
+
+    #[test]
+    fn vertex_chunks_hold_at_least_one_vertex() {
+        let obj = triangle();
+        let layout = obj.memory_layout(Precision::F32);
+
+        assert_eq!(3, obj.vertex_chunks(&layout, 1).count(), "Budgets below the stride still make progress");
+    }
+
+    #[test]
+    fn write_indices_into_uses_layout_index_format() {
+        let obj = triangle();
+        let layout = obj.memory_layout(Precision::F32);
+        let mut dest = [0u8; 6];
+
+        assert_eq!(Ok(6), obj.write_indices_into(&mut dest, &layout));
+        assert_eq!([0, 0, 1, 0, 2, 0], dest, "Indices are little endian u16");
+    }
+
+    #[test]
+    fn big_endian_layout_writes_big_endian_buffers() {
+        let obj = triangle();
+        let layout = obj.memory_layout(Precision::F32).with_endianness(Endianness::Big);
+        let mut vertices = vec![0u8; layout.vertex_buffer_size];
+        let mut indices = [0u8; 6];
+
+        obj.write_vertices_into(&mut vertices, &layout).expect("Buffer is large enough");
+        obj.write_indices_into(&mut indices, &layout).expect("Buffer is large enough");
+
+        assert_eq!(-1.0, f32::from_be_bytes(vertices[0..4].try_into().unwrap()));
+        assert_eq!([0, 0, 0, 1, 0, 2], indices, "Indices are big endian u16");
+    }
+
+    #[test]
+    fn convert_endianness_matches_writing_directly() {
+        let obj = triangle();
+        let mut layout = obj.memory_layout(Precision::F64);
+        let big_layout = layout.clone().with_endianness(Endianness::Big);
+        let mut vertices = vec![0u8; layout.vertex_buffer_size];
+        let mut indices = vec![0u8; layout.index_buffer_size];
+        let mut expected_vertices = vertices.clone();
+        let mut expected_indices = indices.clone();
+        obj.write_vertices_into(&mut vertices, &layout).expect("Buffer is large enough");
+        obj.write_indices_into(&mut indices, &layout).expect("Buffer is large enough");
+        obj.write_vertices_into(&mut expected_vertices, &big_layout).expect("Buffer is large enough");
+        obj.write_indices_into(&mut expected_indices, &big_layout).expect("Buffer is large enough");
+
+        layout.convert_endianness(&mut vertices, &mut indices, Endianness::Big).expect("Buffers match the layout");
+
+        assert_eq!(expected_vertices, vertices);
+        assert_eq!(expected_indices, indices);
+        assert_eq!(Endianness::Big, layout.endianness, "Layout describes the converted buffers");
+    }
+
+    fn triangle() -> Object3d {
+        object("Test", vec!(
+            VertexData::vertex_pt_from_floats(f!(-1.0), f!(0.0), f!(-1.0), f!(0.0), f!(0.0)),
+            VertexData::vertex_pt_from_floats(f!(0.0), f!(0.0), f!(1.0), f!(0.0), f!(1.0)),
+            VertexData::vertex_pt_from_floats(f!(1.0), f!(0.0), f!(1.0), f!(1.0), f!(0.0)),
+        ), &[0, 1, 2])
+    }
+}
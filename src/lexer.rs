@@ -1,9 +1,11 @@
 use std::collections::VecDeque;
-use std::io::{Read};
+use std::io::{BufRead, BufReader, Read};
 use std::str::FromStr;
 use crate::token;
 use token::{Token, TokenType, TokenDataType};
 use crate::nan_safe_float::Float;
+use crate::diagnostic::Diagnostic;
+use crate::error::WfoError;
 
 #[derive(PartialEq)]
 enum LexerState {
@@ -14,11 +16,93 @@ enum LexerState {
     Comment,
 }
 
-struct Lexer {
+// How input bytes are turned into characters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    // Every byte is one character
+    Latin1,
+    // Bytes are decoded as UTF-8. Bytes that aren't valid UTF-8 are read as Latin-1 instead and
+    // a warning is recorded
+    Utf8WithLatin1Fallback,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexerOptions {
+    pub separators: Vec<char>,
+    pub encoding: TextEncoding,
+}
+
+// Every char::is_whitespace character except the line endings, what the spec treats as a separator
+const SPEC_SEPARATORS: [char; 23] = [
+    '\t', '\u{0B}', '\u{0C}', ' ', '\u{85}', '\u{A0}', '\u{1680}',
+    '\u{2000}', '\u{2001}', '\u{2002}', '\u{2003}', '\u{2004}', '\u{2005}', '\u{2006}', '\u{2007}',
+    '\u{2008}', '\u{2009}', '\u{200A}', '\u{2028}', '\u{2029}', '\u{202F}', '\u{205F}', '\u{3000}',
+];
+
+impl Default for LexerOptions {
+    fn default() -> Self {
+        LexerOptions {
+            separators: SPEC_SEPARATORS.to_vec(),
+            encoding: TextEncoding::Latin1,
+        }
+    }
+}
+
+const SNIFF_SAMPLE_SIZE: usize = 8192;
+
+// Cheap check on the start of an input that rejects content that obviously isn't OBJ text (NUL
+// bytes, many control characters or undecodable bytes) before lexing turns it into junk tokens.
+// The check covers a sample rather than a line, so the error has no location
+pub(crate) fn sniff_text(bytes: &[u8]) -> Result<(), WfoError> {
+    let sample = &bytes[..bytes.len().min(SNIFF_SAMPLE_SIZE)];
+    if sample.is_empty() {
+        return Ok(());
+    }
+    let error = || Err(WfoError::LexError {
+        message: String::from("Input is not a Wavefront OBJ text file"),
+        line_number: 0,
+        line_position: 0,
+    });
+    if sample.contains(&0) {
+        return error();
+    }
+
+    let control_count = sample.iter()
+        .filter(|b| (**b < 0x20 && !b"\t\n\r\x0C".contains(b)) || **b == 0x7F)
+        .count();
+
+    let mut invalid_count = 0;
+    let mut rest = sample;
+    while let Err(e) = std::str::from_utf8(rest) {
+        let bad_len = match e.error_len() {
+            Some(x) => x,
+            None => break, // sequence cut off by the end of the sample
+        };
+        invalid_count += bad_len;
+        rest = &rest[e.valid_up_to() + bad_len..];
+    }
+
+    // Latin-1 names make a few invalid bytes normal, binary data makes them common
+    if control_count * 20 > sample.len() || invalid_count * 10 > sample.len() {
+        return error();
+    }
+
+    Ok(())
+}
+
+pub(crate) struct Lexer {
     char_buffer: String,
     char_position: u64,
     line_number: u64,
     state: LexerState,
+    options: LexerOptions,
+    pending_bytes: Vec<u8>,
+    warnings: Vec<Diagnostic>,
+    // no token but separators lexed on this line yet
+    at_line_start: bool,
+    // the line's keyword takes names (o, g, usemtl, mtllib), so the rest of the line lexes as
+    // strings even where a name looks like a number, an index or a keyword
+    names_follow: bool,
 }
 
 impl Default for Lexer {
@@ -28,53 +112,167 @@ impl Default for Lexer {
             char_position: 0,
             line_number: 1,
             state: LexerState::Initial,
+            options: LexerOptions::default(),
+            pending_bytes: Vec::new(),
+            warnings: Vec::new(),
+            at_line_start: true,
+            names_follow: false,
         }
     }
 }
 
 impl Lexer {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Default::default()
     }
 
-    fn lex_tokens<R: Read>(&mut self, stream: &mut R) -> Vec<Token> {
+    pub(crate) fn from_options(options: LexerOptions) -> Self {
+        Lexer {
+            options,
+            ..Default::default()
+        }
+    }
+
+    // lex_tokens for input handed to a public entry point, which is rejected by sniff_text on its
+    // first buffered chunk before any of it is lexed
+    pub(crate) fn lex_input<R: Read>(&mut self, stream: &mut R) -> Result<Vec<Token>, WfoError> {
+        let mut stream = BufReader::with_capacity(SNIFF_SAMPLE_SIZE, stream);
+        sniff_text(stream.fill_buf().map_err(|source| WfoError::IoError { path: None, source })?)?;
+
+        self.lex_tokens(&mut stream)
+    }
+
+    // Reading stops at the first I/O error, which has no path since the lexer only sees a stream
+    pub(crate) fn lex_tokens<R: Read>(&mut self, stream: &mut R) -> Result<Vec<Token>, WfoError> {
         let mut lexed_tokens = Vec::new();
 
         loop {
-            let cur_char = Lexer::advance_char(stream);
-            if cur_char.is_none() {
+            let cur_byte = Lexer::advance_byte(stream).map_err(|source| WfoError::IoError { path: None, source })?;
+            if cur_byte.is_none() {
+                self.flush_pending_bytes(&mut lexed_tokens);
                 self.process_char_buffer(&mut lexed_tokens);
                 break;
             }
-            let cur_char = cur_char.unwrap();
 
-            let next_state = self.check_for_state_transition(cur_char);
-            if !next_state.is_none() {
-                self.process_char_buffer(&mut lexed_tokens);
-                self.state = next_state.unwrap();
-            }
+            self.lex_byte(cur_byte.unwrap(), &mut lexed_tokens);
+        }
 
-            self.save_char(cur_char);
+        Ok(lexed_tokens)
+    }
+
+    // Lexes a chunk of a stream that arrives piece by piece. Tokens that may continue into the
+    // next chunk are held back until the next call or until finish
+    pub(crate) fn push_bytes(&mut self, chunk: &[u8]) -> Vec<Token> {
+        let mut lexed_tokens = Vec::new();
+
+        for byte in chunk {
+            self.lex_byte(*byte, &mut lexed_tokens);
         }
 
         lexed_tokens
     }
 
-    fn advance_char<R: Read>(stream: &mut R) -> Option<char> {
-        let mut buffer = [0; 1];
+    pub(crate) fn finish(&mut self) -> Vec<Token> {
+        let mut lexed_tokens = Vec::new();
+        self.flush_pending_bytes(&mut lexed_tokens);
+        self.process_char_buffer(&mut lexed_tokens);
+
+        lexed_tokens
+    }
+
+    // Bytes that had to be read as Latin-1 because they weren't valid UTF-8
+    pub(crate) fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    fn lex_byte(&mut self, byte: u8, lexed_tokens: &mut Vec<Token>) {
+        if self.options.encoding == TextEncoding::Latin1 {
+            self.lex_char(char::from(byte), lexed_tokens);
+            return;
+        }
+
+        let is_continuation = (0x80..=0xBF).contains(&byte);
+        if !self.pending_bytes.is_empty() && !is_continuation {
+            self.flush_pending_bytes(lexed_tokens);
+        }
+        if self.pending_bytes.is_empty() && byte < 0x80 {
+            self.lex_char(char::from(byte), lexed_tokens);
+            return;
+        }
 
-        let result = stream.read(&mut buffer);
-        if result.is_err() || result.unwrap() == 0 {
-            return None
+        self.pending_bytes.push(byte);
+        let expected_len = match self.pending_bytes[0] {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => {
+                self.flush_pending_bytes(lexed_tokens);
+                return;
+            },
+        };
+        if self.pending_bytes.len() < expected_len {
+            return;
         }
 
-        Some(char::from(buffer[0]))
+        match std::str::from_utf8(&self.pending_bytes) {
+            Ok(x) => {
+                let decoded = x.chars().next().expect("Complete sequence decodes to a char");
+                self.pending_bytes.clear();
+                self.lex_char(decoded, lexed_tokens);
+            },
+            Err(_) => self.flush_pending_bytes(lexed_tokens),
+        }
+    }
+
+    // Falls back to Latin-1 for a byte sequence that can't be completed as UTF-8
+    fn flush_pending_bytes(&mut self, lexed_tokens: &mut Vec<Token>) {
+        if self.pending_bytes.is_empty() {
+            return;
+        }
+
+        self.warnings.push(Diagnostic::from(
+            format!("Invalid UTF-8 sequence {:02X?} decoded as Latin-1", self.pending_bytes),
+            self.line_number,
+            self.char_position + 1
+        ));
+        let pending_bytes = std::mem::take(&mut self.pending_bytes);
+        for byte in pending_bytes {
+            self.lex_char(char::from(byte), lexed_tokens);
+        }
+    }
+
+    fn lex_char(&mut self, cur_char: char, lexed_tokens: &mut Vec<Token>) {
+        let next_state = self.check_for_state_transition(cur_char);
+        if !next_state.is_none() {
+            self.process_char_buffer(lexed_tokens);
+            self.state = next_state.unwrap();
+        }
+
+        self.save_char(cur_char);
+    }
+
+    fn takes_names(token_type: TokenType) -> bool {
+        matches!(token_type, TokenType::OBJECT | TokenType::GROUP | TokenType::USEMTL | TokenType::MTLLIB)
+    }
+
+    // None at the end of the stream. Interrupted reads are retried like Read::read_to_end does
+    fn advance_byte<R: Read>(stream: &mut R) -> std::io::Result<Option<u8>> {
+        let mut buffer = [0; 1];
+
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buffer[0])),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
     }
 
     fn check_for_state_transition(&mut self, cur_char: char) -> Option<LexerState> {
         let is_n_line_ending = cur_char == '\n';
         let is_line_ending = cur_char == '\n' || cur_char == '\r';
-        let is_whitespace = cur_char.is_whitespace() && !is_line_ending;
+        let is_whitespace = self.options.separators.contains(&cur_char) && !is_line_ending;
         let is_comment = cur_char == '#';
         let is_normal = !(is_line_ending || is_whitespace || is_comment);
         let has_nr_line_ending = self.char_buffer == "\n\r";
@@ -110,7 +308,7 @@ impl Lexer {
 
         let char_buffer = self.char_buffer.clone();
         self.char_buffer = String::new();
-        let char_pos = self.char_position - (char_buffer.len() as u64) + 1;
+        let char_pos = self.char_position - (char_buffer.chars().count() as u64) + 1;
         let mut new_token: Option<Token> = None;
 
         if self.state == LexerState::Comment {
@@ -134,6 +332,8 @@ impl Lexer {
 
             self.char_position = 0;
             self.line_number += 1;
+            self.at_line_start = true;
+            self.names_follow = false;
         } else if self.state == LexerState::Separator {
             new_token = Some(
                 Token::from(
@@ -145,6 +345,17 @@ impl Lexer {
             )
         }
 
+        if new_token.is_none() && self.names_follow {
+            new_token = Some(
+                Token::from(
+                    TokenType::STRING,
+                    TokenDataType::String(char_buffer.clone()),
+                    self.line_number,
+                    char_pos,
+                )
+            );
+        }
+
         if new_token.is_none() {
             let token_type = TokenType::from_str(char_buffer.clone().as_str());
             if !token_type.is_none() {
@@ -202,13 +413,17 @@ impl Lexer {
         }
 
         let new_token = new_token.expect("Lexer to lex a token");
+        if self.state == LexerState::Token {
+            self.names_follow |= self.at_line_start && Lexer::takes_names(new_token.token_type);
+            self.at_line_start = false;
+        }
         lexed_tokens.push(new_token);
     }
 
     fn lex_polygon(text: &str) -> Option<TokenDataType> {
         let mut chars = VecDeque::from_iter(text.chars());
         let mut buffer = String::new();
-        let mut data: Vec<u64> = Vec::new();
+        let mut data: Vec<i64> = Vec::new();
         let mut divider_count = 0;
 
         while chars.len() > 0 {
@@ -223,7 +438,7 @@ impl Lexer {
                 if buffer.len() == 0 {
                     data.push(0); //TODO: wfo indices are 1 based ... so I should be able to do this?
                 } else {
-                    let int_parse_result = u64::from_str(&buffer);
+                    let int_parse_result = i64::from_str(&buffer);
                     if int_parse_result.is_err() {
                         return None
                     }
@@ -235,17 +450,18 @@ impl Lexer {
         if buffer.len() == 0 {
             data.push(0); //TODO: wfo indices are 1 based ... so I should be able to do this?
         } else {
-            let int_parse_result = u64::from_str(&buffer);
+            let int_parse_result = i64::from_str(&buffer);
             if int_parse_result.is_err() {
                 return None
             }
             data.push(int_parse_result.unwrap());
         }
 
-        if data.len() != 3 {
-            None
-        } else {
-            Some(TokenDataType::VertexPTN(data[0], data[1], data[2]))
+        match data.len() {
+            // v/vt, as used by `l` elements and untextured-normal faces
+            2 => Some(TokenDataType::VertexPTN(data[0], data[1], 0)),
+            3 => Some(TokenDataType::VertexPTN(data[0], data[1], data[2])),
+            _ => None,
         }
     }
 }
@@ -303,6 +519,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lexer_lexes_second_texcoord() {
+        test_lexer_lexes_single_token(
+            &vec![Token::from(TokenType::TEXCOORD2, TokenDataType::None(), 1, 1)],
+            "vt2"
+        );
+    }
+
     #[test]
     fn lexer_lexes_usemtl() {
         test_lexer_lexes_single_token(
@@ -320,9 +544,9 @@ mod tests {
     }
 
     #[test]
-    fn lexer_lexes_illum() {
+    fn lexer_lexes_smoothing_group() {
         test_lexer_lexes_single_token(
-            &vec![Token::from(TokenType::ILLUM, TokenDataType::None(), 1, 1)],
+            &vec![Token::from(TokenType::SMOOTHING, TokenDataType::None(), 1, 1)],
             "s"
         );
     }
@@ -359,6 +583,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lexer_lexes_polygon_with_only_texcoord_index() {
+        test_lexer_lexes_single_token(
+            &vec![Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(1, 2, 0), 1, 1)],
+            "1/2"
+        );
+    }
+
+    #[test]
+    fn lexer_lexes_line() {
+        test_lexer_lexes_single_token(
+            &vec![Token::from(TokenType::LINE, TokenDataType::None(), 1, 1)],
+            "l"
+        );
+    }
+
+    #[test]
+    fn lexer_lexes_polygon_with_relative_indices() {
+        test_lexer_lexes_single_token(
+            &vec![Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(-1, -2, -3), 1, 1)],
+            "-1/-2/-3"
+        );
+    }
+
     #[test]
     fn lexer_lexes_string() {
         test_lexer_lexes_single_token(
@@ -410,7 +658,7 @@ mod tests {
     fn test_lexer_lexes_single_token(expected_result: &Vec<Token>, text: &str) {
         let mut lexer = Lexer::new();
 
-        let result = lexer.lex_tokens(&mut text.as_bytes());
+        let result = lexer.lex_tokens(&mut text.as_bytes()).expect("Reading a string can't fail");
 
         assert_token_vectors_are_equal(
             &expected_result,
@@ -433,7 +681,7 @@ mod tests {
 
         let mut lexer = Lexer::new();
 
-        let result = lexer.lex_tokens(&mut test_data.as_bytes());
+        let result = lexer.lex_tokens(&mut test_data.as_bytes()).expect("Reading a string can't fail");
 
         assert_token_vectors_are_equal(
             &expected_tokens,
@@ -457,7 +705,7 @@ mod tests {
 
         let mut lexer = Lexer::new();
 
-        let result = lexer.lex_tokens(&mut test_data.as_bytes());
+        let result = lexer.lex_tokens(&mut test_data.as_bytes()).expect("Reading a string can't fail");
 
         assert_token_vectors_are_equal(
             &expected_tokens,
@@ -488,14 +736,201 @@ mod tests {
 
             Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 4, 1),
 
-            Token::from(TokenType::ILLUM, TokenDataType::None(), 5, 1),
+            Token::from(TokenType::SMOOTHING, TokenDataType::None(), 5, 1),
             Token::from(TokenType::SEPARATOR, TokenDataType::None(), 5, 2),
             Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 5, 3),
             Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 5, 4),
         );
 
         let mut lexer = Lexer::new();
-        let result = lexer.lex_tokens(&mut test_data.as_bytes());
+        let result = lexer.lex_tokens(&mut test_data.as_bytes()).expect("Reading a string can't fail");
+
+        assert_token_vectors_are_equal(
+            &expected_tokens,
+            &result
+        );
+    }
+
+    #[test]
+    fn lexer_lexes_configured_separator_characters() {
+        let test_data = "f 1/2/3,4/5/6, 7/8/9";
+        let expected_tokens = vec!(
+            Token::from(TokenType::FACE, TokenDataType::None(), 1, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 2),
+            Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(1, 2, 3), 1, 3),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 8),
+            Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(4, 5, 6), 1, 9),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 14),
+            Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(7, 8, 9), 1, 16),
+        );
+
+        let mut lexer = Lexer::from_options(LexerOptions {
+            separators: vec![' ', '\t', ','],
+            ..Default::default()
+        });
+        let result = lexer.lex_tokens(&mut test_data.as_bytes()).expect("Reading a string can't fail");
+
+        assert_token_vectors_are_equal(
+            &expected_tokens,
+            &result
+        );
+    }
+
+    #[test]
+    fn lexer_default_separators_are_the_whitespace_characters() {
+        let whitespace: Vec<char> = (0..=char::MAX as u32)
+            .filter_map(char::from_u32)
+            .filter(|c| c.is_whitespace() && *c != '\n' && *c != '\r')
+            .collect();
+
+        assert_eq!(whitespace, LexerOptions::default().separators);
+    }
+
+    #[test]
+    fn lexer_separates_on_form_feeds_by_default() {
+        let result = Lexer::new().lex_tokens(&mut "v 0\x0c0 0\n".as_bytes()).expect("Reading a string can't fail");
+
+        assert_eq!(
+            vec!(TokenType::VERTEX, TokenType::SEPARATOR, TokenType::NUMBER, TokenType::SEPARATOR, TokenType::NUMBER,
+                 TokenType::SEPARATOR, TokenType::NUMBER, TokenType::LINEBREAK),
+            result.iter().map(|t| t.token_type).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn lexer_keeps_names_as_written() {
+        let result = Lexer::new().lex_tokens(&mut "g 01 1/2 v\nv 01 0 0\n".as_bytes()).expect("Reading a string can't fail");
+
+        let strings: Vec<&TokenDataType> = result.iter().filter(|t| t.token_type == TokenType::STRING).map(|t| &t.data).collect();
+        assert_eq!(
+            vec!(
+                &TokenDataType::String(String::from("01")),
+                &TokenDataType::String(String::from("1/2")),
+                &TokenDataType::String(String::from("v")),
+            ),
+            strings,
+            "Names that look like numbers, indices or keywords stay strings"
+        );
+        assert_eq!(TokenType::NUMBER, result[10].token_type, "Only the name line lexes as strings");
+    }
+
+    #[test]
+    fn lexer_does_not_treat_unconfigured_characters_as_separators() {
+        test_lexer_lexes_single_token(
+            &vec![Token::from(TokenType::STRING, TokenDataType::String(String::from("1/2/3,4/5/6")), 1, 1)],
+            "1/2/3,4/5/6"
+        );
+    }
+
+    #[test]
+    fn lexer_decodes_utf8_when_configured() {
+        let mut lexer = Lexer::from_options(LexerOptions {
+            encoding: TextEncoding::Utf8WithLatin1Fallback,
+            ..Default::default()
+        });
+
+        let result = lexer.lex_tokens(&mut "Café x".as_bytes()).expect("Reading a string can't fail");
+
+        assert_token_vectors_are_equal(
+            &vec![
+                Token::from(TokenType::STRING, TokenDataType::String(String::from("Café")), 1, 1),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 5),
+                Token::from(TokenType::STRING, TokenDataType::String(String::from("x")), 1, 6),
+            ],
+            &result
+        );
+        assert!(lexer.warnings().is_empty(), "Valid UTF-8 produces no warnings");
+    }
+
+    #[test]
+    fn lexer_falls_back_to_latin1_for_invalid_utf8() {
+        let mut lexer = Lexer::from_options(LexerOptions {
+            encoding: TextEncoding::Utf8WithLatin1Fallback,
+            ..Default::default()
+        });
+
+        let result = lexer.lex_tokens(&mut &b"Caf\xE9"[..]).expect("Reading a string can't fail");
+
+        assert_token_vectors_are_equal(
+            &vec![Token::from(TokenType::STRING, TokenDataType::String(String::from("Café")), 1, 1)],
+            &result
+        );
+        assert_eq!(1, lexer.warnings().len(), "Latin-1 fallback is reported");
+        assert_eq!(4, lexer.warnings()[0].line_position, "Warning points at the undecodable byte");
+    }
+
+    // Fails every read after the bytes it was given, like a dropped network stream
+    struct FailingReader<'a>(&'a [u8]);
+
+    impl Read for FailingReader<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset"));
+            }
+            self.0.read(buffer)
+        }
+    }
+
+    #[test]
+    fn lexer_returns_read_errors() {
+        let result = Lexer::new().lex_tokens(&mut FailingReader(b"v 0 0 0\n"));
+
+        assert!(
+            matches!(result, Err(WfoError::IoError { path: None, .. })),
+            "A failed read is an error rather than the end of the input"
+        );
+    }
+
+    #[test]
+    fn lexer_reads_latin1_by_default() {
+        test_lexer_lexes_single_token(
+            &vec![Token::from(TokenType::STRING, TokenDataType::String(String::from("CafÃ©")), 1, 1)],
+            "Café"
+        );
+    }
+
+    #[test]
+    fn sniff_text_accepts_obj_text() {
+        assert!(sniff_text("# Caf\u{e9}\nv 1.0 2.0 3.0\n".as_bytes()).is_ok(), "UTF-8 text is accepted");
+        assert!(sniff_text(b"usemtl Caf\xE9 du monde avec un nom assez long\n").is_ok(), "Occasional Latin-1 bytes are accepted");
+        assert!(sniff_text(b"").is_ok(), "Empty input is accepted");
+    }
+
+    #[test]
+    fn sniff_text_rejects_binary_content() {
+        let expected = Err(String::from("Input is not a Wavefront OBJ text file"));
+        let sniff = |bytes: &[u8]| sniff_text(bytes).map_err(|e| e.message());
+
+        assert_eq!(expected, sniff(b"v 1.0\x00 2.0"), "NUL bytes are rejected");
+        assert_eq!(expected, sniff(b"\xFF\xD8\xFF\xE0\x01\x02JFIF"), "JPEG headers are rejected");
+        assert_eq!(expected, sniff(&[0x9C; 64]), "Dense invalid UTF-8 is rejected");
+    }
+
+    #[test]
+    fn lexer_sniffs_input_before_lexing() {
+        let result = Lexer::new().lex_input(&mut &b"v 1.0\x00 2.0\n"[..]);
+
+        assert_eq!(Some(String::from("Input is not a Wavefront OBJ text file")), result.err().map(|e| e.message()));
+        assert_eq!(7, Lexer::new().lex_input(&mut "v 0 0 0".as_bytes()).expect("Text is lexed").len());
+    }
+
+    #[test]
+    fn lexer_lexes_tokens_split_across_pushed_chunks() {
+        let expected_tokens = vec!(
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 1, 3),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 7),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 8),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 12),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 1, 13),
+        );
+
+        let mut lexer = Lexer::new();
+        let mut result = lexer.push_bytes("v 0.".as_bytes());
+        result.append(&mut lexer.push_bytes("00 1.00 2".as_bytes()));
+        result.append(&mut lexer.push_bytes(".00".as_bytes()));
+        result.append(&mut lexer.finish());
 
         assert_token_vectors_are_equal(
             &expected_tokens,
@@ -0,0 +1,868 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Instant;
+
+mod lexer;
+mod token;
+mod parser;
+mod statement;
+mod compiler;
+mod object3d;
+mod vertex;
+mod nan_safe_float;
+mod diagnostic;
+mod error;
+mod baseline;
+mod push_parser;
+mod geometry;
+mod meshlet;
+mod sdf;
+mod lightmap;
+mod layout;
+mod dump;
+mod stats;
+mod grid;
+mod skinning;
+pub mod invariants;
+mod validate;
+mod prepass;
+mod mtl;
+mod line_index;
+mod orientation;
+mod decimate;
+mod seams;
+mod pool;
+mod scene;
+mod line_strip;
+mod face_normals;
+mod material_sort;
+mod overdraw;
+mod options;
+#[cfg(test)]
+mod test_fixtures;
+
+pub use baseline::{assert_matches_baseline, BaselineMismatch, BaselineTolerances};
+pub use compiler::{CompilerOptions, Fixup, FixupKind, IgnoredContent, IgnoredReason, IndexBase, IndexRangePolicy, ObjectSplit};
+pub use decimate::PlanarTolerance;
+pub use diagnostic::{Diagnostic, DiagnosticKind};
+pub use dump::debug_dump;
+pub use error::WfoError;
+pub use face_normals::FaceNormalSource;
+pub use grid::UniformGrid;
+pub use layout::{Endianness, IndexFormat, MemoryLayout, Precision, VertexChunks};
+pub use lexer::{LexerOptions, TextEncoding};
+pub use lightmap::{LightmapUvGenerator, LightmapUvs, TriangleGridGenerator};
+pub use line_index::parse_range;
+pub use line_strip::{LineStripBuffer, StripSeparation};
+pub use meshlet::{build_meshlets, Meshlet, MeshletLimits};
+pub use mtl::{
+    parse_mtl, parse_mtl_str, parse_mtl_str_with_options, parse_mtl_with_options, AlphaMode, Color, ColorSpace, CubeMap,
+    ImageChannel, Material, MtlOptions, ReflectionMap, TextureMap,
+};
+pub use nan_safe_float::Float;
+pub use prepass::{count_statements, StatementCounts};
+pub use scene::{Scene, SharedScene, TextureReference};
+pub use sdf::{bake_sdf, SdfGrid};
+pub use seams::SeamStats;
+pub use skinning::SkinWeights;
+pub use statement::{Statement, StatementType};
+pub use stats::{
+    normalize_texel_density, texel_density_histogram, triangle_area_histogram, triangle_count_histogram, Histogram,
+    TexelDensityAdjustment,
+};
+pub use object3d::{CollisionShape, FaceSource, Object3d, Polyline, SubMesh, TexCoordBounds};
+pub use options::{ParseOptions, ParseReport, Timings};
+pub use overdraw::GpuOptimizeOptions;
+pub use parser::{CommentRetention, ParserOptions, UnknownKeywords};
+pub use pool::{PoolRange, VertexPool};
+pub use push_parser::PushParser;
+pub use validate::{validate_only, ValidationReport};
+pub use vertex::{AttributeDesc, AttributeValue, VertexAttribute, VertexData, VertexFormat, VertexSource, VertexView};
+
+use compiler::Compiler;
+use lexer::Lexer;
+use parser::Parser;
+
+// Runs the whole lexer -> parser -> compiler pipeline. Faces before the first `o` statement go
+// into an object named default_name
+pub fn parse_obj<R: Read>(reader: &mut R, default_name: &str) -> Result<Vec<Object3d>, WfoError> {
+    parse_obj_with_options(reader, default_name, &ParseOptions::default()).map(|(objects, _)| objects)
+}
+
+// Like parse_obj with every stage configured by options. Alongside the objects it returns the
+// warnings the stages recorded, e.g. Latin-1 fallbacks, skipped unknown keywords or repaired
+// faces, in line order
+pub fn parse_obj_with_options<R: Read>(
+    reader: &mut R,
+    default_name: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<Object3d>, Vec<Diagnostic>), WfoError> {
+    parse_obj_with_report(reader, default_name, options).map(|(objects, report)| (objects, report.warnings))
+}
+
+// parse_obj_with_options that also reports the statements the compiler skipped and the faces it
+// repaired, for importers that audit what didn't make it into the objects as written
+pub fn parse_obj_with_report<R: Read>(
+    reader: &mut R,
+    default_name: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<Object3d>, ParseReport), WfoError> {
+    let start = Instant::now();
+    let mut lexer = Lexer::from_options(options.lexer.clone());
+    let tokens = lexer.lex_input(reader)?;
+    let lexed = Instant::now();
+    let mut parser = Parser::from_options(options.parser.clone());
+    let statements = parser.parse_tokens(&tokens)?;
+    let parsed = Instant::now();
+    let mut compiler = Compiler::from_default_name_and_options(&String::from(default_name), options.compiler.clone());
+    let objects = compiler.compile(&statements)?;
+    let compiled = Instant::now();
+
+    let mut warnings: Vec<Diagnostic> = lexer.warnings().iter()
+        .chain(parser.warnings())
+        .chain(compiler.warnings())
+        .cloned()
+        .collect();
+    warnings.sort_by_key(|d| (d.line_number, d.line_position));
+
+    Ok((objects, ParseReport {
+        warnings,
+        ignored: compiler.ignored_content().to_vec(),
+        fixups: compiler.fixups().to_vec(),
+        timings: options.collect_timings.then(|| Timings {
+            lex: lexed - start,
+            parse: parsed - lexed,
+            compile: compiled - parsed,
+            per_object: compiler.object_timings().to_vec(),
+        }),
+    }))
+}
+
+// The compiler stage on its own, for statements from a PushParser or parse_with_recovery
+pub fn compile_statements(
+    statements: &[Statement],
+    default_name: &str,
+    options: &CompilerOptions,
+) -> Result<Vec<Object3d>, WfoError> {
+    Compiler::from_default_name_and_options(&String::from(default_name), options.clone()).compile(statements)
+}
+
+// Keeps what compiled before a failure, so a viewer can show the geometry of a broken file and
+// point at the error. Input that doesn't parse has nothing to salvage
+pub fn parse_obj_with_salvage<R: Read>(
+    reader: &mut R,
+    default_name: &str,
+    options: &ParseOptions,
+) -> (Vec<Object3d>, Option<WfoError>) {
+    let tokens = match Lexer::from_options(options.lexer.clone()).lex_input(reader) {
+        Ok(x) => x,
+        Err(e) => return (Vec::new(), Some(e)),
+    };
+    let statements = match Parser::from_options(options.parser.clone()).parse_tokens(&tokens) {
+        Ok(x) => x,
+        Err(e) => return (Vec::new(), Some(e)),
+    };
+
+    Compiler::from_default_name_and_options(&String::from(default_name), options.compiler.clone()).compile_with_salvage(&statements)
+}
+
+// Parses without compiling and without stopping at errors: every line yields either a statement
+// or exactly one diagnostic, so tools can annotate the whole file. options.parser.max_errors caps
+// the diagnostics of input that isn't OBJ at all
+pub fn parse_with_recovery(input: &str, options: &ParseOptions) -> (Vec<Statement>, Vec<Diagnostic>) {
+    let mut lexer = Lexer::from_options(options.lexer.clone());
+    let tokens = match lexer.lex_input(&mut input.as_bytes()) {
+        Ok(x) => x,
+        Err(e) => return (Vec::new(), vec!(Diagnostic::from(e.message(), 0, 0))),
+    };
+    let (statements, mut diagnostics) = Parser::from_options(options.parser.clone()).parse_tokens_with_recovery(&tokens);
+    if !lexer.warnings().is_empty() {
+        diagnostics.extend(lexer.warnings().iter().cloned());
+        diagnostics.sort_by_key(|d| (d.line_number, d.line_position));
+    }
+
+    (statements, diagnostics)
+}
+
+// parse_with_recovery for tools that want errors rather than diagnostics, each naming the
+// statement it interrupted
+pub fn parse_collecting_errors(input: &str, options: &ParseOptions) -> (Vec<Statement>, Vec<WfoError>) {
+    let mut lexer = Lexer::from_options(options.lexer.clone());
+    let tokens = match lexer.lex_input(&mut input.as_bytes()) {
+        Ok(x) => x,
+        Err(e) => return (Vec::new(), vec!(e)),
+    };
+    let (statements, mut errors) = Parser::from_options(options.parser.clone()).parse_tokens_collecting_errors(&tokens);
+    if !lexer.warnings().is_empty() {
+        errors.extend(lexer.warnings().iter().map(|w| WfoError::LexError {
+            message: w.message.clone(),
+            line_number: w.line_number,
+            line_position: w.line_position,
+        }));
+        errors.sort_by_key(|e| e.location());
+    }
+
+    (statements, errors)
+}
+
+// The whole text is at hand, so a pre-pass sizes the compiler's buffers before parsing
+pub fn parse_obj_str(text: &str, default_name: &str) -> Result<Vec<Object3d>, WfoError> {
+    let counts = count_statements(text.as_bytes());
+    let tokens = Lexer::new().lex_input(&mut text.as_bytes())?;
+    let statements = Parser::new().parse_tokens(&tokens)?;
+
+    let mut compiler = Compiler::from_default_name(&String::from(default_name));
+    compiler.reserve(&counts);
+    compiler.compile(&statements)
+}
+
+// The default object name is the file name without its extension
+pub fn parse_obj_file<P: AsRef<Path>>(path: P) -> Result<Vec<Object3d>, WfoError> {
+    let path = path.as_ref();
+
+    parse_obj(&mut open_file(path)?, &file_stem(path))
+}
+
+// Like parse_obj_file, also loading every library named by an `mtllib` statement and moving
+// collision hulls into their own list. Library paths are relative to the OBJ file's directory.
+// When libraries define the same material name the later definition wins
+pub fn load_scene<P: AsRef<Path>>(path: P) -> Result<Scene, WfoError> {
+    load_scene_with_options(path, &ParseOptions::default())
+}
+
+// load_scene with every stage configured by options. A library that can't be opened or parsed is
+// a warning at its mtllib statement naming the path that was tried, and the geometry is still
+// returned, unless options.strict_material_libraries makes it an error. options.material_overrides
+// replace library materials by name
+pub fn load_scene_with_options<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<Scene, WfoError> {
+    let path = path.as_ref();
+    let start = Instant::now();
+    let mut lexer = Lexer::from_options(options.lexer.clone());
+    let tokens = lexer.lex_input(&mut open_file(path)?)?;
+    let lexed = Instant::now();
+    let mut parser = Parser::from_options(options.parser.clone());
+    let statements = parser.parse_tokens(&tokens)?;
+    let parsed = Instant::now();
+    let mut compiler = Compiler::from_default_name_and_options(&file_stem(path), options.compiler.clone());
+    let (objects, collision_objects) = Object3d::partition_collision_objects(compiler.compile(&statements)?);
+    let compiled = Instant::now();
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut scene = Scene { objects, collision_objects, ..Default::default() };
+    scene.timings = options.collect_timings.then(|| Timings {
+        lex: lexed - start,
+        parse: parsed - lexed,
+        compile: compiled - parsed,
+        per_object: compiler.object_timings().to_vec(),
+    });
+    scene.warnings = lexer.warnings().iter()
+        .chain(parser.warnings())
+        .chain(compiler.warnings())
+        .cloned()
+        .collect();
+    for (library, line_number, line_position) in compiler.material_libraries() {
+        let library = directory.join(library);
+        match load_material_library(&library, &options.mtl) {
+            Ok(materials) => {
+                for material in materials {
+                    scene.materials.insert(material.name.clone(), material);
+                }
+            },
+            Err(e) if options.strict_material_libraries => return Err(e),
+            Err(e) => scene.warnings.push(Diagnostic {
+                path: Some(library.clone()),
+                ..Diagnostic::warning(format!("Material library not loaded: {}", e), *line_number, *line_position)
+            }),
+        }
+        scene.mtl_libs.push(library);
+    }
+    for (name, material) in &options.material_overrides {
+        scene.materials.insert(name.clone(), Material { name: name.clone(), ..material.clone() });
+    }
+    scene.warnings.sort_by_key(|d| (d.line_number, d.line_position));
+
+    Ok(scene)
+}
+
+// MTL errors are located in the library, so they name it
+fn load_material_library(path: &Path, options: &MtlOptions) -> Result<Vec<Material>, WfoError> {
+    parse_mtl_with_options(&mut open_file(path)?, options).map_err(|e| match e {
+        WfoError::MtlError { message, line_number, line_position, .. } => {
+            WfoError::MtlError { message, line_number, line_position, path: Some(path.to_path_buf()) }
+        },
+        e => e,
+    })
+}
+
+fn open_file(path: &Path) -> Result<BufReader<File>, WfoError> {
+    let file = File::open(path).map_err(|source| WfoError::IoError { path: Some(path.to_path_buf()), source })?;
+
+    Ok(BufReader::new(file))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_obj_str_runs_the_whole_pipeline() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1// 2// 3//\no Named\nf 3// 2// 1//\n", "default")
+            .expect("Valid input parses");
+
+        assert_eq!(2, objects.len(), "One object per o statement plus the default object");
+        assert_eq!("default", objects[0].name, "Faces before any o statement use the default name");
+        assert_eq!("Named", objects[1].name);
+        assert_eq!([1.0, 0.0, 0.0], objects[1].vertices().nth(1).unwrap().pos());
+    }
+
+    #[test]
+    fn parse_obj_str_reads_faces_without_slashes() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", "default").expect("Bare index faces parse");
+
+        assert_eq!(VertexFormat::VertexP, objects[0].format, "Bare indices reference positions only");
+        assert_eq!(vec!(0u64, 1, 2), objects[0].index_buffer);
+    }
+
+    #[test]
+    fn parse_obj_str_resolves_relative_indices() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n", "default").expect("Relative indices parse");
+
+        assert_eq!([0.0, 1.0, 0.0], objects[0].vertices().nth(2).unwrap().pos(), "-1 is the last declared position");
+    }
+
+    #[test]
+    fn parse_obj_str_tracks_smoothing_groups() {
+        let objects = parse_obj_str("s off\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\ns 4\nf 3 2 1\n", "default")
+            .expect("Smoothing group statements parse");
+
+        assert_eq!(vec!(0u32, 4), objects[0].smoothing_groups, "off is smoothing group 0");
+    }
+
+    #[test]
+    fn parse_obj_str_keeps_line_elements() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nl 1 2 3\nl 1/1 3/1\n", "default")
+            .expect("Line elements parse");
+
+        assert_eq!(2, objects[0].polylines.len());
+        assert_eq!(3, objects[0].polylines[0].points.len());
+        assert_eq!(vec!((f!(0.0), f!(0.0), f!(0.0)), (f!(0.0), f!(1.0), f!(0.0))), objects[0].polylines[1].points);
+    }
+
+    #[test]
+    fn parse_obj_str_keeps_optional_w_components() {
+        let objects = parse_obj_str("v 0 0 0 0.5\nv 1 0 0\nv 0 1 0\nvt 0 0 1\nf 1/1 2/1 3/1\n", "default")
+            .expect("Optional w components parse");
+        let vertices: Vec<VertexView> = objects[0].vertices().collect();
+
+        assert_eq!(Some(0.5), vertices[0].pos_w());
+        assert_eq!(None, vertices[1].pos_w(), "w is only set where the file gives one");
+        assert_eq!(Some(1.0), vertices[0].uv_w());
+    }
+
+    #[test]
+    fn parse_obj_str_defaults_missing_tex_coord_components() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5\nf 1/1 2/1 3/1\n", "default")
+            .expect("Single component tex coords parse");
+
+        assert_eq!(Some([0.5, 0.0]), objects[0].vertices().next().unwrap().uv(), "v defaults to 0");
+    }
+
+    #[test]
+    fn objects_write_into_caller_buffers() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", "default").expect("Valid input parses");
+        let layout = objects[0].memory_layout(Precision::F32);
+        let (mut vertices, mut indices) = (vec![0u8; layout.vertex_buffer_size], vec![0u8; layout.index_buffer_size]);
+
+        assert_eq!(Ok(36), objects[0].write_vertices_into(&mut vertices, &layout));
+        assert_eq!(Ok(6), objects[0].write_indices_into(&mut indices, &layout));
+        assert_eq!(IndexFormat::U16, layout.index_format);
+        assert_eq!(
+            vertices,
+            objects[0].vertex_chunks(&layout, 24).collect::<Result<Vec<_>, _>>().expect("Chunks serialize").concat(),
+            "Chunked uploads match the whole buffer"
+        );
+
+        let mut big_layout = layout.clone();
+        big_layout.convert_endianness(&mut vertices, &mut indices, Endianness::Big).expect("Buffers match the layout");
+        assert_eq!(layout.with_endianness(Endianness::Big), big_layout);
+        assert_eq!([0, 1], indices[2..4], "Indices are big endian after converting");
+    }
+
+    #[test]
+    fn parse_obj_with_options_applies_every_stage() {
+        let options = ParseOptions {
+            lexer: LexerOptions { separators: vec!(' ', ','), ..Default::default() },
+            parser: ParserOptions { on_unknown: UnknownKeywords::Collect, ..Default::default() },
+            compiler: CompilerOptions { index_base: IndexBase::Zero, ..Default::default() },
+            ..Default::default()
+        };
+
+        let (objects, warnings) = parse_obj_with_options(&mut "vp 0.5\nv 0,0,0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n".as_bytes(), "default", &options)
+            .expect("Options make the input valid");
+
+        assert_eq!(vec!(0u64, 1, 2), objects[0].index_buffer, "Zero based indices load");
+        assert_eq!(vec!((1, DiagnosticKind::Warning)), warnings.iter().map(|w| (w.line_number, w.kind)).collect::<Vec<_>>());
+        assert!(parse_obj(&mut "vp 0.5\n".as_bytes(), "default").is_err(), "Unknown keywords are errors by default");
+    }
+
+    #[test]
+    fn parse_obj_with_report_lists_skipped_statements() {
+        let input = "# exported by hand\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+
+        let (objects, report) = parse_obj_with_report(&mut input.as_bytes(), "default", &ParseOptions::default())
+            .expect("Input parses");
+
+        assert_eq!(1, objects.len());
+        assert_eq!(
+            vec!(IgnoredContent { statement_type: StatementType::COMMENT, line_number: 1, reason: IgnoredReason::Comment }),
+            report.ignored
+        );
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn parse_obj_with_report_lists_repaired_faces() {
+        let input = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 7\n";
+        let options = ParseOptions {
+            compiler: CompilerOptions { index_range_policy: IndexRangePolicy::DropFace, ..Default::default() },
+            ..Default::default()
+        };
+
+        let (_, report) = parse_obj_with_report(&mut input.as_bytes(), "default", &options).expect("The face is dropped");
+
+        assert_eq!(1, report.fixups.len());
+        assert_eq!(FixupKind::FaceDropped, report.fixups[0].kind);
+        assert_eq!((4, vec!([1, 0, 0], [2, 0, 0], [7, 0, 0])), (report.fixups[0].line_number, report.fixups[0].before.clone()));
+        assert_eq!(report.warnings.len(), report.fixups.len(), "Each fixup has its warning");
+    }
+
+    #[test]
+    fn parse_obj_with_report_times_stages_on_request() {
+        let input = "v 0 0 0\nv 1 0 0\nv 0 1 0\no First\nf 1 2 3\no Second\nf 3 2 1\n";
+        let options = ParseOptions { collect_timings: true, ..Default::default() };
+
+        let (_, report) = parse_obj_with_report(&mut input.as_bytes(), "default", &options).expect("Input parses");
+        let (_, untimed) = parse_obj_with_report(&mut input.as_bytes(), "default", &ParseOptions::default()).expect("Input parses");
+
+        let timings = report.timings.expect("Timings were asked for");
+        assert_eq!(vec!("First", "Second"), timings.per_object.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>());
+        assert!(timings.per_object.iter().map(|(_, d)| *d).sum::<std::time::Duration>() <= timings.compile);
+        assert_eq!(None, untimed.timings, "Timings are opt in");
+    }
+
+    #[test]
+    fn retained_faces_keep_their_arity() {
+        let input = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\nf 1 2 3\n";
+        let options = ParseOptions {
+            compiler: CompilerOptions { retain_faces: true, ..Default::default() },
+            ..Default::default()
+        };
+
+        let (objects, _) = parse_obj_with_options(&mut input.as_bytes(), "default", &options).expect("Input parses");
+
+        assert_eq!(vec!(4, 3), objects[0].faces.iter().map(|f| f.arity()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tex_coord_bounds_flag_wrapping_uvs() {
+        let parse = |u: &str| parse_obj_str(&format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt {} 0\nf 1/1 2/2 3/1\n", u), "default")
+            .expect("Input parses");
+
+        assert!(!parse("1").remove(0).tex_coord_bounds.expect("UVs are tracked").exceeds_unit_square());
+        assert!(parse("2.5").remove(0).tex_coord_bounds.expect("UVs are tracked").exceeds_unit_square());
+    }
+
+    #[test]
+    fn triangles_map_back_to_their_face_statements() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\nf 1 2 3\n", "default").expect("Input parses");
+
+        let lines: Vec<Option<u64>> = (0..4).map(|t| objects[0].triangle_source(t).map(|s| s.line_number)).collect();
+
+        assert_eq!(vec!(Some(5), Some(5), Some(6), None), lines, "Both halves of the quad come from line 5");
+    }
+
+    #[test]
+    fn objects_split_by_vertex_limit() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n", "default").expect("Input parses");
+
+        let chunks = objects[0].split_by_vertex_limit(3).expect("A triangle fits in 3 vertices");
+
+        assert_eq!(vec!(3, 3), chunks.iter().map(|c| c.vertex_buffer.len()).collect::<Vec<_>>(), "Shared vertices are duplicated");
+        assert_eq!(1, objects[0].split_for_u16_indices().len(), "Small objects stay whole");
+    }
+
+    #[test]
+    fn zero_based_files_can_use_relative_indices() {
+        let zero_based = |index_base| ParseOptions {
+            compiler: CompilerOptions { index_base, ..Default::default() },
+            ..Default::default()
+        };
+        let positions = |input: &str, options: &ParseOptions| {
+            let (objects, _) = parse_obj_with_options(&mut input.as_bytes(), "default", options).expect("Input parses");
+            objects[0].vertex_sources.iter().map(|s| s.pos_index).collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!(1, 2, 3), positions("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n", &zero_based(IndexBase::Zero)));
+        assert_eq!(vec!(1, 2, 3), positions("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 -1\n", &zero_based(IndexBase::Zero)));
+        assert_eq!(
+            vec!(1, 2, 3),
+            positions("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 -1\n", &zero_based(IndexBase::Auto)),
+            "Relative indices don't hide a 0 based file"
+        );
+
+        let (objects, _) = parse_obj_with_options(&mut "v 0 0 0\nv 1 0 0\nl 0 -1\n".as_bytes(), "default", &zero_based(IndexBase::Zero))
+            .expect("Relative line indices resolve in 0 based files");
+        assert_eq!(2, objects[0].polylines[0].points.len());
+    }
+
+    #[test]
+    fn numeric_names_keep_their_text() {
+        let options = ParseOptions {
+            compiler: CompilerOptions { split_by: ObjectSplit::Groups, ..Default::default() },
+            ..Default::default()
+        };
+
+        let (objects, _) = parse_obj_with_options(&mut "v 0 0 0\nv 1 0 0\nv 0 1 0\ng 01\nf 1 2 3\n".as_bytes(), "default", &options)
+            .expect("Input parses");
+        let named = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\no 2.50\nf 1 2 3\n", "default").expect("Input parses");
+
+        assert_eq!("01", objects[0].name);
+        assert_eq!("2.50", named[0].name, "Object names that look like numbers are accepted as written");
+    }
+
+    #[test]
+    fn parse_obj_with_salvage_keeps_objects_before_the_failure() {
+        let input = "v 0 0 0\nv 1 0 0\nv 0 1 0\no Good\nf 1 2 3\no Broken\nf 1 2 9\n";
+
+        let (objects, error) = parse_obj_with_salvage(&mut input.as_bytes(), "default", &ParseOptions::default());
+
+        assert_eq!(vec!("Good"), objects.iter().map(|o| o.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(Some((7, 1)), error.expect("The bad face is reported").location());
+    }
+
+    #[test]
+    fn parse_with_recovery_covers_every_line() {
+        let options = ParseOptions { parser: ParserOptions { max_errors: Some(1), ..Default::default() }, ..Default::default() };
+        let (statements, diagnostics) = parse_with_recovery("v 0 0 0\nv 1\nv 2\n", &ParseOptions::default());
+
+        assert_eq!(vec!((StatementType::VERTEX, 1)), statements.iter().map(|s| (s.statement_type(), s.line_number())).collect::<Vec<_>>());
+        assert_eq!(vec!(2u64, 3), diagnostics.iter().map(|d| d.line_number).collect::<Vec<_>>());
+        assert_eq!(2, parse_with_recovery("v 1\nv 2\nv 3\n", &options).1.len(), "The first error and the stop marker");
+    }
+
+    #[test]
+    fn parse_collecting_errors_reports_every_bad_line() {
+        let (statements, errors) = parse_collecting_errors("v 1\nv 0 0 0\nvn 2\n", &ParseOptions::default());
+
+        assert_eq!(1, statements.len());
+        assert_eq!(vec!(Some(1), Some(3)), errors.iter().map(|e| e.location().map(|l| l.0)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parse_range_reports_lines_of_the_whole_file() {
+        let (statements, diagnostics) = parse_range("v 0 0 0\nv 1\nv 0 1 0\n", 2..4).expect("Lines are in the file");
+
+        assert_eq!(vec!(3u64), statements.iter().map(|s| s.line_number()).collect::<Vec<_>>());
+        assert_eq!(2, diagnostics[0].line_number);
+    }
+
+    #[test]
+    fn parse_obj_str_returns_parse_errors() {
+        assert!(parse_obj_str("v 1.0\n", "default").is_err(), "Incomplete statements are an error");
+    }
+
+    #[test]
+    fn parse_obj_file_names_default_object_after_file() {
+        let path = std::env::temp_dir().join("wfo_parser_lib_test_cube.obj");
+        std::fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1// 2// 3//\n").unwrap();
+
+        let objects = parse_obj_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("wfo_parser_lib_test_cube", objects.expect("File parses")[0].name);
+    }
+
+    #[test]
+    fn load_scene_loads_libraries_next_to_the_file() {
+        let directory = std::env::temp_dir().join("wfo_parser_lib_test_materials");
+        std::fs::create_dir_all(directory.join("materials")).unwrap();
+        std::fs::write(directory.join("crate.obj"), "mtllib materials/wood.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Oak\nf 1 2 3\n").unwrap();
+        std::fs::write(directory.join("materials/wood.mtl"), "newmtl Oak\nKd 0.6 0.4 0.2\n").unwrap();
+
+        let result = load_scene(directory.join("crate.obj"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let scene = result.expect("File and library parse");
+        assert_eq!("crate", scene.objects[0].name);
+        assert_eq!(vec!(directory.join("materials/wood.mtl")), scene.mtl_libs);
+        assert_eq!(Some("Oak"), scene.material_of(&scene.objects[0]).map(|m| m.name.as_str()), "usemtl names resolve to materials");
+    }
+
+    #[test]
+    fn load_scene_separates_collision_objects() {
+        let path = std::env::temp_dir().join("wfo_parser_lib_test_collision.obj");
+        std::fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\no Crate\nf 1 2 3\no UBX_Crate\nf 1 2 3\n").unwrap();
+
+        let result = load_scene(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let scene = result.expect("File parses");
+        assert_eq!(vec!("Crate"), scene.objects.iter().map(|o| o.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(Some(CollisionShape::Box), scene.collision_objects[0].collision_shape());
+    }
+
+    #[test]
+    fn objects_compare_against_a_baseline() {
+        let parse = |input: &str| parse_obj(&mut input.as_bytes(), "Triangle").expect("Triangle parses");
+        let baseline = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Red\nf 1 2 3\n");
+
+        assert_eq!(Ok(()), assert_matches_baseline(&baseline, &baseline, &BaselineTolerances::default()));
+
+        let recolored = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Blue\nf 1 2 3\n");
+        let mismatches = assert_matches_baseline(&recolored, &baseline, &BaselineTolerances::default())
+            .expect_err("The material changed");
+        assert!(matches!(mismatches[0], BaselineMismatch::Material { .. }), "{:?}", mismatches);
+    }
+
+    #[test]
+    fn push_parser_output_compiles_like_parse_obj() {
+        let input = "v 0 0 0\nv 1 0 0\nv 0 1 0\no Streamed\nf 1 2 3\n";
+        let mut push_parser = PushParser::from_options(&ParseOptions::default());
+
+        for chunk in input.as_bytes().chunks(5) {
+            push_parser.push_bytes(chunk).expect("Every chunk parses");
+        }
+        let statements = push_parser.finish().expect("Input ends cleanly");
+        let objects = compile_statements(&statements, "default", &CompilerOptions::default()).expect("Statements compile");
+
+        assert_eq!(parse_obj_str(input, "default").unwrap(), objects, "Chunking doesn't change the result");
+    }
+
+    // cube from -1 to 1 with outward facing triangles
+    const CUBE: &str = "v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\nv -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\n\
+        f 1 3 2\nf 1 4 3\nf 5 6 7\nf 5 7 8\nf 1 2 6\nf 1 6 5\nf 4 8 7\nf 4 7 3\nf 1 5 8\nf 1 8 4\nf 2 3 7\nf 2 7 6\n";
+
+    #[test]
+    fn objects_bake_into_signed_distance_fields() {
+        let objects = parse_obj_str(CUBE, "Cube").expect("Cube parses");
+
+        let grid = bake_sdf(&objects[0], 4, 1).expect("A cube has triangles to bake");
+
+        assert_eq!([6, 6, 6], grid.resolution);
+        assert!(grid.get(2, 2, 2) < 0.0, "The center of the cube is inside");
+        assert!(grid.get(0, 0, 0) > 0.0, "The padding is outside");
+    }
+
+    #[test]
+    fn debug_dump_snapshots_every_stage() {
+        let mut output: Vec<u8> = Vec::new();
+
+        debug_dump(&mut "o Tri\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".as_bytes(), &mut output).expect("Triangle dumps");
+        let dump = String::from_utf8(output).expect("Dump is valid utf-8");
+
+        assert!(dump.starts_with("tokens "), "{}", dump);
+        assert!(dump.contains("statements 5\n"), "{}", dump);
+        assert!(dump.contains("objects 1\n  object \"Tri\" VertexP vertices 3 indices 3\n"), "{}", dump);
+    }
+
+    #[test]
+    fn objects_summarize_into_histograms() {
+        let objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 2 0\no Small\nf 1 2 3\no Large\nf 1 2 4\nf 1 2 3\n", "default")
+            .expect("Objects parse");
+
+        let counts = triangle_count_histogram(&objects, 2);
+        let areas = triangle_area_histogram(&objects, 2);
+
+        assert_eq!(vec!(1u64, 1), counts.counts, "One object per triangle count");
+        assert_eq!((0.5, 1.0), (areas.min, areas.max));
+        assert_eq!(3, areas.total(), "Every triangle of every object is counted");
+        assert_eq!(0, texel_density_histogram(&objects, (256, 256), 2).total(), "Untextured objects are left out");
+    }
+
+    #[test]
+    fn normalize_texel_density_brings_objects_to_the_target() {
+        let mut objects = parse_obj_str("v 0 0 0\nv 2 0 0\nv 0 2 0\nvt 0 0\nvt 1 0\nvt 0 1\nf 1/1 2/2 3/3\n", "Textured")
+            .expect("Textured triangle parses");
+
+        let adjustments = normalize_texel_density(&mut objects, 64.0, (256, 256), true);
+
+        assert_eq!(1, adjustments.len());
+        assert_eq!(128.0, adjustments[0].density, "256 texels span 2 world units");
+        assert_eq!(0.5, adjustments[0].scale);
+        assert_eq!(Some(64.0), objects[0].texel_density((256, 256)), "UVs are rescaled when applied");
+    }
+
+    #[test]
+    fn objects_index_into_uniform_grids() {
+        let objects = parse_obj_str(CUBE, "Cube").expect("Cube parses");
+
+        let grid = UniformGrid::from_object(&objects[0], 1).expect("A cube has triangles to bucket");
+
+        assert_eq!(12, grid.query_box([-1.0; 3], [1.0; 3]).len(), "The whole grid holds every triangle");
+        assert_eq!(vec!(2u32, 3), grid.query_box([0.0, 0.0, 1.0], [0.0, 0.0, 1.0]), "Only the +z face reaches the top center cell");
+    }
+
+    #[test]
+    fn objects_take_generated_lightmap_uvs() {
+        let mut objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n", "Quad").expect("Quad parses");
+
+        objects[0].generate_lightmap_uvs(&TriangleGridGenerator::default()).expect("Lightmap UVs generate");
+
+        assert_eq!(6, objects[0].vertex_buffer.len(), "Every triangle gets its own vertices");
+        assert_eq!(objects[0].vertex_buffer.len(), objects[0].lightmap_tex_coords.len());
+        assert!(objects[0].lightmap_tex_coords.iter().flatten().all(|x| (0.0..=1.0).contains(x)), "UVs stay in the unit square");
+    }
+
+    // A generator that returns another generator's output unchanged, as a binding would
+    struct Passthrough(LightmapUvs);
+
+    impl LightmapUvGenerator for Passthrough {
+        fn generate(&self, _: &Object3d) -> Result<LightmapUvs, WfoError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn lightmap_uv_generators_plug_in_from_outside_the_crate() {
+        let mut objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", "Triangle").expect("Triangle parses");
+        let uvs = LightmapUvs {
+            vertex_remap: vec!(0, 1, 2),
+            index_buffer: vec!(0, 1, 2),
+            tex_coords: vec!([0.0, 0.0], [1.0, 0.0], [0.0, 1.0]),
+        };
+
+        objects[0].generate_lightmap_uvs(&Passthrough(uvs)).expect("A valid UV set applies");
+
+        assert_eq!(vec!([0.0, 0.0], [1.0, 0.0], [0.0, 1.0]), objects[0].lightmap_tex_coords);
+    }
+
+    #[test]
+    fn objects_attach_skin_weights_from_a_sidecar() {
+        let mut objects = parse_obj_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", "Skinned").expect("Triangle parses");
+        let weights = SkinWeights::from_csv("# v,joint,weight\n2,3,0.25,1,0.75\n".as_bytes()).expect("Sidecar parses");
+
+        objects[0].attach_skin_weights(&weights).expect("Every vertex has a source");
+
+        assert_eq!(vec!([0u16, 0, 0, 0], [1, 3, 0, 0], [0, 0, 0, 0]), objects[0].joint_indices);
+        assert_eq!([0.75, 0.25, 0.0, 0.0], objects[0].joint_weights[1], "The strongest influence comes first");
+    }
+
+    #[test]
+    fn objects_build_into_meshlets() {
+        let objects = parse_obj(&mut "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nf 1 2 3\nf 2 4 3\n".as_bytes(), "Quad")
+            .expect("Quad parses");
+
+        let meshlets = build_meshlets(&objects[0], &MeshletLimits::default()).expect("Default limits are valid");
+
+        assert_eq!(1, meshlets.len());
+        assert_eq!(2, meshlets[0].triangles.len());
+        assert!(build_meshlets(&objects[0], &MeshletLimits { max_vertices: 300, max_triangles: 124 }).is_err());
+    }
+
+    #[test]
+    fn load_scene_applies_material_overrides() {
+        let directory = std::env::temp_dir().join("wfo_parser_lib_test_material_overrides");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("crate.obj"), "mtllib wood.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Oak\nf 1 2 3\n").unwrap();
+        std::fs::write(directory.join("wood.mtl"), "newmtl Oak\nKd 0.6 0.4 0.2\nnewmtl Pine\nKd 0.8 0.7 0.5\n").unwrap();
+        let mut options = ParseOptions::default();
+        options.material_overrides.insert(String::from("Oak"), Material { diffuse: Some(Color::Rgb(f!(1.0), f!(0.0), f!(0.0))), ..Material::from(String::new()) });
+
+        let scene = load_scene_with_options(directory.join("crate.obj"), &options);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let scene = scene.expect("Scene loads");
+        let oak = scene.material_of(&scene.objects[0]).expect("usemtl resolves to the override");
+        assert_eq!("Oak", oak.name, "The override takes the name it replaces");
+        assert_eq!(Some(Color::Rgb(f!(1.0), f!(0.0), f!(0.0))), oak.diffuse);
+        assert!(scene.materials.contains_key("Pine"), "Materials without an override are kept");
+    }
+
+    #[test]
+    fn load_scene_reports_missing_libraries() {
+        let directory = std::env::temp_dir().join("wfo_parser_lib_test_missing_materials");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("crate.obj"), "v 0 0 0\nv 1 0 0\nv 0 1 0\nmtllib gone.mtl\nf 1 2 3\n").unwrap();
+
+        let scene = load_scene(directory.join("crate.obj"));
+        let strict = load_scene_with_options(
+            directory.join("crate.obj"),
+            &ParseOptions { strict_material_libraries: true, ..Default::default() }
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let scene = scene.expect("A missing library doesn't fail the scene");
+        assert_eq!(1, scene.objects.len(), "The geometry is still returned");
+        assert_eq!(1, scene.warnings.len());
+        assert_eq!(DiagnosticKind::Warning, scene.warnings[0].kind);
+        assert_eq!(4, scene.warnings[0].line_number, "The warning points at the mtllib statement");
+        assert_eq!(Some(directory.join("gone.mtl")), scene.warnings[0].path, "The warning names the path that was tried");
+        assert!(strict.expect_err("Strict loading fails").to_string().starts_with("Could not open"));
+    }
+
+    #[test]
+    fn every_entry_point_rejects_binary_input() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+        let text = "v 0\x00 0 0\n";
+        let message = |e: WfoError| e.message();
+        let expected = Some(String::from("Input is not a Wavefront OBJ text file"));
+
+        assert_eq!(expected, parse_obj(&mut &png[..], "default").err().map(message));
+        assert_eq!(expected, parse_obj_with_options(&mut &png[..], "default", &ParseOptions::default()).err().map(message));
+        assert_eq!(expected, parse_obj_with_salvage(&mut &png[..], "default", &ParseOptions::default()).1.map(message));
+        assert_eq!(expected, parse_obj_str(text, "default").err().map(message));
+        assert_eq!(expected, parse_with_recovery(text, &ParseOptions::default()).1.first().map(|d| d.message.clone()));
+        assert_eq!(expected, parse_collecting_errors(text, &ParseOptions::default()).1.into_iter().next().map(message));
+    }
+
+    #[test]
+    fn load_scene_locates_errors_in_the_library() {
+        let directory = std::env::temp_dir().join("wfo_parser_lib_test_broken_materials");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("crate.obj"), "mtllib broken.mtl\n").unwrap();
+        std::fs::write(directory.join("broken.mtl"), "newmtl Oak\nKd 0.6 0.4\n").unwrap();
+
+        let scene = load_scene(directory.join("crate.obj"));
+        let strict = load_scene_with_options(
+            directory.join("crate.obj"),
+            &ParseOptions { strict_material_libraries: true, ..Default::default() }
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let warnings = scene.expect("An unparsable library doesn't fail the scene").warnings;
+        assert_eq!(
+            vec!(format!("Material library not loaded: {}:2:1: Expected 1 or 3 numbers after Kd", directory.join("broken.mtl").display())),
+            warnings.iter().map(|w| w.message.clone()).collect::<Vec<String>>()
+        );
+        match strict.expect_err("Strict loading fails on a malformed library") {
+            WfoError::MtlError { line_number, path, .. } => {
+                assert_eq!(2, line_number, "The error points at the library line");
+                assert_eq!(Some(directory.join("broken.mtl")), path);
+            },
+            e => panic!("Expected an MTL error but got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn parse_obj_file_rejects_binary_files() {
+        let path = std::env::temp_dir().join("wfo_parser_lib_test_binary.obj");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR").unwrap();
+
+        let result = parse_obj_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("Input is not a Wavefront OBJ text file", result.expect_err("A PNG isn't OBJ").message());
+    }
+
+    #[test]
+    fn parse_obj_file_reports_missing_files() {
+        let result = parse_obj_file("/nonexistent/missing.obj");
+
+        assert!(result.expect_err("Missing file is an error").to_string().starts_with("Could not open /nonexistent/missing.obj"));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error::WfoError;
+use crate::geometry::{cross, dot, length, normalize, sub};
+use crate::object3d::Object3d;
+
+// A second, non-overlapping UV set. Unwrapping usually has to cut seams, so generators return a
+// new vertex list (each entry naming the original vertex it copies) and a new index buffer
+#[derive(PartialEq, Debug, Clone)]
+pub struct LightmapUvs {
+    pub vertex_remap: Vec<u64>,
+    pub index_buffer: Vec<u64>,
+    pub tex_coords: Vec<[f32; 2]>,
+}
+
+// Extension point for plugging in an unwrapper (e.g. bindings to xatlas). Generators that can't
+// unwrap an object report it as WfoError::InvalidArgument
+pub trait LightmapUvGenerator {
+    fn generate(&self, obj: &Object3d) -> Result<LightmapUvs, WfoError>;
+}
+
+// Baseline generator that gives every triangle its own cell in a square grid. Triangles keep their
+// shape but not their relative size, which is enough for previews and small props
+pub struct TriangleGridGenerator {
+    // empty space kept around each triangle, as a fraction of a cell
+    pub padding: f32,
+}
+
+impl Default for TriangleGridGenerator {
+    fn default() -> Self {
+        TriangleGridGenerator {
+            padding: 0.05,
+        }
+    }
+}
+
+impl LightmapUvGenerator for TriangleGridGenerator {
+    fn generate(&self, obj: &Object3d) -> Result<LightmapUvs, WfoError> {
+        let positions: Vec<[f32; 3]> = obj.vertices().map(|v| v.pos()).collect();
+        let triangle_count = obj.index_buffer.len() / 3;
+        let cells_per_row = (triangle_count as f32).sqrt().ceil().max(1.0) as usize;
+        let cell_size = 1.0 / cells_per_row as f32;
+        let mut uvs = LightmapUvs {
+            vertex_remap: Vec::new(),
+            index_buffer: Vec::new(),
+            tex_coords: Vec::new(),
+        };
+
+        for (triangle, indices) in obj.index_buffer.chunks(3).enumerate() {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[indices[i] as usize]);
+            let flat = flatten_triangle(a, b, c);
+            let cell_origin = [(triangle % cells_per_row) as f32 * cell_size, (triangle / cells_per_row) as f32 * cell_size];
+            let inner_size = cell_size * (1.0 - 2.0 * self.padding);
+            let extent = flat.iter().flatten().fold(0.0f32, |m, x| m.max(*x));
+            let fit = if extent > 0.0 { inner_size / extent } else { 0.0 };
+
+            for (corner, index) in indices.iter().enumerate() {
+                uvs.index_buffer.push(uvs.vertex_remap.len() as u64);
+                uvs.vertex_remap.push(*index);
+                uvs.tex_coords.push([
+                    cell_origin[0] + cell_size * self.padding + flat[corner][0] * fit,
+                    cell_origin[1] + cell_size * self.padding + flat[corner][1] * fit,
+                ]);
+            }
+        }
+
+        Ok(uvs)
+    }
+}
+
+// Lays the triangle flat in its own plane with a at the origin and ab along the u axis, shifted so
+// every coordinate is non-negative
+fn flatten_triangle(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [[f32; 2]; 3] {
+    let u_axis = normalize(sub(b, a));
+    let normal = cross(sub(b, a), sub(c, a));
+    let v_axis = normalize(cross(normal, u_axis));
+    let ac = sub(c, a);
+    let flat = [[0.0, 0.0], [length(sub(b, a)), 0.0], [dot(ac, u_axis), dot(ac, v_axis)]];
+    let min_u = flat.iter().map(|p| p[0]).fold(0.0f32, f32::min);
+
+    flat.map(|p| [p[0] - min_u, p[1]])
+}
+
+impl Object3d {
+    // Applies a generated lightmap UV set, duplicating vertices along the generator's seams
+    pub fn apply_lightmap_uvs(&mut self, uvs: LightmapUvs) -> Result<(), WfoError> {
+        let error = |message: &str| Err(WfoError::InvalidArgument { message: String::from(message) });
+        if uvs.index_buffer.len() != self.index_buffer.len() {
+            return error("Lightmap UV generator changed the triangle count");
+        }
+        if uvs.vertex_remap.len() != uvs.tex_coords.len() {
+            return error("Lightmap UV generator returned mismatched vertex and tex coord counts");
+        }
+        if uvs.vertex_remap.iter().any(|i| *i as usize >= self.vertex_buffer.len()) {
+            return error("Lightmap UV generator referenced a vertex that doesn't exist");
+        }
+        if uvs.index_buffer.iter().any(|i| *i as usize >= uvs.vertex_remap.len()) {
+            return error("Lightmap UV generator indexed past its own vertex list");
+        }
+
+        self.vertex_buffer = uvs.vertex_remap.iter().map(|i| self.vertex_buffer[*i as usize].clone()).collect();
+        if !self.vertex_sources.is_empty() {
+            self.vertex_sources = uvs.vertex_remap.iter().map(|i| self.vertex_sources[*i as usize]).collect();
+        }
+        if !self.joint_indices.is_empty() {
+            self.joint_indices = uvs.vertex_remap.iter().map(|i| self.joint_indices[*i as usize]).collect();
+        }
+        if !self.joint_weights.is_empty() {
+            self.joint_weights = uvs.vertex_remap.iter().map(|i| self.joint_weights[*i as usize]).collect();
+        }
+        self.index_buffer = uvs.index_buffer;
+        self.lightmap_tex_coords = uvs.tex_coords;
+
+        Ok(())
+    }
+
+    pub fn generate_lightmap_uvs<G: LightmapUvGenerator>(&mut self, generator: &G) -> Result<(), WfoError> {
+        let uvs = generator.generate(self)?;
+
+        self.apply_lightmap_uvs(uvs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::test_fixtures::quad;
+    use crate::vertex::VertexData;
+    use super::*;
+
+    #[test]
+    fn triangle_grid_generator_gives_each_triangle_its_own_cell() {
+        let mut obj = quad();
+
+        obj.generate_lightmap_uvs(&TriangleGridGenerator { padding: 0.0 }).expect("Lightmap UVs generate");
+
+        assert_eq!(6, obj.vertex_buffer.len(), "Every triangle gets its own vertices");
+        assert_eq!(vec!(0u64, 1, 2, 3, 4, 5), obj.index_buffer, "Index buffer references the split vertices");
+        assert_eq!(
+            VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(1.0)),
+            obj.vertex_buffer[3],
+            "Split vertices copy the original vertex data"
+        );
+        assert_eq!(6, obj.lightmap_tex_coords.len(), "Each vertex has a lightmap tex coord");
+        assert!(
+            obj.lightmap_tex_coords[..3].iter().all(|uv| uv[0] <= 0.5 && uv[1] <= 0.5),
+            "First triangle is packed into the first cell"
+        );
+        assert!(
+            obj.lightmap_tex_coords[3..].iter().all(|uv| uv[0] >= 0.5 && uv[1] <= 0.5),
+            "Second triangle is packed into the second cell"
+        );
+    }
+
+    #[test]
+    fn apply_lightmap_uvs_keeps_skin_weights_on_their_vertices() {
+        let mut obj = quad();
+        obj.joint_indices = vec!([0, 0, 0, 0], [1, 0, 0, 0], [2, 0, 0, 0], [3, 0, 0, 0]);
+        obj.joint_weights = vec![[1.0, 0.0, 0.0, 0.0]; 4];
+
+        obj.generate_lightmap_uvs(&TriangleGridGenerator::default()).expect("Lightmap UVs generate");
+
+        assert_eq!(6, obj.joint_weights.len());
+        assert_eq!(
+            vec!(0u16, 1, 2, 2, 3, 0),
+            obj.joint_indices.iter().map(|j| j[0]).collect::<Vec<_>>(),
+            "Split vertices copy the original vertex's joints"
+        );
+    }
+
+    #[test]
+    fn apply_lightmap_uvs_rejects_indices_past_the_remap() {
+        let mut obj = quad();
+
+        let result = obj.apply_lightmap_uvs(LightmapUvs {
+            vertex_remap: vec!(0, 1, 2),
+            index_buffer: vec!(0, 1, 2, 2, 3, 0),
+            tex_coords: vec!([0.0, 0.0], [1.0, 0.0], [0.0, 1.0]),
+        });
+
+        assert_eq!(
+            Some(String::from("Lightmap UV generator indexed past its own vertex list")),
+            result.err().map(|e| e.message())
+        );
+        assert_eq!(4, obj.vertex_buffer.len(), "A rejected UV set leaves the object untouched");
+    }
+
+    #[test]
+    fn apply_lightmap_uvs_rejects_changed_triangle_count() {
+        let mut obj = quad();
+
+        let result = obj.apply_lightmap_uvs(LightmapUvs {
+            vertex_remap: vec!(0, 1, 2),
+            index_buffer: vec!(0, 1, 2),
+            tex_coords: vec!([0.0, 0.0], [1.0, 0.0], [0.0, 1.0]),
+        });
+
+        assert!(result.is_err(), "Lightmap UVs must keep every triangle");
+    }
+}
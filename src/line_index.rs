@@ -0,0 +1,107 @@
+use std::ops::Range;
+use crate::diagnostic::Diagnostic;
+use crate::error::WfoError;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::prepass::count_statements;
+use crate::statement::Statement;
+
+// Byte offset of the start of every line, so a block of lines can be cut out of the text without
+// scanning it again
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) struct LineIndex {
+    line_starts: Vec<usize>,
+    text_len: usize,
+}
+
+impl LineIndex {
+    pub(crate) fn from_text(text: &str) -> Self {
+        let mut line_starts = vec!(0);
+        line_starts.extend(text.bytes().enumerate().filter(|(_, b)| *b == b'\n').map(|(i, _)| i + 1));
+        if text.ends_with('\n') {
+            line_starts.pop();
+        }
+
+        LineIndex { line_starts, text_len: text.len() }
+    }
+
+    pub(crate) fn line_count(&self) -> u64 {
+        self.line_starts.len() as u64
+    }
+
+    // Bytes of the 1 based lines in range, end exclusive
+    pub(crate) fn byte_range(&self, lines: Range<u64>) -> Option<Range<usize>> {
+        if lines.start < 1 || lines.start > lines.end || lines.end > self.line_count() + 1 {
+            return None;
+        }
+
+        let offset = |line: u64| self.line_starts.get(line as usize - 1).copied().unwrap_or(self.text_len);
+        Some(offset(lines.start)..offset(lines.end))
+    }
+}
+
+// Lexes and parses only the given lines (1 based, end exclusive), e.g. to re-validate the block an
+// editor just changed. Statements and diagnostics carry their line numbers in the whole file, and
+// relative face indices resolve against the v/vt/vn declared before the block
+pub fn parse_range(input: &str, lines: Range<u64>) -> Result<(Vec<Statement>, Vec<Diagnostic>), WfoError> {
+    let index = LineIndex::from_text(input);
+    let bytes = index.byte_range(lines.clone()).ok_or_else(|| WfoError::InvalidArgument {
+        message: format!("Lines {}..{} are outside the {} lines of the input", lines.start, lines.end, index.line_count()),
+    })?;
+
+    let before = count_statements(&input.as_bytes()[..bytes.start]);
+    let mut tokens = Lexer::new().lex_input(&mut input[bytes].as_bytes())?;
+    for token in tokens.iter_mut() {
+        token.line_number += lines.start - 1;
+    }
+    let mut parser = Parser::new();
+    parser.seed_declared_counts([before.vertices as u64, before.tex_coords as u64, before.normals as u64]);
+
+    Ok(parser.parse_tokens_with_recovery(&tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::statement::{StatementDataType, StatementType};
+    use super::*;
+
+    const TEXT: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 1.0\nf 1 2 3\n";
+
+    #[test]
+    fn line_index_maps_lines_to_bytes() {
+        let index = LineIndex::from_text(TEXT);
+
+        assert_eq!(6, index.line_count());
+        assert_eq!(Some(8..16), index.byte_range(2..3));
+        assert_eq!(Some(41..TEXT.len()), index.byte_range(6..7), "The last line runs to the end");
+        assert_eq!(None, index.byte_range(0..2), "Lines are 1 based");
+        assert_eq!(None, index.byte_range(6..8));
+    }
+
+    #[test]
+    fn parse_range_keeps_file_line_numbers_and_earlier_declarations() {
+        let (statements, diagnostics) = parse_range(TEXT, 4..5).expect("Lines are in range");
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(1, statements.len());
+        assert_eq!(StatementType::FACE, statements[0].statement_type);
+        assert_eq!(4, statements[0].line_number, "Line numbers count from the start of the file");
+        assert_eq!(StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), statements[0].data, "Relative indices see the earlier v");
+    }
+
+    #[test]
+    fn parse_range_reports_errors_in_the_block_only() {
+        let (statements, diagnostics) = parse_range(TEXT, 5..7).expect("Lines are in range");
+
+        assert_eq!(1, statements.len(), "The face after the bad vertex still parses");
+        assert_eq!(vec!(5), diagnostics.iter().map(|d| d.line_number).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn parse_range_rejects_lines_outside_the_input() {
+        assert_eq!(
+            Some(String::from("Lines 3..10 are outside the 6 lines of the input")),
+            parse_range(TEXT, 3..10).err().map(|e| e.message())
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::object3d::Object3d;
+
+// How the strips of several `l` elements are kept apart in the output
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum StripSeparation {
+    // one buffer per strip
+    #[default]
+    Split,
+    // one buffer with the sentinel index between strips, e.g. 0xFFFFFFFF for 32 bit indices with
+    // GL_PRIMITIVE_RESTART_FIXED_INDEX or Vulkan's primitiveRestartEnable
+    PrimitiveRestart(u64),
+}
+
+// Positions and indices to draw as a line strip
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct LineStripBuffer {
+    pub positions: Vec<[f32; 3]>,
+    pub index_buffer: Vec<u64>,
+}
+
+impl Object3d {
+    pub fn line_strips(&self, separation: StripSeparation) -> Vec<LineStripBuffer> {
+        let mut buffers: Vec<LineStripBuffer> = Vec::new();
+
+        for polyline in &self.polylines {
+            let buffer = match (separation, buffers.last_mut()) {
+                (StripSeparation::PrimitiveRestart(sentinel), Some(x)) => {
+                    x.index_buffer.push(sentinel);
+                    x
+                },
+                _ => {
+                    buffers.push(LineStripBuffer::default());
+                    buffers.last_mut().expect("A buffer was just pushed")
+                },
+            };
+            for point in &polyline.points {
+                buffer.index_buffer.push(buffer.positions.len() as u64);
+                buffer.positions.push([*point.0 as f32, *point.1 as f32, *point.2 as f32]);
+            }
+        }
+
+        buffers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::object3d::Polyline;
+    use super::*;
+
+    fn two_lines() -> Object3d {
+        Object3d {
+            polylines: vec!(
+                Polyline { points: vec!((f!(0.0), f!(0.0), f!(0.0)), (f!(1.0), f!(0.0), f!(0.0)), (f!(1.0), f!(1.0), f!(0.0))), line_number: 1 },
+                Polyline { points: vec!((f!(5.0), f!(0.0), f!(0.0)), (f!(6.0), f!(0.0), f!(0.0))), line_number: 2 },
+            ),
+            ..Object3d::from(String::from("Lines"))
+        }
+    }
+
+    #[test]
+    fn line_strips_split_into_one_buffer_per_line() {
+        let strips = two_lines().line_strips(StripSeparation::Split);
+
+        assert_eq!(2, strips.len());
+        assert_eq!(vec!(0u64, 1, 2), strips[0].index_buffer);
+        assert_eq!(vec!(0u64, 1), strips[1].index_buffer, "Each buffer indexes its own positions");
+        assert_eq!(vec!([5.0, 0.0, 0.0], [6.0, 0.0, 0.0]), strips[1].positions);
+    }
+
+    #[test]
+    fn line_strips_join_with_primitive_restart_sentinel() {
+        let strips = two_lines().line_strips(StripSeparation::PrimitiveRestart(u32::MAX as u64));
+
+        assert_eq!(1, strips.len());
+        assert_eq!(vec!(0u64, 1, 2, u32::MAX as u64, 3, 4), strips[0].index_buffer);
+        assert_eq!(5, strips[0].positions.len());
+    }
+
+    #[test]
+    fn line_strips_are_empty_without_lines() {
+        assert!(Object3d::from(String::from("Empty")).line_strips(StripSeparation::PrimitiveRestart(0)).is_empty());
+    }
+}
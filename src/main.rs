@@ -1,12 +1,3 @@
-mod lexer;
-mod token;
-mod parser;
-mod statement;
-mod compiler;
-mod object3d;
-mod vertex;
-mod nan_safe_float;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,82 @@
+use crate::object3d::{Object3d, SubMesh};
+
+impl Object3d {
+    // Reorders the triangles so each material's triangles are contiguous, leaving one sub mesh
+    // (one draw call) per material. Materials keep the order they are first used in and triangles
+    // keep their order within a material. Returns how many sub meshes were merged away
+    pub fn sort_by_material(&mut self) -> usize {
+        let before = self.sub_meshes.len();
+        let materials = SubMesh::triangle_materials(&self.sub_meshes);
+        let mut order: Vec<&Option<String>> = Vec::new();
+        for material in &materials {
+            if !order.contains(material) {
+                order.push(material);
+            }
+        }
+        if order.len() == before {
+            return 0;
+        }
+
+        let mut triangles: Vec<usize> = (0..materials.len()).collect();
+        triangles.sort_by_key(|t| order.iter().position(|m| *m == materials[*t]));
+
+        let mut index_buffer = Vec::with_capacity(self.index_buffer.len());
+        let mut triangle_sources = Vec::new();
+        let mut smoothing_groups = Vec::new();
+        let mut sub_meshes = Vec::new();
+        for triangle in triangles {
+            index_buffer.extend_from_slice(&self.index_buffer[triangle * 3..triangle * 3 + 3]);
+            if let Some(source) = self.triangle_sources.get(triangle) {
+                triangle_sources.push(source.clone());
+            }
+            if let Some(group) = self.smoothing_groups.get(triangle) {
+                smoothing_groups.push(*group);
+            }
+            SubMesh::extend_ranges(&mut sub_meshes, materials[triangle], 3);
+        }
+
+        self.index_buffer = index_buffer;
+        self.triangle_sources = triangle_sources;
+        self.smoothing_groups = smoothing_groups;
+        self.sub_meshes = sub_meshes;
+
+        before - self.sub_meshes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object3d::FaceSource;
+    use crate::test_fixtures::positions;
+    use super::*;
+
+    fn sub_mesh(material: &str, index_start: usize, index_count: usize) -> SubMesh {
+        SubMesh { material: Some(String::from(material)), index_start, index_count }
+    }
+
+    fn interleaved() -> Object3d {
+        let points: Vec<[f64; 3]> = (0..6).map(|i| [i as f64, 0.0, 0.0]).collect();
+
+        Object3d {
+            triangle_sources: (1..=4).map(FaceSource::from).collect(),
+            smoothing_groups: vec!(1, 2, 3, 4),
+            sub_meshes: vec!(sub_mesh("Red", 0, 3), sub_mesh("Blue", 3, 3), sub_mesh("Red", 6, 6)),
+            ..positions("Interleaved", &points, &[0, 1, 2, 1, 2, 3, 2, 3, 4, 3, 4, 5])
+        }
+    }
+
+    #[test]
+    fn sort_by_material_makes_each_material_contiguous() {
+        let mut obj = interleaved();
+
+        assert_eq!(1, obj.sort_by_material());
+        assert_eq!(vec!(sub_mesh("Red", 0, 9), sub_mesh("Blue", 9, 3)), obj.sub_meshes);
+        assert_eq!(vec!(0u64, 1, 2, 2, 3, 4, 3, 4, 5, 1, 2, 3), obj.index_buffer);
+        assert_eq!(vec!(1u32, 3, 4, 2), obj.smoothing_groups, "Per triangle data follows its triangle");
+        assert_eq!(
+            vec!(1u64, 3, 4, 2),
+            obj.triangle_sources.iter().map(|s| s.line_number).collect::<Vec<_>>()
+        );
+        assert_eq!(0, obj.sort_by_material(), "Sorting again changes nothing");
+    }
+}
@@ -0,0 +1,179 @@
+use crate::geometry::{add, dot, length, normalize, scale, sub, triangle_cross};
+use crate::object3d::Object3d;
+
+// Meshlet triangles use u8 local indices, so max_vertices must not exceed 256. A meshlet needs
+// room for at least one triangle
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct MeshletLimits {
+    pub max_vertices: usize,
+    pub max_triangles: usize,
+}
+
+impl Default for MeshletLimits {
+    fn default() -> Self {
+        MeshletLimits {
+            max_vertices: 64,
+            max_triangles: 124,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Meshlet {
+    // indices into the object's vertex buffer
+    pub vertices: Vec<u32>,
+    // indices into this meshlet's vertices
+    pub triangles: Vec<[u8; 3]>,
+    pub center: [f32; 3],
+    pub radius: f32,
+    pub cone_axis: [f32; 3],
+    // cosine of the widest angle between the cone axis and any triangle normal in the meshlet
+    pub cone_cutoff: f32,
+}
+
+impl Meshlet {
+    // True when every triangle faces away from a viewer looking along view_direction, so the
+    // whole meshlet can be skipped
+    pub fn is_backfacing(&self, view_direction: [f32; 3]) -> bool {
+        if self.cone_cutoff <= 0.0 {
+            return false;
+        }
+
+        let spread_sin = (1.0 - self.cone_cutoff * self.cone_cutoff).sqrt();
+        dot(normalize(view_direction), self.cone_axis) > spread_sin
+    }
+}
+
+// Greedily groups consecutive triangles into meshlets for mesh shader pipelines. Triangle order is
+// preserved, so running a locality optimization beforehand produces tighter meshlets
+pub fn build_meshlets(obj: &Object3d, limits: &MeshletLimits) -> Result<Vec<Meshlet>, String> {
+    if limits.max_vertices < 3 || limits.max_vertices > 256 || limits.max_triangles == 0 {
+        return Err(format!(
+            "Meshlet limits of {} vertices and {} triangles can't hold a triangle, vertices must be between 3 and 256",
+            limits.max_vertices,
+            limits.max_triangles
+        ));
+    }
+
+    let positions: Vec<[f32; 3]> = obj.vertices().map(|v| v.pos()).collect();
+    let mut meshlets = Vec::new();
+    let mut vertices: Vec<u32> = Vec::new();
+    let mut triangles: Vec<[u8; 3]> = Vec::new();
+
+    for indices in obj.index_buffer.chunks(3) {
+        let new_vertex_count = indices.iter()
+            .enumerate()
+            .filter(|(i, x)| !vertices.contains(&(**x as u32)) && !indices[..*i].contains(x))
+            .count();
+
+        if vertices.len() + new_vertex_count > limits.max_vertices || triangles.len() >= limits.max_triangles {
+            meshlets.push(finish_meshlet(&positions, std::mem::take(&mut vertices), std::mem::take(&mut triangles)));
+        }
+
+        let mut triangle = [0u8; 3];
+        for (corner, index) in indices.iter().enumerate() {
+            let local_index = match vertices.iter().position(|v| *v == *index as u32) {
+                Some(x) => x,
+                None => {
+                    vertices.push(*index as u32);
+                    vertices.len() - 1
+                }
+            };
+            triangle[corner] = local_index as u8;
+        }
+        triangles.push(triangle);
+    }
+
+    if !triangles.is_empty() {
+        meshlets.push(finish_meshlet(&positions, vertices, triangles));
+    }
+
+    Ok(meshlets)
+}
+
+fn finish_meshlet(positions: &[[f32; 3]], vertices: Vec<u32>, triangles: Vec<[u8; 3]>) -> Meshlet {
+    let meshlet_positions: Vec<[f32; 3]> = vertices.iter().map(|v| positions[*v as usize]).collect();
+
+    let center = scale(
+        meshlet_positions.iter().fold([0.0, 0.0, 0.0], |sum, p| add(sum, *p)),
+        1.0 / meshlet_positions.len() as f32
+    );
+    let radius = meshlet_positions.iter().map(|p| length(sub(*p, center))).fold(0.0, f32::max);
+
+    let normals: Vec<[f32; 3]> = triangles.iter()
+        .map(|t| normalize(triangle_cross(
+            meshlet_positions[t[0] as usize],
+            meshlet_positions[t[1] as usize],
+            meshlet_positions[t[2] as usize]
+        )))
+        .collect();
+    let cone_axis = normalize(normals.iter().fold([0.0, 0.0, 0.0], |sum, n| add(sum, *n)));
+    let cone_cutoff = normals.iter().map(|n| dot(*n, cone_axis)).fold(1.0, f32::min);
+
+    Meshlet {
+        vertices,
+        triangles,
+        center,
+        radius,
+        cone_axis,
+        cone_cutoff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::quad;
+    use super::*;
+
+    #[test]
+    fn build_meshlets_groups_small_object_into_one_meshlet() {
+        let meshlets = build_meshlets(&quad(), &MeshletLimits::default()).expect("Default limits are valid");
+
+        assert_eq!(1, meshlets.len(), "A small object fits in one meshlet");
+        assert_eq!(vec!(0u32, 1, 2, 3), meshlets[0].vertices, "Meshlet references each vertex once");
+        assert_eq!(vec!([0u8, 1, 2], [2, 3, 0]), meshlets[0].triangles, "Meshlet triangles use local indices");
+        assert_eq!([0.0, 0.0, 0.0], meshlets[0].center, "Meshlet bounds are centered on its vertices");
+        assert_eq!(2.0f32.sqrt(), meshlets[0].radius, "Meshlet bounds contain every vertex");
+    }
+
+    #[test]
+    fn build_meshlets_respects_limits() {
+        let limits = MeshletLimits { max_vertices: 3, max_triangles: 124 };
+
+        let meshlets = build_meshlets(&quad(), &limits).expect("Limits are valid");
+
+        assert_eq!(2, meshlets.len(), "A new meshlet starts when the vertex limit is reached");
+        assert_eq!(vec!(2u32, 3, 0), meshlets[1].vertices);
+        assert_eq!(vec!([0u8, 1, 2]), meshlets[1].triangles);
+    }
+
+    #[test]
+    fn build_meshlets_computes_normal_cone() {
+        let meshlets = build_meshlets(&quad(), &MeshletLimits::default()).expect("Default limits are valid");
+
+        assert_eq!([0.0, 1.0, 0.0], meshlets[0].cone_axis, "Cone axis follows the triangle normals");
+        assert_eq!(1.0, meshlets[0].cone_cutoff, "Coplanar triangles produce the tightest cone");
+        assert!(meshlets[0].is_backfacing([0.0, 1.0, 0.0]), "Meshlet is culled when viewed from behind");
+        assert!(!meshlets[0].is_backfacing([0.0, -1.0, 0.0]), "Meshlet is kept when viewed from the front");
+    }
+
+    #[test]
+    fn build_meshlets_rejects_limits_that_cant_hold_a_triangle() {
+        for (max_vertices, max_triangles) in [(2, 124), (64, 0)] {
+            let limits = MeshletLimits { max_vertices, max_triangles };
+
+            assert!(build_meshlets(&quad(), &limits).is_err(), "{:?} can't hold a triangle", limits);
+        }
+    }
+
+    #[test]
+    fn build_meshlets_rejects_more_vertices_than_local_indices_can_address() {
+        let limits = MeshletLimits { max_vertices: 257, max_triangles: 124 };
+
+        assert_eq!(
+            Err(String::from("Meshlet limits of 257 vertices and 124 triangles can't hold a triangle, vertices must be between 3 and 256")),
+            build_meshlets(&quad(), &limits)
+        );
+        assert!(build_meshlets(&quad(), &MeshletLimits { max_vertices: 256, max_triangles: 124 }).is_ok());
+    }
+}
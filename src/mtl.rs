@@ -0,0 +1,756 @@
+use std::io::Read;
+use crate::error::WfoError;
+use crate::lexer::sniff_text;
+use crate::nan_safe_float::Float;
+
+// A material from a `.mtl` library. Properties the library leaves out stay None so callers can
+// tell "not set" apart from an explicit black or zero
+#[derive(PartialEq, Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    // Ka, Kd, Ks and Ke
+    pub ambient: Option<Color>,
+    pub diffuse: Option<Color>,
+    pub specular: Option<Color>,
+    pub emissive: Option<Color>,
+    // Ns
+    pub specular_exponent: Option<Float>,
+    // Ni
+    pub optical_density: Option<Float>,
+    // d, or 1 - Tr. Whichever comes last wins
+    pub dissolve: Option<Float>,
+    // Tf
+    pub transmission_filter: Option<Color>,
+    // illum
+    pub illumination_model: Option<u8>,
+    // map_Kd, map_Ks, map_Ke, map_Ns, map_d, map_bump or bump, disp, decal and refl
+    pub diffuse_map: Option<TextureMap>,
+    pub specular_map: Option<TextureMap>,
+    pub emissive_map: Option<TextureMap>,
+    pub specular_exponent_map: Option<TextureMap>,
+    pub dissolve_map: Option<TextureMap>,
+    pub bump_map: Option<TextureMap>,
+    pub displacement_map: Option<TextureMap>,
+    pub decal_map: Option<TextureMap>,
+    pub reflection_map: Option<ReflectionMap>,
+    // the PBR extension: Pr, Pm, Ps, Pc, Pcr, aniso and anisor
+    pub roughness: Option<Float>,
+    pub metallic: Option<Float>,
+    pub sheen: Option<Float>,
+    pub clearcoat_thickness: Option<Float>,
+    pub clearcoat_roughness: Option<Float>,
+    pub anisotropy: Option<Float>,
+    pub anisotropy_rotation: Option<Float>,
+    // norm, map_Pr and map_Pm
+    pub normal_map: Option<TextureMap>,
+    pub roughness_map: Option<TextureMap>,
+    pub metallic_map: Option<TextureMap>,
+    // what the library's rgb colors are in, from MtlOptions. XYZ colors don't depend on it
+    pub color_space: ColorSpace,
+}
+
+// A color statement in any of the spec's three forms
+#[derive(PartialEq, Debug, Clone)]
+pub enum Color {
+    // `Kd r g b`, or `Kd r` for a grey
+    Rgb(Float, Float, Float),
+    // `Kd xyz x y z` in CIE XYZ, or `Kd xyz x` for a grey
+    Xyz(Float, Float, Float),
+    // `Kd spectral file.rfl factor`, a reflectance curve scaled by factor (1 when left out)
+    Spectral {
+        file: String,
+        factor: Float,
+    },
+}
+
+impl Color {
+    // Linear sRGB (D65). Spectral colors need the curve file, so they have no RGB value here
+    pub fn to_rgb(&self) -> Option<(Float, Float, Float)> {
+        match self {
+            Color::Rgb(r, g, b) => Some((*r, *g, *b)),
+            Color::Xyz(x, y, z) => {
+                let (x, y, z) = (x.into_inner(), y.into_inner(), z.into_inner());
+                let channel = |cx: f64, cy: f64, cz: f64| Float::new(cx * x + cy * y + cz * z).ok();
+                Some((
+                    channel(3.2404542, -1.5371385, -0.4985314)?,
+                    channel(-0.9692660, 1.8760108, 0.0415560)?,
+                    channel(0.0556434, -0.2040259, 1.0572252)?,
+                ))
+            },
+            Color::Spectral { .. } => None,
+        }
+    }
+}
+
+// A texture statement with its option flags applied over the spec's defaults
+#[derive(PartialEq, Debug, Clone)]
+pub struct TextureMap {
+    pub path: String,
+    // -o and -s, the texture coordinate offset and scale
+    pub origin: (Float, Float, Float),
+    pub scale: (Float, Float, Float),
+    // -mm base gain
+    pub base: Float,
+    pub gain: Float,
+    // -bm, only meaningful on bump maps
+    pub bump_multiplier: Option<Float>,
+    // -boost, sharpening for mip-mapped textures
+    pub boost: Option<Float>,
+    // -imfchan, the channel a scalar map (bump, map_d, ...) reads
+    pub channel: Option<ImageChannel>,
+    // -blendu and -clamp
+    pub blend_u: bool,
+    pub clamp: bool,
+}
+
+// refl statements. A cube map is built up from one statement per face
+#[derive(PartialEq, Debug, Clone)]
+pub enum ReflectionMap {
+    // -type sphere, also assumed when -type is left out
+    Sphere(TextureMap),
+    // boxed, six faces make it far larger than a sphere map
+    Cube(Box<CubeMap>),
+}
+
+// -type cube_top, cube_bottom, cube_front, cube_back, cube_left and cube_right
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct CubeMap {
+    pub top: Option<TextureMap>,
+    pub bottom: Option<TextureMap>,
+    pub front: Option<TextureMap>,
+    pub back: Option<TextureMap>,
+    pub left: Option<TextureMap>,
+    pub right: Option<TextureMap>,
+}
+
+// -imfchan r, g, b, m (matte), l (luminance) or z (depth)
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ImageChannel {
+    Red,
+    Green,
+    Blue,
+    Matte,
+    Luminance,
+    Depth,
+}
+
+impl TextureMap {
+    pub(crate) fn from(path: String) -> Self {
+        TextureMap {
+            path,
+            origin: (Float::new(0.0).unwrap(), Float::new(0.0).unwrap(), Float::new(0.0).unwrap()),
+            scale: (Float::new(1.0).unwrap(), Float::new(1.0).unwrap(), Float::new(1.0).unwrap()),
+            base: Float::new(0.0).unwrap(),
+            gain: Float::new(1.0).unwrap(),
+            bump_multiplier: None,
+            boost: None,
+            channel: None,
+            blend_u: true,
+            clamp: false,
+        }
+    }
+}
+
+impl Material {
+    pub(crate) fn from(name: String) -> Self {
+        Material {
+            name,
+            ambient: None,
+            diffuse: None,
+            specular: None,
+            emissive: None,
+            specular_exponent: None,
+            optical_density: None,
+            dissolve: None,
+            transmission_filter: None,
+            illumination_model: None,
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            specular_exponent_map: None,
+            dissolve_map: None,
+            bump_map: None,
+            displacement_map: None,
+            decal_map: None,
+            reflection_map: None,
+            roughness: None,
+            metallic: None,
+            sheen: None,
+            clearcoat_thickness: None,
+            clearcoat_roughness: None,
+            anisotropy: None,
+            anisotropy_rotation: None,
+            normal_map: None,
+            roughness_map: None,
+            metallic_map: None,
+            color_space: ColorSpace::default(),
+        }
+    }
+
+    // Surfaces a renderer shouldn't backface cull: see-through ones (d below 1, a map_d mask, or a
+    // glass illumination model) show their back faces
+    pub fn is_double_sided(&self) -> bool {
+        self.dissolve.is_some_and(|d| d < Float::new(1.0).unwrap())
+            || self.dissolve_map.is_some()
+            || self.illumination_model.is_some_and(|x| [4, 6, 7, 9].contains(&x))
+    }
+
+    // d below 1 blends the whole surface, a map_d alone is treated as a cutout mask
+    pub fn alpha_mode(&self) -> AlphaMode {
+        if self.dissolve.is_some_and(|d| d < Float::new(1.0).unwrap()) {
+            AlphaMode::Blended
+        } else if self.dissolve_map.is_some() {
+            AlphaMode::AlphaTested
+        } else {
+            AlphaMode::Opaque
+        }
+    }
+
+    // Every texture the material uses with the keyword that set it. Cube reflection faces are all
+    // refl
+    pub fn texture_maps(&self) -> Vec<(&'static str, &TextureMap)> {
+        let maps = [
+            ("map_Kd", &self.diffuse_map),
+            ("map_Ks", &self.specular_map),
+            ("map_Ke", &self.emissive_map),
+            ("map_Ns", &self.specular_exponent_map),
+            ("map_d", &self.dissolve_map),
+            ("bump", &self.bump_map),
+            ("disp", &self.displacement_map),
+            ("decal", &self.decal_map),
+            ("norm", &self.normal_map),
+            ("map_Pr", &self.roughness_map),
+            ("map_Pm", &self.metallic_map),
+        ];
+        let reflections = match &self.reflection_map {
+            Some(ReflectionMap::Sphere(x)) => vec!(x),
+            Some(ReflectionMap::Cube(x)) => [&x.top, &x.bottom, &x.front, &x.back, &x.left, &x.right].into_iter().flatten().collect(),
+            None => Vec::new(),
+        };
+
+        maps.into_iter()
+            .filter_map(|(keyword, map)| map.as_ref().map(|x| (keyword, x)))
+            .chain(reflections.into_iter().map(|x| ("refl", x)))
+            .collect()
+    }
+
+    // Kd as linear RGB, decoded from the library's color space. None when Kd is unset or spectral
+    pub fn kd_linear(&self) -> Option<(Float, Float, Float)> {
+        let (r, g, b) = self.diffuse.as_ref()?.to_rgb()?;
+        match (&self.diffuse, self.color_space) {
+            (Some(Color::Rgb(..)), ColorSpace::Srgb) => Some((srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))),
+            _ => Some((r, g, b)),
+        }
+    }
+
+    // Kd encoded for display, e.g. for an sRGB framebuffer or a UI color swatch
+    pub fn kd_srgb(&self) -> Option<(Float, Float, Float)> {
+        let (r, g, b) = self.kd_linear()?;
+
+        Some((linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)))
+    }
+}
+
+// How a renderer has to draw a material's transparency, in the order the passes usually run
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum AlphaMode {
+    Opaque,
+    AlphaTested,
+    Blended,
+}
+
+// Tools disagree on whether MTL colors are display (sRGB) values or linear ones. Most write the
+// values of a color picker, so sRGB is the default
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ColorSpace {
+    Linear,
+    #[default]
+    Srgb,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct MtlOptions {
+    pub color_space: ColorSpace,
+}
+
+fn srgb_to_linear(x: Float) -> Float {
+    let x = x.into_inner();
+    let linear = if x <= 0.04045 { x / 12.92 } else { ((x + 0.055) / 1.055).powf(2.4) };
+
+    Float::new(linear).unwrap()
+}
+
+fn linear_to_srgb(x: Float) -> Float {
+    let x = x.into_inner();
+    let encoded = if x <= 0.0031308 { x * 12.92 } else { 1.055 * x.powf(1.0 / 2.4) - 0.055 };
+
+    Float::new(encoded).unwrap()
+}
+
+// One non blank line of a material library, split into its keyword and arguments
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct MtlStatement {
+    pub(crate) keyword: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) line_number: u64,
+    // where the keyword starts, 1 based like Token positions
+    pub(crate) line_position: u64,
+}
+
+pub fn parse_mtl<R: Read>(reader: &mut R) -> Result<Vec<Material>, WfoError> {
+    parse_mtl_with_options(reader, &MtlOptions::default())
+}
+
+pub fn parse_mtl_with_options<R: Read>(reader: &mut R, options: &MtlOptions) -> Result<Vec<Material>, WfoError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|source| WfoError::IoError { path: None, source })?;
+
+    parse_mtl_str_with_options(&String::from_utf8_lossy(&bytes), options)
+}
+
+// Errors are WfoError::MtlError located at the statement that failed, or a LexError for input
+// that isn't text at all
+pub fn parse_mtl_str(text: &str) -> Result<Vec<Material>, WfoError> {
+    parse_mtl_str_with_options(text, &MtlOptions::default())
+}
+
+pub fn parse_mtl_str_with_options(text: &str, options: &MtlOptions) -> Result<Vec<Material>, WfoError> {
+    sniff_text(text.as_bytes())?;
+    let mut materials = parse_mtl_statements(&lex_mtl(text))?;
+    for material in materials.iter_mut() {
+        material.color_space = options.color_space;
+    }
+
+    Ok(materials)
+}
+
+// Comments run from '#' to the end of the line, like in OBJ files
+pub(crate) fn lex_mtl(text: &str) -> Vec<MtlStatement> {
+    let mut statements = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let line_position = (line.len() - line.trim_start().len()) as u64 + 1;
+        let mut words = line.split_whitespace().map(String::from);
+        if let Some(keyword) = words.next() {
+            statements.push(MtlStatement { keyword, args: words.collect(), line_number: line_index as u64 + 1, line_position });
+        }
+    }
+
+    statements
+}
+
+// Keywords this parser doesn't model (e.g. vendor extensions) are skipped
+pub(crate) fn parse_mtl_statements(statements: &[MtlStatement]) -> Result<Vec<Material>, WfoError> {
+    let mut materials: Vec<Material> = Vec::new();
+
+    for statement in statements {
+        parse_mtl_statement(statement, &mut materials).map_err(|message| WfoError::MtlError {
+            message,
+            line_number: statement.line_number,
+            line_position: statement.line_position,
+            path: None,
+        })?;
+    }
+
+    Ok(materials)
+}
+
+fn parse_mtl_statement(statement: &MtlStatement, materials: &mut Vec<Material>) -> Result<(), String> {
+    if statement.keyword == "newmtl" {
+        if statement.args.is_empty() {
+            return Err(String::from("Expected a material name after newmtl"));
+        }
+        materials.push(Material::from(statement.args.join(" ")));
+        return Ok(());
+    }
+
+    let known = [
+        "Ka", "Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr", "Tf", "illum",
+        "map_Kd", "map_Ks", "map_Ke", "map_Ns", "map_d", "map_bump", "bump", "disp", "decal", "refl",
+        "Pr", "Pm", "Ps", "Pc", "Pcr", "aniso", "anisor", "norm", "map_Pr", "map_Pm",
+    ];
+    if !known.contains(&statement.keyword.as_str()) {
+        return Ok(());
+    }
+    let material = materials.last_mut().ok_or_else(|| format!(
+        "Material property {} appears before any newmtl", statement.keyword
+    ))?;
+    match statement.keyword.as_str() {
+        "Ka" => material.ambient = Some(parse_color(statement)?),
+        "Kd" => material.diffuse = Some(parse_color(statement)?),
+        "Ks" => material.specular = Some(parse_color(statement)?),
+        "Ke" => material.emissive = Some(parse_color(statement)?),
+        "Tf" => material.transmission_filter = Some(parse_color(statement)?),
+        "Ns" => material.specular_exponent = Some(parse_scalar(statement)?),
+        "Ni" => material.optical_density = Some(parse_scalar(statement)?),
+        "d" => material.dissolve = Some(parse_scalar(statement)?),
+        "Tr" => material.dissolve = Some(Float::new(1.0).unwrap() - parse_scalar(statement)?),
+        "map_Kd" => material.diffuse_map = Some(parse_texture_map(statement)?),
+        "map_Ks" => material.specular_map = Some(parse_texture_map(statement)?),
+        "map_Ke" => material.emissive_map = Some(parse_texture_map(statement)?),
+        "map_Ns" => material.specular_exponent_map = Some(parse_texture_map(statement)?),
+        "map_d" => material.dissolve_map = Some(parse_texture_map(statement)?),
+        "map_bump" | "bump" => material.bump_map = Some(parse_texture_map(statement)?),
+        "disp" => material.displacement_map = Some(parse_texture_map(statement)?),
+        "decal" => material.decal_map = Some(parse_texture_map(statement)?),
+        "refl" => material.reflection_map = Some(parse_reflection_map(statement, material.reflection_map.take())?),
+        "Pr" => material.roughness = Some(parse_scalar(statement)?),
+        "Pm" => material.metallic = Some(parse_scalar(statement)?),
+        "Ps" => material.sheen = Some(parse_scalar(statement)?),
+        "Pc" => material.clearcoat_thickness = Some(parse_scalar(statement)?),
+        "Pcr" => material.clearcoat_roughness = Some(parse_scalar(statement)?),
+        "aniso" => material.anisotropy = Some(parse_scalar(statement)?),
+        "anisor" => material.anisotropy_rotation = Some(parse_scalar(statement)?),
+        "norm" => material.normal_map = Some(parse_texture_map(statement)?),
+        "map_Pr" => material.roughness_map = Some(parse_texture_map(statement)?),
+        "map_Pm" => material.metallic_map = Some(parse_texture_map(statement)?),
+        _ => {
+            let model = statement.args.first().and_then(|a| a.parse::<u8>().ok()).filter(|_| statement.args.len() == 1);
+            material.illumination_model = Some(model.ok_or_else(|| String::from(
+                "Expected an illumination model number after illum"
+            ))?);
+        },
+    }
+
+    Ok(())
+}
+
+fn parse_number(statement: &MtlStatement, arg: &str) -> Result<Float, String> {
+    arg.parse::<f64>().ok()
+        .and_then(|x| Float::new(x).ok())
+        .ok_or_else(|| format!("Expected a number after {} but found {}", statement.keyword, arg))
+}
+
+fn parse_scalar(statement: &MtlStatement) -> Result<Float, String> {
+    // `d -halo 0.5` scales dissolve by the viewing angle, the factor is all that's kept
+    let args: Vec<&String> = statement.args.iter().filter(|a| a.as_str() != "-halo").collect();
+    if args.len() != 1 {
+        return Err(format!("Expected 1 number after {}", statement.keyword));
+    }
+
+    parse_number(statement, args[0])
+}
+
+// `Kd r g b`, `Kd xyz x y z` or `Kd spectral file.rfl factor`. The last two numbers of the rgb and
+// xyz forms can be left out for a grey
+fn parse_color(statement: &MtlStatement) -> Result<Color, String> {
+    if statement.args.first().is_some_and(|a| a == "spectral") {
+        return match &statement.args[1..] {
+            [file] => Ok(Color::Spectral { file: file.clone(), factor: Float::new(1.0).unwrap() }),
+            [file, factor] => Ok(Color::Spectral { file: file.clone(), factor: parse_number(statement, factor)? }),
+            _ => Err(format!("Expected a file name and an optional factor after {} spectral", statement.keyword)),
+        };
+    }
+
+    let is_xyz = statement.args.first().is_some_and(|a| a == "xyz");
+    let channels = statement.args[is_xyz as usize..].iter()
+        .map(|a| parse_number(statement, a))
+        .collect::<Result<Vec<Float>, String>>()?;
+    let (a, b, c) = match channels[..] {
+        [a] => (a, a, a),
+        [a, b, c] => (a, b, c),
+        _ if is_xyz => return Err(format!("Expected 1 or 3 numbers after {} xyz", statement.keyword)),
+        _ => return Err(format!("Expected 1 or 3 numbers after {}", statement.keyword)),
+    };
+
+    Ok(if is_xyz { Color::Xyz(a, b, c) } else { Color::Rgb(a, b, c) })
+}
+
+// Option flags come before the file name, which is everything after them so names with spaces
+// survive. Flags outside the typed set are skipped along with their values
+fn parse_texture_map(statement: &MtlStatement) -> Result<TextureMap, String> {
+    let mut map = TextureMap::from(String::new());
+    let mut args = statement.args.iter().peekable();
+    let is_number = |a: &&String| a.parse::<f64>().is_ok();
+
+    while let Some(flag) = args.next_if(|a| a.starts_with('-')) {
+        let mut numbers = |min: usize, max: usize| -> Result<Vec<Float>, String> {
+            let mut values = Vec::new();
+            while values.len() < max {
+                match args.next_if(is_number) {
+                    Some(x) => values.push(parse_number(statement, x)?),
+                    None => break,
+                }
+            }
+            if values.len() < min {
+                return Err(format!("Expected a number after {}", flag));
+            }
+            Ok(values)
+        };
+        match flag.as_str() {
+            "-o" | "-s" | "-t" => {
+                let values = numbers(1, 3)?;
+                let default = if flag == "-s" { 1.0 } else { 0.0 };
+                let component = |i: usize| values.get(i).copied().unwrap_or(Float::new(default).unwrap());
+                let value = (component(0), component(1), component(2));
+                match flag.as_str() {
+                    "-o" => map.origin = value,
+                    "-s" => map.scale = value,
+                    _ => {},
+                }
+            },
+            "-mm" => {
+                let values = numbers(2, 2)?;
+                (map.base, map.gain) = (values[0], values[1]);
+            },
+            "-bm" => map.bump_multiplier = Some(numbers(1, 1)?[0]),
+            "-boost" => map.boost = Some(numbers(1, 1)?[0]),
+            "-texres" => {numbers(1, 1)?;},
+            "-blendu" | "-blendv" | "-clamp" | "-cc" => {
+                let on = match args.next().map(|a| a.as_str()) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => return Err(format!("Expected on or off after {}", flag)),
+                };
+                match flag.as_str() {
+                    "-blendu" => map.blend_u = on,
+                    "-clamp" => map.clamp = on,
+                    _ => {},
+                }
+            },
+            "-imfchan" => {
+                map.channel = Some(match args.next().map(|a| a.as_str()) {
+                    Some("r") => ImageChannel::Red,
+                    Some("g") => ImageChannel::Green,
+                    Some("b") => ImageChannel::Blue,
+                    Some("m") => ImageChannel::Matte,
+                    Some("l") => ImageChannel::Luminance,
+                    Some("z") => ImageChannel::Depth,
+                    _ => return Err(String::from("Expected r, g, b, m, l or z after -imfchan")),
+                });
+            },
+            // only refl has a type, see parse_reflection_map
+            "-type" => {args.next();},
+            _ => return Err(format!("Unknown texture option {}", flag)),
+        }
+    }
+
+    map.path = args.map(|a| a.as_str()).collect::<Vec<&str>>().join(" ");
+    if map.path.is_empty() {
+        return Err(format!("Expected a file name after {}", statement.keyword));
+    }
+
+    Ok(map)
+}
+
+// A cube face is added to the faces of earlier refl statements, anything else replaces them
+fn parse_reflection_map(statement: &MtlStatement, previous: Option<ReflectionMap>) -> Result<ReflectionMap, String> {
+    let map_type = statement.args.windows(2).find(|w| w[0] == "-type").map(|w| w[1].as_str());
+    let map = parse_texture_map(statement)?;
+    let mut cube = match previous {
+        Some(ReflectionMap::Cube(x)) => x,
+        _ => Box::default(),
+    };
+    let face = match map_type {
+        None | Some("sphere") => return Ok(ReflectionMap::Sphere(map)),
+        Some("cube_top") => &mut cube.top,
+        Some("cube_bottom") => &mut cube.bottom,
+        Some("cube_front") => &mut cube.front,
+        Some("cube_back") => &mut cube.back,
+        Some("cube_left") => &mut cube.left,
+        Some("cube_right") => &mut cube.right,
+        Some(x) => return Err(format!("Unknown reflection map type {}", x)),
+    };
+    *face = Some(map);
+
+    Ok(ReflectionMap::Cube(cube))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use super::*;
+
+    #[test]
+    fn parse_mtl_str_reads_every_supported_property() {
+        let materials = parse_mtl_str(
+            "# exported\nnewmtl Red Paint\nKa 0.1 0.1 0.1\nKd 0.8 0 0 # base\nKs 1\nKe 0 0 0\nNs 96\nNi 1.45\nd 0.9\nTf 1 1 1\nillum 2\n"
+        ).expect("Valid library parses");
+
+        assert_eq!(
+            vec!(Material {
+                name: String::from("Red Paint"),
+                ambient: Some(Color::Rgb(f!(0.1), f!(0.1), f!(0.1))),
+                diffuse: Some(Color::Rgb(f!(0.8), f!(0.0), f!(0.0))),
+                specular: Some(Color::Rgb(f!(1.0), f!(1.0), f!(1.0))),
+                emissive: Some(Color::Rgb(f!(0.0), f!(0.0), f!(0.0))),
+                specular_exponent: Some(f!(96.0)),
+                optical_density: Some(f!(1.45)),
+                dissolve: Some(f!(0.9)),
+                transmission_filter: Some(Color::Rgb(f!(1.0), f!(1.0), f!(1.0))),
+                illumination_model: Some(2),
+                ..Material::from(String::new())
+            }),
+            materials
+        );
+    }
+
+    #[test]
+    fn parse_mtl_str_reads_several_materials_and_transparency() {
+        let materials = parse_mtl_str("newmtl a\nTr 0.25\nmap_Kd a.png\n\nnewmtl b\nd -halo 0.5\n").expect("Valid library parses");
+
+        assert_eq!(vec!("a", "b"), materials.iter().map(|m| m.name.as_str()).collect::<Vec<&str>>());
+        assert_eq!(Some(f!(0.75)), materials[0].dissolve, "Tr is the inverse of d");
+        assert_eq!(Some(f!(0.5)), materials[1].dissolve);
+        assert_eq!(None, materials[1].diffuse, "Unset properties stay None");
+    }
+
+    #[test]
+    fn parse_mtl_str_reads_emissive_color_and_map() {
+        let materials = parse_mtl_str("newmtl screen\nKe 0.2 0.8 1\nmap_Ke -clamp on glow.png\n").expect("Valid library parses");
+
+        assert_eq!(Some(Color::Rgb(f!(0.2), f!(0.8), f!(1.0))), materials[0].emissive);
+        assert_eq!(Some(TextureMap { clamp: true, ..TextureMap::from(String::from("glow.png")) }), materials[0].emissive_map);
+    }
+
+    #[test]
+    fn material_converts_kd_between_color_spaces() {
+        let srgb = parse_mtl_str("newmtl a\nKd 0.5 1 0\n").expect("Valid library parses");
+        let linear = parse_mtl_str_with_options("newmtl a\nKd 0.5 1 0\n", &MtlOptions { color_space: ColorSpace::Linear })
+            .expect("Valid library parses");
+        let round = |c: Option<(Float, Float, Float)>| c.map(|(r, g, b)| [r, g, b].map(|x| (x.into_inner() * 1000.0).round() / 1000.0));
+
+        assert_eq!(ColorSpace::Srgb, srgb[0].color_space, "Colors are sRGB unless the options say otherwise");
+        assert_eq!(Some([0.214, 1.0, 0.0]), round(srgb[0].kd_linear()), "sRGB values are decoded");
+        assert_eq!(Some([0.5, 1.0, 0.0]), round(srgb[0].kd_srgb()), "Encoding undoes the decoding");
+        assert_eq!(Some([0.5, 1.0, 0.0]), round(linear[0].kd_linear()), "Linear values are kept");
+        assert_eq!(Some([0.735, 1.0, 0.0]), round(linear[0].kd_srgb()));
+        assert_eq!(None, Material::from(String::from("b")).kd_linear(), "An unset Kd has no value");
+    }
+
+    #[test]
+    fn parse_mtl_str_rejects_malformed_statements() {
+        let cases = [
+            ("Kd 1 1 1\n", "1:1: Material property Kd appears before any newmtl"),
+            ("newmtl\n", "1:1: Expected a material name after newmtl"),
+            ("newmtl a\n  Kd 1 1\n", "2:3: Expected 1 or 3 numbers after Kd"),
+            ("newmtl a\nNs high\n", "2:1: Expected a number after Ns but found high"),
+            ("newmtl a\nillum 2.5\n", "2:1: Expected an illumination model number after illum"),
+            ("newmtl a\nKd xyz 1 1\n", "2:1: Expected 1 or 3 numbers after Kd xyz"),
+            ("newmtl a\nKa spectral\n", "2:1: Expected a file name and an optional factor after Ka spectral"),
+        ];
+        for (text, message) in cases {
+            assert_eq!(Some(String::from(message)), parse_mtl_str(text).err().map(|e| e.to_string()), "{:?} is rejected", text);
+        }
+    }
+
+    #[test]
+    fn parse_mtl_str_reads_texture_maps_with_options() {
+        let materials = parse_mtl_str(
+            "newmtl a\nmap_Kd -o 0.5 0.25 -s 2 -blendu off -clamp on -boost 1.5 textures/brick wall.png\nbump -bm 0.3 -mm 0.1 2 -imfchan l n.png\nrefl -type sphere env.png\n"
+        ).expect("Valid library parses");
+        let material = &materials[0];
+
+        assert_eq!(
+            Some(TextureMap {
+                origin: (f!(0.5), f!(0.25), f!(0.0)),
+                scale: (f!(2.0), f!(1.0), f!(1.0)),
+                blend_u: false,
+                clamp: true,
+                boost: Some(f!(1.5)),
+                ..TextureMap::from(String::from("textures/brick wall.png"))
+            }),
+            material.diffuse_map
+        );
+        assert_eq!(
+            Some(TextureMap {
+                bump_multiplier: Some(f!(0.3)),
+                base: f!(0.1),
+                gain: f!(2.0),
+                channel: Some(ImageChannel::Luminance),
+                ..TextureMap::from(String::from("n.png"))
+            }),
+            material.bump_map,
+            "bump is an alias of map_bump"
+        );
+        assert_eq!(Some(ReflectionMap::Sphere(TextureMap::from(String::from("env.png")))), material.reflection_map);
+        assert_eq!(None, material.specular_map);
+    }
+
+    #[test]
+    fn parse_mtl_str_rejects_malformed_texture_maps() {
+        let cases = [
+            ("newmtl a\nmap_Kd -clamp maybe a.png\n", "2:1: Expected on or off after -clamp"),
+            ("newmtl a\nmap_Kd -mm 1 a.png\n", "2:1: Expected a number after -mm"),
+            ("newmtl a\nmap_Kd -blendu on\n", "2:1: Expected a file name after map_Kd"),
+            ("newmtl a\nmap_Kd -zoom 2 a.png\n", "2:1: Unknown texture option -zoom"),
+            ("newmtl a\nmap_d -imfchan q a.png\n", "2:1: Expected r, g, b, m, l or z after -imfchan"),
+            ("newmtl a\nrefl -type cylinder a.png\n", "2:1: Unknown reflection map type cylinder"),
+        ];
+        for (text, message) in cases {
+            assert_eq!(Some(String::from(message)), parse_mtl_str(text).err().map(|e| e.to_string()), "{:?} is rejected", text);
+        }
+    }
+
+    #[test]
+    fn parse_mtl_str_builds_cube_reflection_maps_from_every_face() {
+        let materials = parse_mtl_str(
+            "newmtl a\nrefl -type cube_top top.png\nrefl -type cube_bottom -clamp on bottom.png\nrefl -type cube_left left.png\n\
+             newmtl b\nrefl -type cube_top top.png\nrefl -type sphere env.png\n"
+        ).expect("Valid library parses");
+
+        assert_eq!(
+            Some(ReflectionMap::Cube(Box::new(CubeMap {
+                top: Some(TextureMap::from(String::from("top.png"))),
+                bottom: Some(TextureMap { clamp: true, ..TextureMap::from(String::from("bottom.png")) }),
+                left: Some(TextureMap::from(String::from("left.png"))),
+                ..Default::default()
+            }))),
+            materials[0].reflection_map,
+            "Each face keeps its own texture"
+        );
+        assert_eq!(
+            Some(ReflectionMap::Sphere(TextureMap::from(String::from("env.png")))),
+            materials[1].reflection_map,
+            "A sphere map replaces the cube"
+        );
+    }
+
+    #[test]
+    fn parse_mtl_str_reads_pbr_extension() {
+        let materials = parse_mtl_str(
+            "newmtl metal\nPr 0.3\nPm 1\nPs 0.1\nPc 0.5\nPcr 0.2\naniso 0.4\nanisor 0.25\nnorm -bm 2 n.png\nmap_Pr r.png\nmap_Pm m.png\n"
+        ).expect("Valid library parses");
+
+        assert_eq!(
+            Material {
+                roughness: Some(f!(0.3)),
+                metallic: Some(f!(1.0)),
+                sheen: Some(f!(0.1)),
+                clearcoat_thickness: Some(f!(0.5)),
+                clearcoat_roughness: Some(f!(0.2)),
+                anisotropy: Some(f!(0.4)),
+                anisotropy_rotation: Some(f!(0.25)),
+                normal_map: Some(TextureMap { bump_multiplier: Some(f!(2.0)), ..TextureMap::from(String::from("n.png")) }),
+                roughness_map: Some(TextureMap::from(String::from("r.png"))),
+                metallic_map: Some(TextureMap::from(String::from("m.png"))),
+                ..Material::from(String::from("metal"))
+            },
+            materials[0]
+        );
+    }
+
+    #[test]
+    fn parse_mtl_reads_from_a_reader() {
+        let materials = parse_mtl(&mut "newmtl a\nKd 1 0 0\n".as_bytes()).expect("Valid library parses");
+
+        assert_eq!(Some(Color::Rgb(f!(1.0), f!(0.0), f!(0.0))), materials[0].diffuse);
+    }
+
+    #[test]
+    fn parse_mtl_str_reads_xyz_and_spectral_colors() {
+        let materials = parse_mtl_str(
+            "newmtl a\nKd xyz 0.9505 1 1.089\nKa xyz 0.5\nKs spectral steel.rfl 0.5\nTf spectral glass.rfl\n"
+        ).expect("Valid library parses");
+        let material = &materials[0];
+
+        assert_eq!(Some(Color::Xyz(f!(0.9505), f!(1.0), f!(1.089))), material.diffuse);
+        assert_eq!(Some(Color::Xyz(f!(0.5), f!(0.5), f!(0.5))), material.ambient, "A single xyz value is a grey");
+        assert_eq!(Some(Color::Spectral { file: String::from("steel.rfl"), factor: f!(0.5) }), material.specular);
+        assert_eq!(Some(Color::Spectral { file: String::from("glass.rfl"), factor: f!(1.0) }), material.transmission_filter);
+        let white = material.diffuse.as_ref().and_then(|c| c.to_rgb()).expect("XYZ converts to RGB");
+        assert!([white.0, white.1, white.2].iter().all(|x| (x.into_inner() - 1.0).abs() < 0.01), "D65 white is RGB white");
+        assert_eq!(None, material.specular.as_ref().and_then(|c| c.to_rgb()), "Spectral colors need their curve file");
+    }
+}
@@ -1,10 +1,132 @@
-use crate::vertex::{VertexData, VertexFormat};
+use std::collections::{HashMap, HashSet};
+use crate::nan_safe_float::Float;
+use crate::vertex::{AttributeDesc, VertexAttribute, VertexData, VertexFormat, VertexSource, VertexView};
 
-pub(crate) struct Object3d {
+// The face statement a triangle was compiled from, with its original (pos, tex_coord, normal)
+// index triples
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct FaceSource {
+    pub line_number: u64,
+    pub indices: Vec<(u64, u64, u64)>,
+}
+
+impl FaceSource {
+    pub(crate) fn from(line_number: u64) -> Self {
+        Self {
+            line_number,
+            indices: Vec::new(),
+        }
+    }
+    
+    pub fn arity(&self) -> usize {
+        self.indices.len()
+    }
+}
+
+// Collision hulls authored alongside render meshes, recognized by the common UCX_/UBX_/USP_/UCP_
+// object name prefixes
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum CollisionShape {
+    Convex,
+    Box,
+    Sphere,
+    Capsule,
+}
+
+impl CollisionShape {
+    pub(crate) fn from_object_name(name: &str) -> Option<Self> {
+        match name.get(..4) {
+            Some("UCX_") => Some(CollisionShape::Convex),
+            Some("UBX_") => Some(CollisionShape::Box),
+            Some("USP_") => Some(CollisionShape::Sphere),
+            Some("UCP_") => Some(CollisionShape::Capsule),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct TexCoordBounds {
+    pub min: (Float, Float),
+    pub max: (Float, Float),
+}
+
+impl TexCoordBounds {
+    pub(crate) fn from(tex_coord: (Float, Float)) -> Self {
+        Self {
+            min: tex_coord,
+            max: tex_coord,
+        }
+    }
+    
+    pub(crate) fn extend(&mut self, tex_coord: (Float, Float)) {
+        self.min = (self.min.0.min(tex_coord.0), self.min.1.min(tex_coord.1));
+        self.max = (self.max.0.max(tex_coord.0), self.max.1.max(tex_coord.1));
+    }
+    
+    // True when the texture coordinates rely on wrapping (or an atlas remap) to be sampled
+    pub fn exceeds_unit_square(&self) -> bool {
+        *self.min.0 < 0.0 || *self.min.1 < 0.0 || *self.max.0 > 1.0 || *self.max.1 > 1.0
+    }
+}
+
+// An `l` element. Line vertices have no normals so they can't share the triangle vertex buffer,
+// the resolved positions are kept instead
+#[derive(PartialEq, Debug, Clone)]
+pub struct Polyline {
+    pub points: Vec<(Float, Float, Float)>,
+    pub line_number: u64,
+}
+
+// A run of the index buffer drawn with one material, so an object that switches materials mid mesh
+// can still share a single vertex and index buffer across several draw calls
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SubMesh {
+    pub material: Option<String>,
+    pub index_start: usize,
+    pub index_count: usize,
+}
+
+impl SubMesh {
+    // Appends index_count indices drawn with material, growing the last range when it already
+    // uses that material
+    pub(crate) fn extend_ranges(sub_meshes: &mut Vec<SubMesh>, material: &Option<String>, index_count: usize) {
+        match sub_meshes.last_mut() {
+            Some(last) if last.material == *material => last.index_count += index_count,
+            last => {
+                let index_start = last.map(|x| x.index_start + x.index_count).unwrap_or(0);
+                sub_meshes.push(SubMesh { material: material.clone(), index_start, index_count });
+            },
+        }
+    }
+    
+    // The material of every triangle, in index buffer order
+    pub(crate) fn triangle_materials(sub_meshes: &[SubMesh]) -> Vec<&Option<String>> {
+        sub_meshes.iter()
+            .flat_map(|x| std::iter::repeat_n(&x.material, x.index_count / 3))
+            .collect()
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Object3d {
     pub name: String,
     pub format: VertexFormat,
     pub vertex_buffer: Vec<VertexData>,
     pub index_buffer: Vec<u64>,
+    pub vertex_sources: Vec<VertexSource>,
+    pub triangle_sources: Vec<FaceSource>,
+    pub tex_coord_bounds: Option<TexCoordBounds>,
+    pub smoothing_groups: Vec<u32>,
+    pub faces: Vec<FaceSource>,
+    pub lightmap_tex_coords: Vec<[f32; 2]>,
+    pub joint_indices: Vec<[u16; 4]>,
+    pub joint_weights: Vec<[f32; 4]>,
+    pub polylines: Vec<Polyline>,
+    // name from the `usemtl` active when the object's first face was compiled, see sub_meshes for
+    // objects that switch materials
+    pub material: Option<String>,
+    pub sub_meshes: Vec<SubMesh>,
 }
 
 impl Object3d {
@@ -14,14 +136,73 @@ impl Object3d {
             format: VertexFormat::Unknown,
             vertex_buffer: Vec::new(),
             index_buffer: Vec::new(),
+            vertex_sources: Vec::new(),
+            triangle_sources: Vec::new(),
+            tex_coord_bounds: None,
+            smoothing_groups: Vec::new(),
+            faces: Vec::new(),
+            lightmap_tex_coords: Vec::new(),
+            joint_indices: Vec::new(),
+            joint_weights: Vec::new(),
+            polylines: Vec::new(),
+            material: None,
+            sub_meshes: Vec::new(),
+        }
+    }
+    
+    pub fn vertices(&self) -> impl Iterator<Item = VertexView<'_>> {
+        self.vertex_buffer.iter().map(VertexView::from)
+    }
+    
+    // The format's interleaved f32 attributes, followed by the second UV set when every vertex
+    // has one
+    pub fn attributes(&self) -> Vec<AttributeDesc> {
+        let mut attributes = self.format.attributes().to_vec();
+        if !self.vertex_buffer.is_empty() && self.vertex_buffer.iter().all(|v| v.tex_coord2.is_some()) {
+            attributes.push(AttributeDesc::from(VertexAttribute::TexCoord2, 2, self.format.stride()));
+        }
+        
+        attributes
+    }
+    
+    // Line list indices (two per edge) for drawing the triangles as a wireframe. Each edge shared
+    // by neighbouring triangles is listed once, in the order it is first used. Edges only match by
+    // vertex index, so both sides of a uv or normal seam are listed
+    pub fn to_wireframe(&self) -> Vec<u64> {
+        let mut seen: HashSet<(u64, u64)> = HashSet::new();
+        let mut lines = Vec::new();
+        
+        for triangle in self.index_buffer.chunks_exact(3) {
+            for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+                if a != b && seen.insert((a.min(b), a.max(b))) {
+                    lines.push(a);
+                    lines.push(b);
+                }
+            }
         }
+        
+        lines
+    }
+    
+    pub fn collision_shape(&self) -> Option<CollisionShape> {
+        CollisionShape::from_object_name(&self.name)
     }
     
-    pub(crate) fn add_vertex(&mut self, new_vertex: VertexData) -> Result<(), String> {
+    // Separates collision hulls from render meshes, preserving the order of both
+    pub(crate) fn partition_collision_objects(objects: Vec<Object3d>) -> (Vec<Object3d>, Vec<Object3d>) {
+        objects.into_iter().partition(|o| o.collision_shape().is_none())
+    }
+    
+    // The face statement a triangle of the index buffer was compiled from
+    pub fn triangle_source(&self, triangle_index: usize) -> Option<&FaceSource> {
+        self.triangle_sources.get(triangle_index)
+    }
+    
+    pub(crate) fn add_vertex(&mut self, new_vertex: VertexData, source: VertexSource) -> Result<(), String> {
         if self.format == VertexFormat::Unknown {
             self.format = new_vertex.format;
         } else if self.format != new_vertex.format {
-            return Err(String::from("Compilation error: Unexpected vertex format change"));
+            return Err(format!("Compilation error: Unexpected vertex format change from {:?} to {:?}", self.format, new_vertex.format));
         }
         
         // TODO: performance bottleneck ... replace O(x) linear search with something better
@@ -39,25 +220,128 @@ impl Object3d {
             self.index_buffer.push(i as u64);
         } else {
             self.index_buffer.push(self.vertex_buffer.len() as u64);
+            self.extend_tex_coord_bounds(&new_vertex);
             self.vertex_buffer.push(new_vertex);
+            self.vertex_sources.push(source);
         }
         
         Ok(())
     }
+    
+    pub fn split_for_u16_indices(&self) -> Vec<Object3d> {
+        self.split_chunks(u16::MAX as usize)
+    }
+    
+    // Partitions the mesh into chunks that each reference at most vertex_limit unique vertices.
+    // Vertices shared across a chunk boundary are duplicated into every chunk that uses them.
+    // Per vertex and per triangle data is carried over, retained faces are not. Polylines don't
+    // use the vertex buffer and all go to the first chunk. A chunk needs room for at least one
+    // triangle, so limits below 3 are rejected
+    pub fn split_by_vertex_limit(&self, vertex_limit: usize) -> Result<Vec<Object3d>, String> {
+        if vertex_limit < 3 {
+            return Err(format!("A vertex limit of {} can't hold a triangle, it must be at least 3", vertex_limit));
+        }
+        
+        Ok(self.split_chunks(vertex_limit))
+    }
+    
+    fn split_chunks(&self, vertex_limit: usize) -> Vec<Object3d> {
+        let mut chunks = Vec::new();
+        let mut chunk = self.empty_chunk();
+        let mut remapped_indices: HashMap<u64, u64> = HashMap::new();
+        let materials = SubMesh::triangle_materials(&self.sub_meshes);
+        
+        for (triangle, indices) in self.index_buffer.chunks(3).enumerate() {
+            let mut new_indices: Vec<u64> = indices.iter()
+                .filter(|i| !remapped_indices.contains_key(i))
+                .copied()
+                .collect();
+            new_indices.sort();
+            new_indices.dedup();
+            
+            if remapped_indices.len() + new_indices.len() > vertex_limit && !chunk.index_buffer.is_empty() {
+                chunks.push(std::mem::replace(&mut chunk, self.empty_chunk()));
+                remapped_indices.clear();
+            }
+            
+            for index in indices {
+                let new_index = match remapped_indices.get(index) {
+                    Some(x) => *x,
+                    None => {
+                        let new_index = chunk.vertex_buffer.len() as u64;
+                        let vertex = self.vertex_buffer[*index as usize].clone();
+                        chunk.extend_tex_coord_bounds(&vertex);
+                        chunk.vertex_buffer.push(vertex);
+                        if let Some(source) = self.vertex_sources.get(*index as usize) {
+                            chunk.vertex_sources.push(*source);
+                        }
+                        if let Some(tex_coord) = self.lightmap_tex_coords.get(*index as usize) {
+                            chunk.lightmap_tex_coords.push(*tex_coord);
+                        }
+                        if let Some(joints) = self.joint_indices.get(*index as usize) {
+                            chunk.joint_indices.push(*joints);
+                        }
+                        if let Some(weights) = self.joint_weights.get(*index as usize) {
+                            chunk.joint_weights.push(*weights);
+                        }
+                        remapped_indices.insert(*index, new_index);
+                        
+                        new_index
+                    }
+                };
+                chunk.index_buffer.push(new_index);
+            }
+            
+            if let Some(source) = self.triangle_sources.get(triangle) {
+                chunk.triangle_sources.push(source.clone());
+            }
+            if let Some(group) = self.smoothing_groups.get(triangle) {
+                chunk.smoothing_groups.push(*group);
+            }
+            if let Some(material) = materials.get(triangle) {
+                SubMesh::extend_ranges(&mut chunk.sub_meshes, material, 3);
+            }
+        }
+        
+        if !chunk.index_buffer.is_empty() || chunks.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks[0].polylines = self.polylines.clone();
+        
+        chunks
+    }
+    
+    fn empty_chunk(&self) -> Object3d {
+        let mut chunk = Object3d::from(self.name.clone());
+        chunk.format = self.format;
+        chunk.material = self.material.clone();
+        
+        chunk
+    }
+    
+    fn extend_tex_coord_bounds(&mut self, vertex: &VertexData) {
+        if let Some(tex_coord) = vertex.tex_coord {
+            match self.tex_coord_bounds.as_mut() {
+                Some(bounds) => bounds.extend(tex_coord),
+                None => self.tex_coord_bounds = Some(TexCoordBounds::from(tex_coord)),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::f;
     use crate::nan_safe_float::Float;
+    use crate::test_fixtures;
+    use crate::vertex::{VertexDataIndex, VertexFormat};
     use super::*;
-    use crate::vertex::VertexFormat;
 
     #[test]
     fn add_vertex_sets_object_vertex_format_when_unknown() {
         let mut obj = Object3d::from(String::from("Test"));
         
-        obj.add_vertex(VertexData::vertex_p_from_floats(f!(0.0), f!(0.0), f!(0.0)))
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(0.0), f!(0.0), f!(0.0)), test_source())
             .expect("No error with valid data set");
         
         assert_eq!(
@@ -71,10 +355,10 @@ mod tests {
     fn add_vertex_returns_err_when_vertex_format_changes() {
         let mut obj = Object3d::from(String::from("Test"));
         
-        obj.add_vertex(VertexData::vertex_p_from_floats(f!(0.0), f!(0.0), f!(0.0)))
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(0.0), f!(0.0), f!(0.0)), test_source())
             .expect("No error with valid data set");
         
-        let result = obj.add_vertex(VertexData::vertex_pt_from_floats(f!(0.0), f!(0.0), f!(0.0), f!(0.0), f!(0.0)));
+        let result = obj.add_vertex(VertexData::vertex_pt_from_floats(f!(0.0), f!(0.0), f!(0.0), f!(0.0), f!(0.0)), test_source());
         assert!(
             result.is_err(),
             "add-vertex returns err when vertex format changes"
@@ -85,7 +369,7 @@ mod tests {
     fn add_vertex_adds_new_vertex_to_vertex_buffer_and_index_buffer() {
         let mut obj = Object3d::from(String::from("Test"));
         
-        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)))
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)), test_source())
             .expect("No error with valid data set");
         
         assert_eq!(
@@ -104,10 +388,10 @@ mod tests {
     fn add_vertex_references_duplicate_vertex_via_index_buffer() {
         let mut obj = Object3d::from(String::from("Test"));
         
-        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)))
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)), test_source())
             .expect("No error with valid data set");
         
-        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)))
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)), test_source())
             .expect("No error with valid data set");
         
         assert_eq!(
@@ -121,4 +405,178 @@ mod tests {
             "add vertex references duplicate vertex via index buffer"
         );
     }
+    
+    #[test]
+    fn add_vertex_records_source_of_new_vertices_only() {
+        let mut obj = Object3d::from(String::from("Test"));
+        let first_source = VertexSource::from(&VertexDataIndex::from_indices(&(1, 0, 0)), 4);
+        let second_source = VertexSource::from(&VertexDataIndex::from_indices(&(1, 0, 0)), 5);
+        
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)), first_source)
+            .expect("No error with valid data set");
+        
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(1.0), f!(1.0)), second_source)
+            .expect("No error with valid data set");
+        
+        assert_eq!(
+            vec!(first_source),
+            obj.vertex_sources,
+            "add vertex records the source of the first occurrence of each vertex"
+        );
+    }
+    
+    #[test]
+    fn collision_shape_is_detected_from_object_name_prefix() {
+        assert_eq!(Some(CollisionShape::Convex), Object3d::from(String::from("UCX_Crate_00")).collision_shape());
+        assert_eq!(Some(CollisionShape::Box), Object3d::from(String::from("UBX_Crate")).collision_shape());
+        assert_eq!(Some(CollisionShape::Sphere), Object3d::from(String::from("USP_Ball")).collision_shape());
+        assert_eq!(Some(CollisionShape::Capsule), Object3d::from(String::from("UCP_Pillar")).collision_shape());
+        assert_eq!(None, Object3d::from(String::from("Crate")).collision_shape());
+        assert_eq!(None, Object3d::from(String::from("UCX")).collision_shape());
+    }
+    
+    #[test]
+    fn partition_collision_objects_separates_collision_hulls() {
+        let objects = vec!(
+            Object3d::from(String::from("Crate")),
+            Object3d::from(String::from("UCX_Crate")),
+            Object3d::from(String::from("Barrel")),
+        );
+        
+        let (render_objects, collision_objects) = Object3d::partition_collision_objects(objects);
+        
+        assert_eq!(
+            vec!("Crate", "Barrel"),
+            render_objects.iter().map(|o| o.name.as_str()).collect::<Vec<&str>>(),
+            "partition collision objects keeps render meshes in order"
+        );
+        assert_eq!(
+            vec!("UCX_Crate"),
+            collision_objects.iter().map(|o| o.name.as_str()).collect::<Vec<&str>>(),
+            "partition collision objects returns collision hulls separately"
+        );
+    }
+    
+    #[test]
+    fn add_vertex_tracks_tex_coord_bounds() {
+        let mut obj = Object3d::from(String::from("Test"));
+        
+        obj.add_vertex(VertexData::vertex_pt_from_floats(f!(0.0), f!(0.0), f!(0.0), f!(0.5), f!(-0.25)), test_source())
+            .expect("No error with valid data set");
+        obj.add_vertex(VertexData::vertex_pt_from_floats(f!(1.0), f!(0.0), f!(0.0), f!(2.0), f!(0.75)), test_source())
+            .expect("No error with valid data set");
+        
+        assert_eq!(
+            Some(TexCoordBounds { min: (f!(0.5), f!(-0.25)), max: (f!(2.0), f!(0.75)) }),
+            obj.tex_coord_bounds,
+            "add vertex grows the tex coord bounds to contain every vertex"
+        );
+        assert!(
+            obj.tex_coord_bounds.unwrap().exceeds_unit_square(),
+            "tex coord bounds outside [0, 1] are reported"
+        );
+    }
+    
+    #[test]
+    fn tex_coord_bounds_are_none_without_tex_coords() {
+        let mut obj = Object3d::from(String::from("Test"));
+        
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(0.0), f!(0.0), f!(0.0)), test_source())
+            .expect("No error with valid data set");
+        
+        assert_eq!(None, obj.tex_coord_bounds, "tex coord bounds are none without tex coords");
+    }
+    
+    #[test]
+    fn to_wireframe_lists_shared_edges_once() {
+        let obj = quad();
+        
+        assert_eq!(vec!(0u64, 1, 1, 2, 2, 0, 2, 3, 3, 0), obj.to_wireframe(), "The diagonal is listed once");
+    }
+    
+    #[test]
+    fn split_by_vertex_limit_keeps_small_objects_whole() {
+        let obj = quad();
+        
+        let chunks = obj.split_by_vertex_limit(4).expect("A quad fits in 4 vertices");
+        
+        assert_eq!(1, chunks.len(), "split returns a single chunk when the object fits");
+        assert_eq!(obj.vertex_buffer, chunks[0].vertex_buffer);
+        assert_eq!(obj.index_buffer, chunks[0].index_buffer);
+    }
+    
+    #[test]
+    fn split_by_vertex_limit_duplicates_boundary_vertices() {
+        let obj = quad();
+        
+        let chunks = obj.split_by_vertex_limit(3).expect("A triangle fits in 3 vertices");
+        
+        assert_eq!(2, chunks.len(), "split starts a new chunk when the vertex limit is reached");
+        assert_eq!(
+            vec!(
+                VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(1.0)),
+                VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(-1.0)),
+                VertexData::vertex_p_from_floats(f!(-1.0), f!(0.0), f!(-1.0)),
+            ),
+            chunks[1].vertex_buffer,
+            "split duplicates vertices shared with the previous chunk"
+        );
+        assert_eq!(vec!(0u64, 1u64, 2u64), chunks[1].index_buffer, "split rebases chunk indices");
+        assert_eq!(vec!(5u32), chunks[1].smoothing_groups, "split carries per triangle data");
+        assert_eq!(VertexFormat::VertexP, chunks[1].format, "split preserves the vertex format");
+    }
+    
+    #[test]
+    fn split_by_vertex_limit_rebuilds_sub_meshes_per_chunk() {
+        let obj = Object3d {
+            sub_meshes: vec!(
+                SubMesh { material: Some(String::from("Stone")), index_start: 0, index_count: 3 },
+                SubMesh { material: None, index_start: 3, index_count: 3 },
+            ),
+            ..quad()
+        };
+        
+        let chunks = obj.split_by_vertex_limit(3).expect("A triangle fits in 3 vertices");
+        
+        assert_eq!(vec!(SubMesh { material: Some(String::from("Stone")), index_start: 0, index_count: 3 }), chunks[0].sub_meshes);
+        assert_eq!(vec!(SubMesh { material: None, index_start: 0, index_count: 3 }), chunks[1].sub_meshes, "Ranges restart in each chunk");
+    }
+    
+    #[test]
+    fn split_by_vertex_limit_rejects_limits_below_a_triangle() {
+        let obj = quad();
+        
+        assert!(obj.split_by_vertex_limit(2).is_err(), "split rejects limits that can't hold a triangle");
+        assert!(obj.split_by_vertex_limit(0).is_err(), "split rejects a zero limit");
+    }
+    
+    #[test]
+    fn sub_mesh_extend_ranges_merges_runs_of_one_material() {
+        let mut sub_meshes = Vec::new();
+        let stone = Some(String::from("Stone"));
+        
+        SubMesh::extend_ranges(&mut sub_meshes, &stone, 6);
+        SubMesh::extend_ranges(&mut sub_meshes, &stone, 3);
+        SubMesh::extend_ranges(&mut sub_meshes, &None, 3);
+        
+        assert_eq!(
+            vec!(
+                SubMesh { material: stone.clone(), index_start: 0, index_count: 9 },
+                SubMesh { material: None, index_start: 9, index_count: 3 },
+            ),
+            sub_meshes
+        );
+        assert_eq!(vec!(&stone, &stone, &stone, &None), SubMesh::triangle_materials(&sub_meshes));
+    }
+    
+    fn quad() -> Object3d {
+        Object3d {
+            smoothing_groups: vec!(1, 5),
+            ..test_fixtures::quad()
+        }
+    }
+    
+    fn test_source() -> VertexSource {
+        VertexSource::from(&VertexDataIndex::from_indices(&(1, 0, 0)), 1)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::compiler::{CompilerOptions, Fixup, IgnoredContent};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::LexerOptions;
+use crate::mtl::{Material, MtlOptions};
+use crate::parser::ParserOptions;
+
+// Options for every stage of the pipeline, see parse_obj_with_options
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ParseOptions {
+    pub lexer: LexerOptions,
+    pub parser: ParserOptions,
+    pub compiler: CompilerOptions,
+    // material libraries loaded by load_scene_with_options
+    pub mtl: MtlOptions,
+    // load_scene_with_options fails on a material library that can't be opened or parsed, rather
+    // than recording a warning and returning the geometry without those materials
+    pub strict_material_libraries: bool,
+    // materials load_scene_with_options uses in place of the libraries' definitions of the same
+    // name, or adds when no library defines them
+    pub material_overrides: HashMap<String, Material>,
+    // measure each stage into ParseReport::timings and Scene::timings
+    pub collect_timings: bool,
+}
+
+// Everything the pipeline noticed besides the objects, see parse_obj_with_report
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ParseReport {
+    // warnings of every stage in line order
+    pub warnings: Vec<Diagnostic>,
+    // statements the compiler skipped, in statement order
+    pub ignored: Vec<IgnoredContent>,
+    // faces rewritten under a lenient IndexRangePolicy, one per repair warning
+    pub fixups: Vec<Fixup>,
+    // set when ParseOptions::collect_timings asks for them
+    pub timings: Option<Timings>,
+}
+
+// Wall clock time of each pipeline stage, for import performance telemetry
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Timings {
+    pub lex: Duration,
+    pub parse: Duration,
+    pub compile: Duration,
+    // the share of compile each returned object took, by name in output order
+    pub per_object: Vec<(String, Duration)>,
+}
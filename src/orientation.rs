@@ -0,0 +1,162 @@
+use crate::geometry::{add, dot, length, scale, sub, triangle_cross};
+use crate::object3d::Object3d;
+
+impl Object3d {
+    // Turns inside-out parts of the mesh (common after decimation or boolean tools) the right way
+    // round. Each connected component is judged on its own: closed ones by the sign of their
+    // volume, open ones by whether their dominant face normal points away from the object's
+    // center. Flipping reverses the winding and negates the vertex normals. Returns how many
+    // components were flipped
+    pub fn ensure_outward_normals(&mut self) -> usize {
+        let positions: Vec<[f32; 3]> = self.vertices().map(|v| v.pos()).collect();
+        if positions.is_empty() {
+            return 0;
+        }
+        let center = scale(positions.iter().fold([0.0; 3], |sum, p| add(sum, *p)), 1.0 / positions.len() as f32);
+
+        let mut flipped = 0;
+        for component in self.triangle_components() {
+            let mut volume = 0.0;
+            let mut dominant_normal = [0.0; 3];
+            let mut component_center = [0.0; 3];
+            for triangle in &component {
+                let [a, b, c] = self.triangle_positions(&positions, *triangle);
+                let (a, b, c) = (sub(a, center), sub(b, center), sub(c, center));
+                let normal = triangle_cross(a, b, c);
+                volume += dot(a, normal) / 6.0;
+                dominant_normal = add(dominant_normal, normal);
+                component_center = add(component_center, scale(add(add(a, b), c), 1.0 / 3.0));
+            }
+
+            // a closed surface's volume dwarfs the rounding of an open one's
+            let extent = component.iter()
+                .flat_map(|t| self.triangle_positions(&positions, *t))
+                .map(|p| length(sub(p, center)))
+                .fold(0.0f32, f32::max);
+            let inward = if volume.abs() > 1e-4 * extent.powi(3) {
+                volume < 0.0
+            } else {
+                dot(dominant_normal, component_center) < 0.0
+            };
+            if inward {
+                self.flip_triangles(&component);
+                flipped += 1;
+            }
+        }
+
+        flipped
+    }
+
+    // Triangle indices grouped by shared vertices
+    fn triangle_components(&self) -> Vec<Vec<usize>> {
+        let mut parents: Vec<usize> = (0..self.vertex_buffer.len()).collect();
+        fn root(parents: &mut [usize], i: usize) -> usize {
+            let mut i = i;
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+        for triangle in self.index_buffer.chunks(3) {
+            for corner in &triangle[1..] {
+                let (a, b) = (root(&mut parents, triangle[0] as usize), root(&mut parents, *corner as usize));
+                parents[a] = b;
+            }
+        }
+
+        let mut components: Vec<Vec<usize>> = Vec::new();
+        let mut component_of_root = vec![usize::MAX; self.vertex_buffer.len()];
+        for (triangle, indices) in self.index_buffer.chunks(3).enumerate() {
+            let r = root(&mut parents, indices[0] as usize);
+            if component_of_root[r] == usize::MAX {
+                component_of_root[r] = components.len();
+                components.push(Vec::new());
+            }
+            components[component_of_root[r]].push(triangle);
+        }
+
+        components
+    }
+
+    fn triangle_positions(&self, positions: &[[f32; 3]], triangle: usize) -> [[f32; 3]; 3] {
+        let indices = &self.index_buffer[triangle * 3..triangle * 3 + 3];
+
+        [positions[indices[0] as usize], positions[indices[1] as usize], positions[indices[2] as usize]]
+    }
+
+    fn flip_triangles(&mut self, triangles: &[usize]) {
+        let mut vertices = Vec::new();
+        for triangle in triangles {
+            self.index_buffer.swap(triangle * 3 + 1, triangle * 3 + 2);
+            vertices.extend_from_slice(&self.index_buffer[triangle * 3..triangle * 3 + 3]);
+        }
+        vertices.sort();
+        vertices.dedup();
+
+        for vertex in vertices {
+            if let Some(normal) = self.vertex_buffer[vertex as usize].normal.as_mut() {
+                *normal = (-normal.0, -normal.1, -normal.2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::test_fixtures::{object, positions};
+    use crate::vertex::VertexData;
+    use super::*;
+
+    #[test]
+    fn ensure_outward_normals_flips_inside_out_closed_meshes() {
+        let mut obj = tetrahedron();
+        let outward = obj.index_buffer.clone();
+        obj.flip_triangles(&[0, 1, 2, 3]);
+
+        assert_eq!(1, obj.ensure_outward_normals(), "The inverted tetrahedron is flipped");
+        assert_eq!(outward, obj.index_buffer);
+        assert_eq!(0, obj.ensure_outward_normals(), "Outward meshes are left alone");
+    }
+
+    #[test]
+    fn ensure_outward_normals_judges_components_separately() {
+        let mut obj = tetrahedron();
+        let mut second = tetrahedron();
+        second.flip_triangles(&[0, 1, 2, 3]);
+        let offset = obj.vertex_buffer.len() as u64;
+        for vertex in second.vertex_buffer {
+            obj.vertex_buffer.push(VertexData { pos: (vertex.pos.0 + 5.0, vertex.pos.1, vertex.pos.2), ..vertex });
+        }
+        obj.index_buffer.extend(second.index_buffer.iter().map(|i| i + offset));
+
+        assert_eq!(1, obj.ensure_outward_normals(), "Only the inverted copy is flipped");
+        assert_eq!(&obj.index_buffer[..12], &obj.index_buffer[12..].iter().map(|i| i - offset).collect::<Vec<u64>>()[..]);
+    }
+
+    #[test]
+    fn ensure_outward_normals_negates_vertex_normals() {
+        // two open sheets, the top one facing down toward the center
+        let mut obj = object("Sheets", vec!(
+            VertexData::vertex_pn_from_floats(f!(0.0), f!(1.0), f!(0.0), f!(0.0), f!(-1.0), f!(0.0)),
+            VertexData::vertex_pn_from_floats(f!(1.0), f!(1.0), f!(0.0), f!(0.0), f!(-1.0), f!(0.0)),
+            VertexData::vertex_pn_from_floats(f!(0.0), f!(1.0), f!(1.0), f!(0.0), f!(-1.0), f!(0.0)),
+            VertexData::vertex_pn_from_floats(f!(0.0), f!(0.0), f!(0.0), f!(0.0), f!(1.0), f!(0.0)),
+            VertexData::vertex_pn_from_floats(f!(0.0), f!(0.0), f!(1.0), f!(0.0), f!(1.0), f!(0.0)),
+            VertexData::vertex_pn_from_floats(f!(1.0), f!(0.0), f!(0.0), f!(0.0), f!(1.0), f!(0.0)),
+        ), &[0, 1, 2, 3, 4, 5]);
+
+        assert_eq!(2, obj.ensure_outward_normals(), "Both sheets face the center");
+        assert_eq!(vec!(0u64, 2, 1, 3, 5, 4), obj.index_buffer);
+        assert_eq!([0.0, 1.0, 0.0], obj.vertices().next().unwrap().normal().unwrap());
+    }
+
+    fn tetrahedron() -> Object3d {
+        let corners = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+        // counter-clockwise seen from outside
+        positions("Tetrahedron", &corners, &[0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3])
+    }
+}
@@ -0,0 +1,119 @@
+use crate::geometry::{add, dot, length, normalize, scale, sub, triangle_cross};
+use crate::object3d::Object3d;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct GpuOptimizeOptions {
+    // sort triangle clusters so the outward facing ones far from the centroid are drawn first and
+    // occlude the rest, cutting overdraw from most view directions
+    pub overdraw: bool,
+    // triangles per cluster, smaller clusters reduce overdraw further but break up runs that share
+    // vertices
+    pub cluster_size: usize,
+}
+
+impl Default for GpuOptimizeOptions {
+    fn default() -> Self {
+        Self {
+            overdraw: true,
+            cluster_size: 64,
+        }
+    }
+}
+
+impl Object3d {
+    // Reorders triangles for rendering. Triangles only move within their sub mesh so material
+    // ranges stay valid, per triangle data follows its triangle
+    pub fn optimize_for_gpu(&mut self, options: &GpuOptimizeOptions) {
+        if options.overdraw && options.cluster_size > 0 {
+            self.sort_clusters_front_to_back(options.cluster_size);
+        }
+    }
+
+    fn sort_clusters_front_to_back(&mut self, cluster_size: usize) {
+        let positions: Vec<[f32; 3]> = self.vertices().map(|v| v.pos()).collect();
+        if positions.is_empty() {
+            return;
+        }
+        let centroid = scale(positions.iter().copied().fold([0.0; 3], add), 1.0 / positions.len() as f32);
+        let corner = |triangle: usize, i: usize| positions[self.index_buffer[triangle * 3 + i] as usize];
+
+        let triangle_count = self.index_buffer.len() / 3;
+        let ranges: Vec<(usize, usize)> = match self.sub_meshes.is_empty() {
+            true => vec!((0, triangle_count)),
+            false => self.sub_meshes.iter().map(|x| (x.index_start / 3, (x.index_start + x.index_count) / 3)).collect(),
+        };
+
+        let mut order: Vec<usize> = Vec::with_capacity(triangle_count);
+        for (start, end) in ranges {
+            let mut clusters: Vec<(f32, Vec<usize>)> = Vec::new();
+            let triangles: Vec<usize> = (start..end).collect();
+            for cluster in triangles.chunks(cluster_size) {
+                let (mut weighted_centroid, mut normal, mut area) = ([0.0; 3], [0.0; 3], 0.0);
+                for triangle in cluster {
+                    let (a, b, c) = (corner(*triangle, 0), corner(*triangle, 1), corner(*triangle, 2));
+                    let cross = triangle_cross(a, b, c);
+                    let triangle_area = length(cross);
+                    weighted_centroid = add(weighted_centroid, scale(add(add(a, b), c), triangle_area / 3.0));
+                    normal = add(normal, cross);
+                    area += triangle_area;
+                }
+                let cluster_centroid = scale(weighted_centroid, 1.0 / f32::max(area, f32::EPSILON));
+                clusters.push((dot(sub(cluster_centroid, centroid), normalize(normal)), cluster.to_vec()));
+            }
+            clusters.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+            order.extend(clusters.into_iter().flat_map(|(_, x)| x));
+        }
+
+        self.index_buffer = order.iter().flat_map(|t| self.index_buffer[t * 3..t * 3 + 3].to_vec()).collect();
+        self.triangle_sources = order.iter().filter_map(|t| self.triangle_sources.get(*t).cloned()).collect();
+        self.smoothing_groups = order.iter().filter_map(|t| self.smoothing_groups.get(*t).copied()).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object3d::SubMesh;
+    use crate::test_fixtures::positions;
+    use super::*;
+
+    // An inward facing triangle inside the object followed by an outward facing one on its surface
+    fn inner_then_outer() -> Object3d {
+        let points = [
+            [0.0, 0.0, 0.5], [0.0, 1.0, 0.5], [1.0, 0.0, 0.5],
+            [0.0, 0.0, 2.0], [1.0, 0.0, 2.0], [0.0, 1.0, 2.0],
+            [0.0, 0.0, -2.5],
+        ];
+
+        Object3d {
+            smoothing_groups: vec!(1, 2),
+            ..positions("Shell", &points, &[0, 1, 2, 3, 4, 5])
+        }
+    }
+
+    #[test]
+    fn optimize_for_gpu_draws_outward_facing_clusters_first() {
+        let mut obj = inner_then_outer();
+
+        obj.optimize_for_gpu(&GpuOptimizeOptions { cluster_size: 1, ..Default::default() });
+
+        assert_eq!(vec!(3u64, 4, 5, 0, 1, 2), obj.index_buffer);
+        assert_eq!(vec!(2u32, 1), obj.smoothing_groups, "Per triangle data follows its triangle");
+
+        let mut untouched = inner_then_outer();
+        untouched.optimize_for_gpu(&GpuOptimizeOptions { overdraw: false, cluster_size: 1 });
+        assert_eq!(vec!(0u64, 1, 2, 3, 4, 5), untouched.index_buffer);
+    }
+
+    #[test]
+    fn optimize_for_gpu_keeps_triangles_in_their_sub_mesh() {
+        let mut obj = inner_then_outer();
+        obj.sub_meshes = vec!(
+            SubMesh { material: Some(String::from("Inner")), index_start: 0, index_count: 3 },
+            SubMesh { material: Some(String::from("Outer")), index_start: 3, index_count: 3 },
+        );
+
+        obj.optimize_for_gpu(&GpuOptimizeOptions { cluster_size: 1, ..Default::default() });
+
+        assert_eq!(vec!(0u64, 1, 2, 3, 4, 5), obj.index_buffer);
+    }
+}
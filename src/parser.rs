@@ -1,16 +1,62 @@
+use crate::f;
 use crate::nan_safe_float::Float;
 use crate::token::{Token, TokenType, TokenDataType};
 use crate::statement::{Statement, StatementDataType, StatementType};
+use crate::diagnostic::Diagnostic;
+use crate::error::WfoError;
 
-struct Parser {
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ParserOptions {
+    // Stop parse_tokens_with_recovery after this many diagnostics so input that isn't an OBJ
+    // file at all doesn't produce one per line
+    pub max_errors: Option<usize>,
+    pub comment_retention: CommentRetention,
+    pub on_unknown: UnknownKeywords,
+}
+
+// What to do with a line that starts with a keyword the parser doesn't know, e.g. `vp` or a
+// vendor extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum UnknownKeywords {
+    // drop the line silently
+    Skip,
+    #[default]
+    Error,
+    // drop the line and record a warning
+    Collect,
+}
+
+// Which comment statements the parser returns. Every comment of a large scan costs memory, but the
+// header block at the top of the file usually carries the provenance worth keeping
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CommentRetention {
+    DropAll,
+    // comments before the first statement that isn't one
+    HeaderOnly,
+    #[default]
+    KeepAll,
+}
+
+pub(crate) struct Parser {
     statement_type: Option<StatementType>,
     statement_data: StatementDataType,
     statement_line_number: u64,
     statement_line_position: u64,
+    last_line_number: u64,
+    last_line_position: u64,
     data_buffer: Vec<Float>,
     index_buffer: Vec<u64>,
+    // whether each index_buffer entry was relative
+    relative_buffer: Vec<bool>,
     parsed_token_count: u64,
     next_expected_token: TokenType,
+    options: ParserOptions,
+    // v, vt and vn statements parsed so far, for resolving relative face indices
+    declared_counts: [u64; 3],
+    past_header: bool,
+    // the rest of a line with an unknown keyword is being dropped
+    skipping_unknown: bool,
+    warnings: Vec<Diagnostic>,
 }
 
 impl Default for Parser {
@@ -20,41 +66,267 @@ impl Default for Parser {
             statement_data: StatementDataType::None(),
             statement_line_number: 0,
             statement_line_position: 0,
+            last_line_number: 0,
+            last_line_position: 0,
             data_buffer: Vec::new(),
             index_buffer: Vec::new(),
+            relative_buffer: Vec::new(),
             parsed_token_count: 0,
             next_expected_token: TokenType::COMMENT,
+            options: ParserOptions::default(),
+            declared_counts: [0; 3],
+            past_header: false,
+            skipping_unknown: false,
+            warnings: Vec::new(),
         }
     }
 }
 
 impl Parser {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Default::default()
     }
 
+    pub(crate) fn from_options(options: ParserOptions) -> Self {
+        Parser {
+            options,
+            ..Default::default()
+        }
+    }
+
+    // Continues the v/vt/vn counts of input that came before the tokens, e.g. the lines above a
+    // range being re-parsed, so relative face indices resolve as in the whole file
+    pub(crate) fn seed_declared_counts(&mut self, counts: [u64; 3]) {
+        self.declared_counts = counts;
+    }
+
     pub fn parse_tokens(
-        mut self,
+        &mut self,
         tokens: &Vec<Token>,
-    ) -> Result<Vec<Statement>, String> {
+    ) -> Result<Vec<Statement>, WfoError> {
         let mut parsed_statements = Vec::new();
 
         for cur_token in tokens {
             let parse_result = self.parse_token(cur_token);
             if parse_result.is_err() {
-                return Err(parse_result.err().unwrap());
+                return Err(self.error_at(parse_result.err().unwrap(), cur_token));
             }
             
-            let parse_result = parse_result?;
+            let parse_result = parse_result.expect("Errors returned above");
             if !parse_result.is_none() {
                 parsed_statements.push(parse_result.unwrap());
             }
         }
 
+        let flush_result = self.finish()?;
+        if let Some(statement) = flush_result {
+            parsed_statements.push(statement);
+        }
+
         Ok(parsed_statements)
     }
+
+    // Like parse_tokens but collects every error instead of stopping at the first, for validation
+    // tooling. The same recovery as parse_tokens_with_recovery, with each diagnostic as a ParseError
+    // naming the statement it interrupted
+    pub fn parse_tokens_collecting_errors(
+        self,
+        tokens: &Vec<Token>,
+    ) -> (Vec<Statement>, Vec<WfoError>) {
+        let (parsed_statements, diagnostics) = self.recover(tokens);
+        let errors = diagnostics.into_iter()
+            .map(|(diagnostic, statement)| WfoError::ParseError {
+                message: diagnostic.message,
+                line_number: diagnostic.line_number,
+                line_position: diagnostic.line_position,
+                statement,
+            })
+            .collect();
+
+        (parsed_statements, errors)
+    }
+
+    // Unlike parse_tokens, this doesn't stop at the first error. The rest of the offending line is
+    // skipped so every line yields either a statement or exactly one diagnostic
+    pub fn parse_tokens_with_recovery(
+        self,
+        tokens: &Vec<Token>,
+    ) -> (Vec<Statement>, Vec<Diagnostic>) {
+        let (parsed_statements, diagnostics) = self.recover(tokens);
+
+        (parsed_statements, diagnostics.into_iter().map(|(diagnostic, _)| diagnostic).collect())
+    }
+
+    // Diagnostics come with the statement being parsed when they were raised
+    fn recover(
+        mut self,
+        tokens: &Vec<Token>,
+    ) -> (Vec<Statement>, Vec<(Diagnostic, Option<StatementType>)>) {
+        let mut parsed_statements = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut skipping_line = false;
+
+        for cur_token in tokens {
+            if skipping_line {
+                skipping_line = cur_token.token_type != TokenType::LINEBREAK;
+                continue;
+            }
+
+            match self.parse_token(cur_token) {
+                Ok(Some(statement)) => parsed_statements.push(statement),
+                Ok(None) => {},
+                Err(message) => {
+                    diagnostics.push((Diagnostic::from(message, cur_token.line_number, cur_token.line_position), self.statement_type));
+                    if self.options.max_errors.is_some_and(|max| diagnostics.len() >= max) {
+                        diagnostics.push((Diagnostic::from(
+                            format!("Stopped after {} errors", diagnostics.len()),
+                            cur_token.line_number,
+                            cur_token.line_position
+                        ), None));
+                        return (parsed_statements, self.merge_warnings(diagnostics));
+                    }
+                    self.reset_state();
+                    skipping_line = cur_token.token_type != TokenType::LINEBREAK;
+                },
+            }
+        }
+
+        let unfinished = self.statement_type;
+        match self.flush() {
+            Ok(Some(statement)) => parsed_statements.push(statement),
+            Ok(None) => {},
+            Err(message) => {
+                // only the end of the input can leave a statement unfinished
+                diagnostics.push((Diagnostic::truncated_input(
+                    format!("Input ends mid-statement and may be truncated: {}", message),
+                    self.last_line_number,
+                    self.last_line_position
+                ), unfinished));
+            },
+        }
+        if let Some(diagnostic) = Self::check_declared_counts(&parsed_statements) {
+            diagnostics.push((diagnostic, None));
+        }
+
+        (parsed_statements, self.merge_warnings(diagnostics))
+    }
+
+    fn merge_warnings(
+        &mut self,
+        mut diagnostics: Vec<(Diagnostic, Option<StatementType>)>,
+    ) -> Vec<(Diagnostic, Option<StatementType>)> {
+        if !self.warnings.is_empty() {
+            diagnostics.extend(self.warnings.drain(..).map(|w| (w, None)));
+            diagnostics.sort_by_key(|(d, _)| (d.line_number, d.line_position));
+        }
+
+        diagnostics
+    }
+
+    // Some exporters write summary comments like "# 8 vertices" or "# 6 faces". Finding fewer
+    // statements than those comments declare suggests the file was cut off
+    pub(crate) fn check_declared_counts(statements: &[Statement]) -> Option<Diagnostic> {
+        let (mut declared_vertices, mut declared_faces) = (0u64, 0u64);
+        for statement in statements.iter().filter(|s| s.statement_type == StatementType::COMMENT) {
+            let text = match &statement.data {
+                StatementDataType::String(x) => x,
+                _ => continue,
+            };
+            let words: Vec<&str> = text.trim_start_matches('#').split_whitespace().collect();
+            for pair in words.windows(2) {
+                let count = match pair[0].parse::<u64>() {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                match pair[1] {
+                    "vertices" | "verts" => declared_vertices += count,
+                    "faces" | "polygons" => declared_faces += count,
+                    _ => {},
+                }
+            }
+        }
+
+        let count = |statement_type| statements.iter().filter(|s| s.statement_type == statement_type).count() as u64;
+        let (vertices, faces) = (count(StatementType::VERTEX), count(StatementType::FACE));
+        if vertices >= declared_vertices && faces >= declared_faces {
+            return None;
+        }
+
+        let last = statements.last()?;
+        Some(Diagnostic::truncated_input(
+            format!(
+                "Comments declare {} vertices and {} faces but only {} and {} were found, the input may be truncated",
+                declared_vertices, declared_faces, vertices, faces
+            ),
+            last.line_number,
+            last.line_position
+        ))
+    }
+
+    // Parses tokens as they arrive from a pushed stream. Statements still in progress at the end
+    // of the batch are completed by later calls or by finish
+    pub(crate) fn push_tokens(&mut self, tokens: &Vec<Token>) -> Result<Vec<Statement>, WfoError> {
+        let mut parsed_statements = Vec::new();
+
+        for cur_token in tokens {
+            match self.parse_token(cur_token) {
+                Ok(Some(statement)) => parsed_statements.push(statement),
+                Ok(None) => {},
+                Err(message) => return Err(self.error_at(message, cur_token)),
+            }
+        }
+
+        Ok(parsed_statements)
+    }
+
+    // Only the end of the input can leave a statement unfinished, so a failed flush is reported
+    // as truncation like parse_tokens_with_recovery does
+    pub(crate) fn finish(&mut self) -> Result<Option<Statement>, WfoError> {
+        let (line_number, line_position) = (self.last_line_number, self.last_line_position + 1);
+
+        self.flush().map_err(|message| WfoError::TruncatedInput {
+            message: format!("Input ends mid-statement and may be truncated: {}", message),
+            line_number,
+            line_position,
+            statement: self.statement_type,
+        })
+    }
+
+    // The statement being parsed is still set when a token inside it fails
+    fn error_at(&self, message: String, token: &Token) -> WfoError {
+        WfoError::ParseError {
+            message,
+            line_number: token.line_number,
+            line_position: token.line_position,
+            statement: self.statement_type,
+        }
+    }
+
+    // A final statement that isn't terminated by a line break is still a statement, so treat
+    // the end of the token stream as an implicit line break
+    fn flush(&mut self) -> Result<Option<Statement>, String> {
+        if self.statement_type.is_none() {
+            return Ok(None);
+        }
+
+        let implicit_line_break = Token::from(
+            TokenType::LINEBREAK,
+            TokenDataType::String(String::new()),
+            self.last_line_number,
+            self.last_line_position + 1
+        );
+
+        self.parse_token(&implicit_line_break)
+    }
     
     fn parse_token(&mut self, token: &Token) -> Result<Option<Statement>, String> {
+        self.last_line_number = token.line_number;
+        self.last_line_position = token.line_position;
+        if self.skipping_unknown {
+            self.skipping_unknown = token.token_type != TokenType::LINEBREAK;
+            return Ok(None);
+        }
+
         if self.statement_type.is_none() {
             let parse_result = self.handle_expecting_header_state(token);
             if parse_result.is_err() {
@@ -65,12 +337,31 @@ impl Parser {
             if parse_result.is_err() {
                 return Err(parse_result.err().unwrap())
             }
+            if let Ok(Some(statement)) = &parse_result {
+                self.count_declaration(statement.statement_type);
+                if !self.retains(statement.statement_type) {
+                    return Ok(None);
+                }
+            }
             return parse_result;
         }
         
         Ok(None)
     }
 
+    fn retains(&mut self, statement_type: StatementType) -> bool {
+        if statement_type != StatementType::COMMENT {
+            self.past_header = true;
+            return true;
+        }
+
+        match self.options.comment_retention {
+            CommentRetention::DropAll => false,
+            CommentRetention::HeaderOnly => !self.past_header,
+            CommentRetention::KeepAll => true,
+        }
+    }
+
     fn handle_expecting_header_state(&mut self, cur_token: &Token) -> Result<(), String>{
         if
             cur_token.token_type == TokenType::SEPARATOR ||
@@ -81,6 +372,11 @@ impl Parser {
         
         let new_statement_type = Self::convert_token_type_to_statement_type(cur_token.token_type);
         if new_statement_type.is_none() {
+            if let TokenDataType::String(keyword) = &cur_token.data {
+                if cur_token.token_type == TokenType::STRING {
+                    return self.skip_unknown_keyword(keyword, cur_token);
+                }
+            }
             return Err(String::from("Expected statement start"));
         }
 
@@ -99,17 +395,40 @@ impl Parser {
         Ok(())
     }
     
+    fn skip_unknown_keyword(&mut self, keyword: &str, token: &Token) -> Result<(), String> {
+        let message = format!("Unknown keyword {}", keyword);
+        match self.options.on_unknown {
+            UnknownKeywords::Error => return Err(message),
+            UnknownKeywords::Skip => {},
+            UnknownKeywords::Collect => {
+                self.warnings.push(Diagnostic::warning(format!("{}, line skipped", message), token.line_number, token.line_position));
+            },
+        }
+        self.skipping_unknown = true;
+
+        Ok(())
+    }
+
+    // Lines skipped under UnknownKeywords::Collect, in input order
+    pub(crate) fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+    
     fn handle_token(&mut self, token: &Token) -> Result<Option<Statement>, String>{
         match self.statement_type {
             Some(StatementType::COMMENT) => self.parse_comment_statement(token),
             Some(StatementType::MTLLIB) => self.parse_single_string_statement(token),
             Some(StatementType::OBJECT) => self.parse_single_string_statement(token),
-            Some(StatementType::VERTEX) => self.parse_number_statement(token, 3),
-            Some(StatementType::NORMAL) => self.parse_number_statement(token, 3),
-            Some(StatementType::TEXCOORD) => self.parse_number_statement(token, 2),
+            // v and vt may carry an optional trailing w, and plenty of files write `vt u` alone
+            Some(StatementType::VERTEX) => self.parse_number_statement(token, 3, 4),
+            Some(StatementType::NORMAL) => self.parse_number_statement(token, 3, 3),
+            Some(StatementType::TEXCOORD) => self.parse_number_statement(token, 1, 3),
+            Some(StatementType::TEXCOORD2) => self.parse_number_statement(token, 2, 2),
             Some(StatementType::USEMTL) => self.parse_single_string_statement(token),
             Some(StatementType::FACE) => self.parse_face_statement(token),
-            Some(StatementType::ILLUM) => self.parse_number_statement(token, 1),
+            Some(StatementType::SMOOTHING) => self.parse_smoothing_group_statement(token),
+            Some(StatementType::GROUP) => self.parse_group_statement(token),
+            Some(StatementType::LINE) => self.parse_line_statement(token),
             _ => Ok(None)
         }
     }
@@ -141,10 +460,71 @@ impl Parser {
         }
     }
     
-    fn parse_number_statement(&mut self, token: &Token, expected_number_count: u64) -> Result<Option<Statement>, String> {
-        let tokens_until_line_break = 1 + (expected_number_count * 2);
+    // `g` takes any number of names (none means the default group), kept as one space separated
+    // string. The lexer keeps names that look like numbers as strings
+    fn parse_group_statement(&mut self, token: &Token) -> Result<Option<Statement>, String> {
+        let name = match (&token.token_type, &token.data) {
+            (TokenType::SEPARATOR, _) => {
+                self.parsed_token_count += 1;
+                return Ok(None);
+            },
+            (TokenType::LINEBREAK, _) => {
+                if self.statement_data == StatementDataType::None() {
+                    self.statement_data = StatementDataType::String(String::new());
+                }
+                self.parsed_token_count += 1;
+                return Ok(Some(self.extract_statement()));
+            },
+            (TokenType::STRING, TokenDataType::String(x)) => x.clone(),
+            _ => return Err(Self::get_unexpected_token_error(token)),
+        };
 
-        if self.next_expected_token == TokenType::SEPARATOR && token.token_type == TokenType::SEPARATOR {
+        self.statement_data = match &self.statement_data {
+            StatementDataType::String(names) => StatementDataType::String(format!("{} {}", names, name)),
+            _ => StatementDataType::String(name),
+        };
+        self.parsed_token_count += 1;
+        Ok(None)
+    }
+
+    // `s` takes a single group number, with `off` and `0` both meaning no smoothing
+    fn parse_smoothing_group_statement(&mut self, token: &Token) -> Result<Option<Statement>, String> {
+        if self.is_expected_token(token, TokenType::SEPARATOR) {
+            self.next_expected_token = TokenType::NUMBER;
+            self.parsed_token_count += 1;
+            return Ok(None);
+        }
+        if self.next_expected_token == TokenType::LINEBREAK && token.token_type == TokenType::LINEBREAK {
+            self.parsed_token_count += 1;
+            return Ok(Some(self.extract_statement()));
+        }
+        if self.next_expected_token != TokenType::NUMBER {
+            return Err(Self::get_unexpected_token_error(token));
+        }
+
+        let group = match &token.data {
+            TokenDataType::String(x) if x == "off" => f!(0.0),
+            TokenDataType::Number(x) if **x >= 0.0 && x.fract() == 0.0 && **x <= u32::MAX as f64 => *x,
+            TokenDataType::Number(x) => {
+                return Err(format!("Expected smoothing group to be \"off\" or a non-negative integer but found {}", x));
+            },
+            TokenDataType::String(x) => {
+                return Err(format!("Expected smoothing group to be \"off\" or a non-negative integer but found {}", x));
+            },
+            _ => return Err(Self::get_unexpected_token_error(token)),
+        };
+        self.statement_data = StatementDataType::Number(group);
+        self.next_expected_token = TokenType::LINEBREAK;
+        self.parsed_token_count += 1;
+
+        Ok(None)
+    }
+
+    fn parse_number_statement(&mut self, token: &Token, min_number_count: usize, max_number_count: usize) -> Result<Option<Statement>, String> {
+        // a separator after the last required number may start an optional one
+        let may_continue = self.next_expected_token == TokenType::LINEBREAK && self.data_buffer.len() < max_number_count;
+
+        if (self.next_expected_token == TokenType::SEPARATOR || may_continue) && token.token_type == TokenType::SEPARATOR {
             self.next_expected_token = TokenType::NUMBER;
             
             self.parsed_token_count += 1;
@@ -158,7 +538,7 @@ impl Parser {
             
             self.parsed_token_count += 1;
             
-            if self.parsed_token_count >= tokens_until_line_break {
+            if self.data_buffer.len() >= min_number_count {
                 self.next_expected_token = TokenType::LINEBREAK;
             } else {
                 self.next_expected_token = TokenType::SEPARATOR;
@@ -166,7 +546,17 @@ impl Parser {
             
             return Ok(None);
         } else if self.next_expected_token == TokenType::LINEBREAK && token.token_type == TokenType::LINEBREAK {
-            if expected_number_count == 3 && self.data_buffer.len() == 3 {
+            if self.data_buffer.len() == 4 {
+                self.statement_data = StatementDataType::Number4D(
+                    self.data_buffer[0],
+                    self.data_buffer[1],
+                    self.data_buffer[2],
+                    self.data_buffer[3]
+                );
+
+                self.parsed_token_count += 1;
+                return Ok(Some(self.extract_statement()));
+            } else if self.data_buffer.len() == 3 {
                 self.statement_data = StatementDataType::Number3D(
                     self.data_buffer[0],
                     self.data_buffer[1],
@@ -175,7 +565,7 @@ impl Parser {
 
                 self.parsed_token_count += 1;
                 return Ok(Some(self.extract_statement()));
-            } else if expected_number_count == 2 && self.data_buffer.len() == 2 {
+            } else if self.data_buffer.len() == 2 {
                 self.statement_data = StatementDataType::Number2D(
                     self.data_buffer[0],
                     self.data_buffer[1]
@@ -183,7 +573,7 @@ impl Parser {
                 
                 self.parsed_token_count += 1;
                 return Ok(Some(self.extract_statement()));
-            } else if expected_number_count == 1 && self.data_buffer.len() == 1 {
+            } else if self.data_buffer.len() == 1 {
                 self.statement_data = StatementDataType::Number(self.data_buffer[0]);
                 
                 self.parsed_token_count += 1;
@@ -201,43 +591,46 @@ impl Parser {
     }
     
     fn parse_face_statement(&mut self, token: &Token) -> Result<Option<Statement>, String> {
-        if self.is_expected_token(token, TokenType::SEPARATOR) {
+        // a separator after the third vertex may start another one
+        let may_continue = self.next_expected_token == TokenType::LINEBREAK && self.index_buffer.len() >= 9;
+        if self.is_expected_token(token, TokenType::SEPARATOR) || (may_continue && token.token_type == TokenType::SEPARATOR) {
             self.next_expected_token = TokenType::POLYGON;
             
             self.parsed_token_count += 1;
             Ok(None)
-        } else if self.is_expected_token(token, TokenType::POLYGON) {
-            if let TokenDataType::VertexPTN(x, y, z) = token.data {
-                self.index_buffer.push(x);
-                self.index_buffer.push(y);
-                self.index_buffer.push(z);
-            } else {
-                return Err(String::from("Expected token data to be VertexPNT"));
+        } else if self.next_expected_token == TokenType::POLYGON && Self::is_face_vertex_token(token) {
+            match token.data {
+                TokenDataType::VertexPTN(x, y, z) => {
+                    self.push_index(x, 0)?;
+                    self.push_index(y, 1)?;
+                    self.push_index(z, 2)?;
+                },
+                // `f 1 2 3` lexes as numbers, which are position only vertices
+                TokenDataType::Number(x) if x.fract() == 0.0 => {
+                    self.push_index(*x as i64, 0)?;
+                    self.push_index(0, 1)?;
+                    self.push_index(0, 2)?;
+                },
+                TokenDataType::Number(x) => {
+                    return Err(format!("Expected face vertex index to be an integer but found {}", x));
+                },
+                _ => return Err(String::from("Expected token data to be VertexPNT")),
             }
 
             self.parsed_token_count += 1;
-            if self.parsed_token_count >= 7 {
+            if self.index_buffer.len() >= 9 {
                 self.next_expected_token = TokenType::LINEBREAK;
             } else {
                 self.next_expected_token = TokenType::SEPARATOR;
             }
 
             Ok(None)
-        } else if self.is_expected_token(token, TokenType::LINEBREAK) {
-            if self.index_buffer.len() != 9 {
-                return Err(String::from("Expected face statement to have 9 indices"));
-            }
-            self.statement_data = StatementDataType::FacePTN(
-                self.index_buffer[0],
-                self.index_buffer[1],
-                self.index_buffer[2],
-                self.index_buffer[3],
-                self.index_buffer[4],
-                self.index_buffer[5],
-                self.index_buffer[6],
-                self.index_buffer[7],
-                self.index_buffer[8]
-            );
+        } else if token.token_type == TokenType::LINEBREAK && self.index_buffer.len() >= 9 {
+            let corners: Vec<[u64; 3]> = self.index_buffer.chunks(3).map(|c| [c[0], c[1], c[2]]).collect();
+            self.statement_data = match StatementDataType::from_face_corners(&corners) {
+                Some(x) => x,
+                None => return Err(String::from("Expected face statement to have 3 or 4 vertices")),
+            };
             
             self.parsed_token_count += 1;
             Ok(Some(self.extract_statement()))
@@ -246,6 +639,73 @@ impl Parser {
         }
     }
     
+    // `l` takes two or more vertices. Only the position index is kept, a texture index on a
+    // line vertex has nothing to map onto
+    fn parse_line_statement(&mut self, token: &Token) -> Result<Option<Statement>, String> {
+        let index = match (&token.token_type, &token.data) {
+            (TokenType::SEPARATOR, _) => {
+                self.parsed_token_count += 1;
+                return Ok(None);
+            },
+            (TokenType::LINEBREAK, _) => {
+                if self.index_buffer.len() < 2 {
+                    return Err(String::from("Expected line statement to have at least 2 vertices"));
+                }
+                self.statement_data = StatementDataType::Polyline(self.index_buffer.clone());
+                self.parsed_token_count += 1;
+                return Ok(Some(self.extract_statement()));
+            },
+            (TokenType::POLYGON, TokenDataType::VertexPTN(x, _, _)) => *x,
+            (TokenType::NUMBER, TokenDataType::Number(x)) if x.fract() == 0.0 => **x as i64,
+            (TokenType::NUMBER, TokenDataType::Number(x)) => {
+                return Err(format!("Expected line vertex index to be an integer but found {}", x));
+            },
+            _ => return Err(Self::get_unexpected_token_error(token)),
+        };
+
+        self.push_index(index, 0)?;
+        self.parsed_token_count += 1;
+        Ok(None)
+    }
+
+    fn push_index(&mut self, index: i64, kind: usize) -> Result<(), String> {
+        let resolved = self.resolve_index(index, kind)?;
+        self.index_buffer.push(resolved);
+        self.relative_buffer.push(index < 0);
+
+        Ok(())
+    }
+
+    // Turns a relative index (-1 is the latest declaration) of the given kind (0 = v, 1 = vt,
+    // 2 = vn) into an absolute 1 based one
+    fn resolve_index(&self, index: i64, kind: usize) -> Result<u64, String> {
+        if index >= 0 {
+            return Ok(index as u64);
+        }
+
+        let count = self.declared_counts[kind];
+        let back = index.unsigned_abs();
+        if back > count {
+            let names = ["position", "texture coordinate", "normal"];
+            return Err(format!("Relative {} index {} reaches before the first of {} declared", names[kind], index, count));
+        }
+
+        Ok(count - back + 1)
+    }
+
+    fn count_declaration(&mut self, statement_type: StatementType) {
+        match statement_type {
+            StatementType::VERTEX => self.declared_counts[0] += 1,
+            StatementType::TEXCOORD => self.declared_counts[1] += 1,
+            StatementType::NORMAL => self.declared_counts[2] += 1,
+            _ => {},
+        }
+    }
+
+    fn is_face_vertex_token(token: &Token) -> bool {
+        token.token_type == TokenType::POLYGON || token.token_type == TokenType::NUMBER
+    }
+    
     fn is_expected_token(&self, token: &Token, expected_type: TokenType) -> bool {
         self.next_expected_token == token.token_type && token.token_type == expected_type
     }
@@ -270,9 +730,12 @@ impl Parser {
             TokenType::VERTEX => Some(StatementType::VERTEX),
             TokenType::NORMAL => Some(StatementType::NORMAL),
             TokenType::TEXCOORD => Some(StatementType::TEXCOORD),
+            TokenType::TEXCOORD2 => Some(StatementType::TEXCOORD2),
             TokenType::USEMTL => Some(StatementType::USEMTL),
             TokenType::FACE => Some(StatementType::FACE),
-            TokenType::ILLUM => Some(StatementType::ILLUM),
+            TokenType::SMOOTHING => Some(StatementType::SMOOTHING),
+            TokenType::GROUP => Some(StatementType::GROUP),
+            TokenType::LINE => Some(StatementType::LINE),
             _ => None
         }
     }
@@ -283,6 +746,10 @@ impl Parser {
             data: self.statement_data.clone(),
             line_number: self.statement_line_number,
             line_position: self.statement_line_position,
+            relative_indices: match self.relative_buffer.contains(&true) {
+                true => self.relative_buffer.clone(),
+                false => Vec::new(),
+            },
         };
         
         self.reset_state();
@@ -298,12 +765,14 @@ impl Parser {
         self.parsed_token_count = 0;
         self.data_buffer = Vec::new();
         self.index_buffer = Vec::new();
+        self.relative_buffer = Vec::new();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::f;
+    use crate::diagnostic::DiagnosticKind;
     use super::*;
 
     #[test]
@@ -352,6 +821,42 @@ mod tests {
         );
     }
     
+    #[test]
+    fn parser_retains_comments_by_level() {
+        let expected = [
+            (CommentRetention::DropAll, vec!(StatementType::OBJECT)),
+            (CommentRetention::HeaderOnly, vec!(StatementType::COMMENT, StatementType::COMMENT, StatementType::OBJECT)),
+            (CommentRetention::KeepAll, vec!(StatementType::COMMENT, StatementType::COMMENT, StatementType::OBJECT, StatementType::COMMENT)),
+        ];
+        for (comment_retention, statement_types) in expected {
+            let mut parser = Parser::from_options(ParserOptions { comment_retention, ..Default::default() });
+            
+            let statements = parser.parse_tokens(&commented_tokens()).expect("Comments parse");
+            
+            assert_eq!(
+                statement_types,
+                statements.iter().map(|s| s.statement_type).collect::<Vec<StatementType>>(),
+                "{:?} keeps the expected comments", comment_retention
+            );
+        }
+    }
+    
+    // # exported by\n# scanner 2.1\no Scan\n# late comment\n
+    fn commented_tokens() -> Vec<Token> {
+        vec![
+            Token::from(TokenType::COMMENT, TokenDataType::String(String::from("# exported by")), 1, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            Token::from(TokenType::COMMENT, TokenDataType::String(String::from("# scanner 2.1")), 2, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 0),
+            Token::from(TokenType::OBJECT, TokenDataType::None(), 3, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 3, 0),
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("Scan")), 3, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 3, 0),
+            Token::from(TokenType::COMMENT, TokenDataType::String(String::from("# late comment")), 4, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 4, 0),
+        ]
+    }
+    
     #[test]
     fn parser_parses_vertex_statement() {
         // v 1.0 2.0 3.0\n
@@ -372,6 +877,63 @@ mod tests {
         );
     }
     
+    #[test]
+    fn parser_parses_vertex_statement_with_w() {
+        // v 1.0 2.0 3.0 0.5\n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(3.0)), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.5)), 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::VERTEX, StatementDataType::Number4D(f!(1.0), f!(2.0), f!(3.0), f!(0.5)), 1, 0),
+            ]
+        );
+    }
+    
+    #[test]
+    fn parser_rejects_normal_statement_with_w() {
+        // vn 0.0 1.0 0.0 1.0\n
+        let result = Parser::new().parse_tokens(&vec![
+            Token::from(TokenType::NORMAL, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+        ]);
+        
+        assert!(result.is_err(), "Normals have no w component");
+    }
+    
+    #[test]
+    fn parser_parses_single_component_texcoord_statement() {
+        // vt 0.5\n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::TEXCOORD, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.5)), 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::TEXCOORD, StatementDataType::Number(f!(0.5)), 1, 0),
+            ]
+        );
+    }
+    
     #[test]
     fn parser_parses_normal_statement() {
         // vn 0.707 0.0 0.707\n
@@ -447,17 +1009,176 @@ mod tests {
     }
     
     #[test]
-    fn parser_parses_illum_statement() {
+    fn parser_parses_quad_face_statement() {
+        // f 1/2/3 4/5/6 7/8/9 10/11/12\n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::FACE, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(1, 2, 3), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(4, 5, 6), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(7, 8, 9), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(10, 11, 12), 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::FACE, StatementDataType::QuadPTN(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12), 1, 0),
+            ]
+        );
+    }
+    
+    #[test]
+    fn parser_parses_face_statement_with_bare_position_indices() {
+        // f 1 2 3\n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::FACE, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(3.0)), 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::FACE, StatementDataType::FacePTN(1, 0, 0, 2, 0, 0, 3, 0, 0), 1, 0),
+            ]
+        );
+    }
+    
+    #[test]
+    fn parser_resolves_relative_face_indices() {
+        // v 0 0 0\nv 1 0 0\nvn 0 0 1\nf -2//-1 -1//-1 1//1\n
+        let mut tokens = Vec::new();
+        for (token_type, x) in [(TokenType::VERTEX, 0.0), (TokenType::VERTEX, 1.0), (TokenType::NORMAL, 0.0)] {
+            tokens.push(Token::from(token_type, TokenDataType::None(), 1, 0));
+            for n in [x, 0.0, 0.0] {
+                tokens.push(Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0));
+                tokens.push(Token::from(TokenType::NUMBER, TokenDataType::Number(Float::new(n).unwrap()), 1, 0));
+            }
+            tokens.push(Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0));
+        }
+        tokens.push(Token::from(TokenType::FACE, TokenDataType::None(), 2, 0));
+        for vertex in [(-2, 0, -1), (-1, 0, -1), (1, 0, 1)] {
+            tokens.push(Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 0));
+            tokens.push(Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(vertex.0, vertex.1, vertex.2), 2, 0));
+        }
+        tokens.push(Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 0));
+
+        let statements = Parser::new().parse_tokens(&tokens).expect("Relative indices resolve");
+
+        assert_eq!(StatementDataType::FacePTN(1, 0, 1, 2, 0, 1, 1, 0, 1), statements[3].data, "Relative indices count back from the latest declaration");
+        assert_eq!(
+            vec!(true, false, true, true, false, true, false, false, false),
+            statements[3].relative_indices,
+            "Resolved indices are marked so the compiler doesn't rebase them"
+        );
+        assert!(statements[0].relative_indices.is_empty());
+    }
+    
+    #[test]
+    fn parser_rejects_relative_indices_before_first_declaration() {
+        // f -1 -1 -1\n
+        let result = Parser::new().parse_tokens(&vec![
+            Token::from(TokenType::FACE, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(-1.0)), 1, 0),
+        ]);
+
+        assert_eq!(Some(String::from("Relative position index -1 reaches before the first of 0 declared")), result.err().map(|e| e.message()));
+    }
+    
+    #[test]
+    fn parser_rejects_fractional_face_indices() {
+        // f 1.5 2 3\n
+        let result = Parser::new().parse_tokens(&vec![
+            Token::from(TokenType::FACE, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.5)), 1, 0),
+        ]);
+
+        assert_eq!(Some(String::from("Expected face vertex index to be an integer but found 1.5")), result.err().map(|e| e.message()));
+    }
+    
+    #[test]
+    fn parser_rejects_faces_with_more_than_four_vertices() {
+        // f 1// 1// 1// 1// 1//\n
+        let mut tokens = vec![Token::from(TokenType::FACE, TokenDataType::None(), 1, 0)];
+        for _ in 0..5 {
+            tokens.push(Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0));
+            tokens.push(Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(1, 0, 0), 1, 0));
+        }
+        tokens.push(Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0));
+
+        let result = Parser::new().parse_tokens(&tokens);
+
+        assert_eq!(Some(String::from("Expected face statement to have 3 or 4 vertices")), result.err().map(|e| e.message()));
+    }
+
+    #[test]
+    fn parser_errors_carry_the_token_location_and_statement() {
+        // v 1.0\n
+        let tokens = vec![
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 3, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 3, 1),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 3, 2),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 3, 5),
+        ];
+
+        let error = Parser::new().parse_tokens(&tokens).err().expect("Vertices need 3 numbers");
+
+        assert_eq!(Some((3, 5)), error.location());
+        assert!(matches!(error, WfoError::ParseError { statement: Some(StatementType::VERTEX), .. }), "{:?}", error);
+    }
+    
+    #[test]
+    fn parser_parses_group_statement_with_several_names() {
+        // g wheels 4\n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::GROUP, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::STRING, TokenDataType::String(String::from("wheels")), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::STRING, TokenDataType::String(String::from("4")), 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::GROUP, StatementDataType::String(String::from("wheels 4")), 1, 0),
+            ]
+        );
+    }
+    
+    #[test]
+    fn parser_parses_group_statement_without_names() {
+        // g\n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::GROUP, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::GROUP, StatementDataType::String(String::new()), 1, 0),
+            ]
+        );
+    }
+    
+    #[test]
+    fn parser_parses_smoothing_group_statement() {
         // s 1\n
         parser_parses_tokens_into_statements(
             &vec![
-                Token::from(TokenType::ILLUM, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SMOOTHING, TokenDataType::None(), 1, 0),
                 Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
                 Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
                 Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
             ],
             &vec![
-                Statement::from(StatementType::ILLUM, StatementDataType::Number(f!(1.0)), 1, 0),
+                Statement::from(StatementType::SMOOTHING, StatementDataType::Number(f!(1.0)), 1, 0),
             ]
         );
     }
@@ -493,11 +1214,281 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parser_parses_final_statement_without_line_break() {
+        // v 1.0 2.0 3.0
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(3.0)), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::VERTEX, StatementDataType::Number3D(f!(1.0), f!(2.0), f!(3.0)), 1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parser_parses_final_comment_without_line_break() {
+        // # This is a comment
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::COMMENT, TokenDataType::String(String::from("# This is a comment")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::COMMENT, StatementDataType::String(String::from("# This is a comment")), 1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parser_parses_line_statement() {
+        // l 1 2/1 -1\n
+        let statements = Parser::new().parse_tokens(&vec![
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 1, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            Token::from(TokenType::LINE, TokenDataType::None(), 2, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 2, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 0),
+            Token::from(TokenType::POLYGON, TokenDataType::VertexPTN(2, 1, 0), 2, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(-1.0)), 2, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 0),
+        ]).expect("Line statements parse");
+
+        assert_eq!(StatementType::LINE, statements[1].statement_type);
+        assert_eq!(
+            StatementDataType::Polyline(vec!(1, 2, 1)),
+            statements[1].data,
+            "Only position indices are kept and relative ones are resolved"
+        );
+    }
+    
+    #[test]
+    fn parser_rejects_line_with_one_vertex() {
+        // l 1\n
+        let result = Parser::new().parse_tokens(&vec![
+            Token::from(TokenType::LINE, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+        ]);
+
+        assert_eq!(Some(String::from("Expected line statement to have at least 2 vertices")), result.err().map(|e| e.message()));
+    }
+    
+    #[test]
+    fn parser_parses_smoothing_group_off_as_zero() {
+        // s off\n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::SMOOTHING, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::STRING, TokenDataType::String(String::from("off")), 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::SMOOTHING, StatementDataType::Number(f!(0.0)), 1, 0),
+            ]
+        );
+    }
+    
+    #[test]
+    fn parser_rejects_invalid_smoothing_groups() {
+        for (token_type, data, found) in [
+            (TokenType::STRING, TokenDataType::String(String::from("on")), "on"),
+            (TokenType::NUMBER, TokenDataType::Number(f!(1.5)), "1.5"),
+            (TokenType::NUMBER, TokenDataType::Number(f!(-1.0)), "-1"),
+        ] {
+            let tokens = vec![
+                Token::from(TokenType::SMOOTHING, TokenDataType::None(), 1, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+                Token::from(token_type, data, 1, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+            ];
+            
+            assert_eq!(
+                Some(format!("Expected smoothing group to be \"off\" or a non-negative integer but found {}", found)),
+                Parser::new().parse_tokens(&tokens).err().map(|e| e.message()),
+                "Parser rejects smoothing group {}", found
+            );
+        }
+    }
+    
+    #[test]
+    fn parser_ignores_blank_lines_between_statements() {
+        // \n
+        // s 1\n
+        // \n
+        parser_parses_tokens_into_statements(
+            &vec![
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 0),
+                Token::from(TokenType::SMOOTHING, TokenDataType::None(), 2, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 0),
+                Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 2, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 0),
+                Token::from(TokenType::SEPARATOR, TokenDataType::None(), 3, 0),
+                Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 3, 0),
+            ],
+            &vec![
+                Statement::from(StatementType::SMOOTHING, StatementDataType::Number(f!(1.0)), 2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parser_returns_err_when_final_statement_is_incomplete() {
+        // v 1.0 2.0
+        let mut parser = Parser::new();
+
+        let result = parser.parse_tokens(&vec![
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 1, 0),
+        ]);
+
+        assert!(
+            matches!(result, Err(WfoError::TruncatedInput { statement: Some(StatementType::VERTEX), .. })),
+            "Parser reports a token stream ending mid-statement as truncated input"
+        );
+    }
+
+    #[test]
+    fn parser_with_recovery_reports_one_diagnostic_per_bad_line() {
+        // g group_name\n
+        // v 1.0 2.0\n
+        // vt 0.75 0.25\n
+        let parser = Parser::new();
+
+        let (statements, diagnostics) = parser.parse_tokens_with_recovery(&vec![
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("g")), 1, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 2),
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("group_name")), 1, 3),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 13),
+
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 2, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 2, 3),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 6),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 2, 7),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 10),
+
+            Token::from(TokenType::TEXCOORD, TokenDataType::None(), 3, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 3, 3),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.75)), 3, 4),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 3, 8),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.25)), 3, 9),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 3, 13),
+        ]);
+
+        assert_statement_vectors_are_equal(
+            &vec![
+                Statement::from(StatementType::TEXCOORD, StatementDataType::Number2D(f!(0.75), f!(0.25)), 3, 1),
+            ],
+            &statements
+        );
+
+        assert_eq!(
+            vec!(1u64, 2u64),
+            diagnostics.iter().map(|d| d.line_number).collect::<Vec<u64>>(),
+            "Parser reports exactly one diagnostic for each bad line"
+        );
+    }
+
+    #[test]
+    fn parser_with_recovery_reports_incomplete_final_statement() {
+        // v 1.0
+        let parser = Parser::new();
+
+        let (statements, diagnostics) = parser.parse_tokens_with_recovery(&vec![
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 3),
+        ]);
+
+        assert_eq!(0, statements.len(), "Parser returns no statements for an incomplete file");
+        assert_eq!(1, diagnostics.len(), "Parser reports the incomplete final statement");
+    }
+
+    #[test]
+    fn parser_with_recovery_flags_incomplete_final_statement_as_truncated() {
+        // v 1.0
+        let parser = Parser::new();
+
+        let (_, diagnostics) = parser.parse_tokens_with_recovery(&vec![
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 3),
+        ]);
+
+        assert_eq!(DiagnosticKind::TruncatedInput, diagnostics[0].kind, "Statement cut off by the end of input is a truncation");
+    }
+
+    #[test]
+    fn parser_with_recovery_stops_after_max_errors() {
+        // x
+        // y
+        // z
+        let parser = Parser::from_options(ParserOptions { max_errors: Some(2), ..Default::default() });
+
+        let (_, diagnostics) = parser.parse_tokens_with_recovery(&vec![
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("x")), 1, 1),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 2),
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("y")), 2, 1),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 2),
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("z")), 3, 1),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 3, 2),
+        ]);
+
+        assert_eq!(3, diagnostics.len(), "Parser stops at the cap and says so");
+        assert_eq!(String::from("Stopped after 2 errors"), diagnostics[2].message);
+        assert_eq!(2, diagnostics[2].line_number, "Parser stops at the line that hit the cap");
+    }
+
+    #[test]
+    fn parser_with_recovery_flags_counts_below_header_comments_as_truncated() {
+        // # 2 vertices
+        // v 1.0 2.0 3.0
+        let parser = Parser::new();
+
+        let (statements, diagnostics) = parser.parse_tokens_with_recovery(&vec![
+            Token::from(TokenType::COMMENT, TokenDataType::String(String::from("# 2 vertices")), 1, 1),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 13),
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 2, 1),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 2, 3),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 6),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 2, 7),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 10),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(3.0)), 2, 11),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 14),
+        ]);
+
+        assert_eq!(2, statements.len(), "Parser returns every complete statement");
+        assert_eq!(1, diagnostics.len(), "Parser reports the missing vertex");
+        assert_eq!(DiagnosticKind::TruncatedInput, diagnostics[0].kind, "Missing declared vertices are a truncation");
+        assert_eq!(2, diagnostics[0].line_number, "Truncation is reported at the last statement");
+    }
+
     fn parser_parses_tokens_into_statements(
         input_tokens: &Vec<Token>,
         expected_statements: &Vec<Statement>
     ) {
-        let parser = Parser::new();
+        let mut parser = Parser::new();
 
         let result = parser.parse_tokens(input_tokens);
         
@@ -550,4 +1541,121 @@ mod tests {
             );
         }
     }
+
+    fn unknown_keyword_tokens() -> Vec<Token> {
+        // vp 0.5\n
+        // v 1.0 2.0 3.0\n
+        vec![
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("vp")), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.5)), 1, 3),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 6),
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 2, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 1),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 2, 2),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 5),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(2.0)), 2, 6),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 9),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(3.0)), 2, 10),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 13),
+        ]
+    }
+
+    #[test]
+    fn parser_errors_on_unknown_keywords_by_default() {
+        let error = Parser::new().parse_tokens(&unknown_keyword_tokens()).err().expect("vp is unknown");
+
+        assert_eq!("Unknown keyword vp", error.message());
+    }
+
+    #[test]
+    fn parser_skips_unknown_keyword_lines_when_lenient() {
+        let mut parser = Parser::from_options(ParserOptions { on_unknown: UnknownKeywords::Skip, ..Default::default() });
+
+        let statements = parser.parse_tokens(&unknown_keyword_tokens()).expect("Unknown lines are skipped");
+
+        assert_eq!(1, statements.len(), "Only the vertex is parsed");
+        assert_eq!(StatementType::VERTEX, statements[0].statement_type);
+        assert!(parser.warnings().is_empty(), "Skip doesn't record warnings");
+    }
+
+    #[test]
+    fn parser_collects_warnings_for_unknown_keyword_lines() {
+        let mut parser = Parser::from_options(ParserOptions { on_unknown: UnknownKeywords::Collect, ..Default::default() });
+
+        let statements = parser.parse_tokens(&unknown_keyword_tokens()).expect("Unknown lines are skipped");
+
+        assert_eq!(1, statements.len());
+        assert_eq!(
+            &[Diagnostic::warning(String::from("Unknown keyword vp, line skipped"), 1, 0)],
+            parser.warnings()
+        );
+
+        let parser = Parser::from_options(ParserOptions { on_unknown: UnknownKeywords::Collect, ..Default::default() });
+        let (_, diagnostics) = parser.parse_tokens_with_recovery(&unknown_keyword_tokens());
+        assert_eq!(vec!(DiagnosticKind::Warning), diagnostics.iter().map(|d| d.kind).collect::<Vec<_>>(), "Recovery reports the warnings too");
+    }
+
+
+    #[test]
+    fn parser_collects_every_error_with_its_location() {
+        // v 1.0 x 3.0\n
+        // vn 0.0 1.0 0.0\n
+        // vn 0.5
+        let parser = Parser::new();
+
+        let (statements, errors) = parser.parse_tokens_collecting_errors(&vec![
+            Token::from(TokenType::VERTEX, TokenDataType::None(), 1, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 1),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 1, 2),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 5),
+            Token::from(TokenType::STRING, TokenDataType::String(String::from("x")), 1, 6),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 1, 7),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(3.0)), 1, 8),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 1, 11),
+            Token::from(TokenType::NORMAL, TokenDataType::None(), 2, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 2, 3),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 6),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(1.0)), 2, 7),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 2, 10),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.0)), 2, 11),
+            Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 2, 14),
+            Token::from(TokenType::NORMAL, TokenDataType::None(), 3, 0),
+            Token::from(TokenType::SEPARATOR, TokenDataType::None(), 3, 2),
+            Token::from(TokenType::NUMBER, TokenDataType::Number(f!(0.5)), 3, 3),
+        ]);
+
+        assert_eq!(1, statements.len(), "The good line between the errors is still parsed");
+        assert_eq!(StatementType::NORMAL, statements[0].statement_type);
+        assert_eq!(
+            vec!((Some((1, 6)), Some(StatementType::VERTEX)), (Some((3, 4)), Some(StatementType::NORMAL))),
+            errors.iter().map(|e| match e {
+                WfoError::ParseError { statement, .. } => (e.location(), *statement),
+                _ => (None, None),
+            }).collect::<Vec<_>>()
+        );
+    }
+
+
+    #[test]
+    fn parser_collecting_errors_keeps_warnings_and_the_stop_marker() {
+        let parser = Parser::from_options(ParserOptions { on_unknown: UnknownKeywords::Collect, max_errors: Some(1), ..Default::default() });
+        let mut tokens = unknown_keyword_tokens();
+        tokens.push(Token::from(TokenType::VERTEX, TokenDataType::None(), 3, 0));
+        tokens.push(Token::from(TokenType::LINEBREAK, TokenDataType::String(String::from("\n")), 3, 1));
+
+        let (statements, errors) = parser.parse_tokens_collecting_errors(&tokens);
+
+        assert_eq!(1, statements.len());
+        assert_eq!(
+            vec!(
+                String::from("Unknown keyword vp, line skipped"),
+                String::from("Unexpected token. Expected \"SEPARATOR\" but found \"LINEBREAK\""),
+                String::from("Stopped after 1 errors"),
+            ),
+            errors.iter().map(|e| e.message()).collect::<Vec<_>>()
+        );
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use crate::object3d::Object3d;
+use crate::vertex::{VertexData, VertexFormat};
+
+// Where one object's data sits in a VertexPool. Indices are local to the object, so draws add
+// base_vertex (e.g. glDrawElementsBaseVertex)
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PoolRange {
+    pub name: String,
+    pub base_vertex: u64,
+    pub vertex_count: usize,
+    pub index_start: usize,
+    pub index_count: usize,
+}
+
+// Every object of a file packed into one shared vertex and index buffer, for engines that batch
+// static geometry into a single GPU buffer
+#[derive(PartialEq, Debug, Clone)]
+pub struct VertexPool {
+    pub format: VertexFormat,
+    pub vertex_buffer: Vec<VertexData>,
+    pub index_buffer: Vec<u64>,
+    pub ranges: Vec<PoolRange>,
+}
+
+impl VertexPool {
+    // Fails when the objects don't share a vertex format, objects without any vertices are
+    // skipped but still get an empty range
+    pub fn from_objects(objects: &[Object3d]) -> Result<Self, String> {
+        let mut pool = VertexPool {
+            format: VertexFormat::Unknown,
+            vertex_buffer: Vec::new(),
+            index_buffer: Vec::new(),
+            ranges: Vec::new(),
+        };
+
+        for object in objects {
+            if !object.vertex_buffer.is_empty() {
+                if pool.format == VertexFormat::Unknown {
+                    pool.format = object.format;
+                } else if pool.format != object.format {
+                    return Err(format!(
+                        "Object {} has vertex format {:?} but the pool holds {:?}", object.name, object.format, pool.format
+                    ));
+                }
+            }
+
+            pool.ranges.push(PoolRange {
+                name: object.name.clone(),
+                base_vertex: pool.vertex_buffer.len() as u64,
+                vertex_count: object.vertex_buffer.len(),
+                index_start: pool.index_buffer.len(),
+                index_count: object.index_buffer.len(),
+            });
+            pool.vertex_buffer.extend_from_slice(&object.vertex_buffer);
+            pool.index_buffer.extend_from_slice(&object.index_buffer);
+        }
+
+        Ok(pool)
+    }
+
+    // Indices that already include each object's base vertex, for APIs without base vertex draws
+    pub fn absolute_indices(&self) -> Vec<u64> {
+        let mut indices = self.index_buffer.clone();
+        for range in &self.ranges {
+            for index in &mut indices[range.index_start..range.index_start + range.index_count] {
+                *index += range.base_vertex;
+            }
+        }
+
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::positions;
+    use super::*;
+
+    fn triangle(name: &str, x: f64) -> Object3d {
+        positions(name, &[[x, 0.0, 0.0], [x, 1.0, 0.0], [x, 0.0, 1.0]], &[0, 1, 2])
+    }
+
+    #[test]
+    fn vertex_pool_packs_objects_with_base_vertex_offsets() {
+        let pool = VertexPool::from_objects(&[triangle("A", 0.0), triangle("B", 5.0)])
+            .expect("Objects share a vertex format");
+
+        assert_eq!(6, pool.vertex_buffer.len());
+        assert_eq!(vec!(0u64, 1, 2, 0, 1, 2), pool.index_buffer, "Indices stay local to each object");
+        assert_eq!(
+            PoolRange { name: String::from("B"), base_vertex: 3, vertex_count: 3, index_start: 3, index_count: 3 },
+            pool.ranges[1]
+        );
+        assert_eq!(vec!(0u64, 1, 2, 3, 4, 5), pool.absolute_indices());
+    }
+
+    #[test]
+    fn vertex_pool_rejects_mixed_vertex_formats() {
+        let mut other = triangle("B", 5.0);
+        other.format = VertexFormat::VertexPN;
+
+        assert_eq!(
+            Some(String::from("Object B has vertex format VertexPN but the pool holds VertexP")),
+            VertexPool::from_objects(&[triangle("A", 0.0), other]).err()
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// Keyword counts from a scan that only looks at the first word of each line, which is far cheaper
+// than lexing. Good for reserving buffer capacity and as the denominator of a progress estimate
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct StatementCounts {
+    pub lines: usize,
+    // non blank lines, each of which the parser turns into one statement
+    pub statements: usize,
+    pub vertices: usize,
+    pub normals: usize,
+    pub tex_coords: usize,
+    // vt2, the second uv set
+    pub tex_coords2: usize,
+    pub faces: usize,
+    pub objects: usize,
+}
+
+impl StatementCounts {
+    // Fraction of the file parsed once parsed_statements statements have come out of the parser
+    pub fn progress(&self, parsed_statements: usize) -> f32 {
+        if self.statements == 0 {
+            return 1.0;
+        }
+
+        (parsed_statements as f32 / self.statements as f32).min(1.0)
+    }
+}
+
+pub fn count_statements(bytes: &[u8]) -> StatementCounts {
+    let mut counts = StatementCounts::default();
+
+    for line in bytes.split(|b| *b == b'\n') {
+        counts.lines += 1;
+        let keyword = match line.split(|b| b.is_ascii_whitespace()).find(|w| !w.is_empty()) {
+            Some(x) => x,
+            None => continue,
+        };
+        counts.statements += 1;
+        match keyword {
+            b"v" => counts.vertices += 1,
+            b"vn" => counts.normals += 1,
+            b"vt" => counts.tex_coords += 1,
+            b"vt2" => counts.tex_coords2 += 1,
+            b"f" => counts.faces += 1,
+            b"o" => counts.objects += 1,
+            _ => {},
+        }
+    }
+    // a trailing line break doesn't start another line
+    if bytes.ends_with(b"\n") || bytes.is_empty() {
+        counts.lines -= 1;
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::push_parser::PushParser;
+    use super::*;
+
+    #[test]
+    fn count_statements_counts_keywords_and_skips_blank_lines() {
+        let counts = count_statements(b"# cube\nv 0 0 0\r\nv 1 0 0\n\n  \nvt 0 0\nvt2 0 0\nvn 0 1 0\no Cube\nf 1 2 1\n");
+
+        assert_eq!(
+            StatementCounts { lines: 10, statements: 8, vertices: 2, normals: 1, tex_coords: 1, tex_coords2: 1, faces: 1, objects: 1 },
+            counts
+        );
+    }
+
+    #[test]
+    fn count_statements_counts_unterminated_last_line() {
+        assert_eq!(2, count_statements(b"v 0 0 0\nv 1 0 0").lines);
+        assert_eq!(0, count_statements(b"").lines);
+    }
+
+    #[test]
+    fn prepass_matches_statements_from_the_real_parse() {
+        let text = "v 0 0 0\nv 1 0 0\n\nv 0 1 0\nf 1 2 3\n";
+        let counts = count_statements(text.as_bytes());
+        let mut push_parser = PushParser::new();
+
+        push_parser.push_bytes(&text.as_bytes()[..17]).expect("Push parser accepts valid chunk");
+        let halfway = counts.progress(push_parser.parsed_statement_count());
+        push_parser.push_bytes(&text.as_bytes()[17..]).expect("Push parser accepts valid chunk");
+        let statements = push_parser.finish().expect("Push parser finishes valid input");
+
+        assert_eq!(counts.statements, statements.len());
+        assert_eq!(0.5, halfway, "Two of four statements are parsed after the first chunk");
+        assert_eq!(1.0, counts.progress(statements.len()));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::diagnostic::Diagnostic;
+use crate::error::WfoError;
+use crate::lexer::{sniff_text, Lexer};
+use crate::options::ParseOptions;
+use crate::parser::Parser;
+use crate::statement::Statement;
+
+// Parses a stream that arrives in chunks (e.g. an OBJ body downloading over the network) without
+// buffering the whole input first
+pub struct PushParser {
+    lexer: Lexer,
+    parser: Parser,
+    statements: Vec<Statement>,
+    // whether a non empty chunk was checked with sniff_text yet
+    sniffed: bool,
+}
+
+impl Default for PushParser {
+    fn default() -> Self {
+        PushParser {
+            lexer: Lexer::new(),
+            parser: Parser::new(),
+            statements: Vec::new(),
+            sniffed: false,
+        }
+    }
+}
+
+impl PushParser {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn from_options(options: &ParseOptions) -> Self {
+        PushParser {
+            lexer: Lexer::from_options(options.lexer.clone()),
+            parser: Parser::from_options(options.parser.clone()),
+            ..Default::default()
+        }
+    }
+
+    // The first non empty chunk is checked for binary content like the start of a file is
+    pub fn push_bytes(&mut self, chunk: &[u8]) -> Result<(), WfoError> {
+        if !self.sniffed && !chunk.is_empty() {
+            sniff_text(chunk)?;
+            self.sniffed = true;
+        }
+        let tokens = self.lexer.push_bytes(chunk);
+        let mut statements = self.parser.push_tokens(&tokens)?;
+        self.statements.append(&mut statements);
+
+        Ok(())
+    }
+
+    // Statements completed so far, e.g. for StatementCounts::progress
+    pub fn parsed_statement_count(&self) -> usize {
+        self.statements.len()
+    }
+
+    // Warnings the lexer and parser recorded so far, e.g. Latin-1 fallbacks or skipped unknown
+    // keywords, in input order. Lines still buffered are only checked by finish_with_warnings
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        let mut warnings: Vec<Diagnostic> = self.lexer.warnings().iter()
+            .chain(self.parser.warnings())
+            .cloned()
+            .collect();
+        warnings.sort_by_key(|d| (d.line_number, d.line_position));
+
+        warnings
+    }
+
+    // Statements of every chunk pushed, pass them to compile_statements for objects
+    pub fn finish(self) -> Result<Vec<Statement>, WfoError> {
+        self.finish_with_warnings().map(|(statements, _)| statements)
+    }
+
+    // finish that also returns the warnings of the whole input, including its last line
+    pub fn finish_with_warnings(mut self) -> Result<(Vec<Statement>, Vec<Diagnostic>), WfoError> {
+        let tokens = self.lexer.finish();
+        let mut statements = self.parser.push_tokens(&tokens)?;
+        self.statements.append(&mut statements);
+
+        if let Some(statement) = self.parser.finish()? {
+            self.statements.push(statement);
+        }
+        let warnings = self.warnings();
+
+        Ok((self.statements, warnings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::lexer::{LexerOptions, TextEncoding};
+    use crate::parser::{ParserOptions, UnknownKeywords};
+    use crate::statement::{StatementDataType, StatementType};
+    use super::*;
+
+    #[test]
+    fn push_parser_parses_statements_split_across_chunks() {
+        let mut push_parser = PushParser::new();
+
+        push_parser.push_bytes("v 1.0 2".as_bytes()).expect("Push parser accepts valid chunk");
+        push_parser.push_bytes(".0 3.0\nvt 0.".as_bytes()).expect("Push parser accepts valid chunk");
+        push_parser.push_bytes("75 0.25".as_bytes()).expect("Push parser accepts valid chunk");
+        let statements = push_parser.finish().expect("Push parser finishes valid input");
+
+        assert_eq!(2, statements.len(), "Push parser returns every statement");
+        assert_eq!(StatementType::VERTEX, statements[0].statement_type);
+        assert_eq!(StatementDataType::Number3D(f!(1.0), f!(2.0), f!(3.0)), statements[0].data);
+        assert_eq!(StatementType::TEXCOORD, statements[1].statement_type);
+        assert_eq!(StatementDataType::Number2D(f!(0.75), f!(0.25)), statements[1].data);
+    }
+
+    #[test]
+    fn push_parser_returns_err_for_invalid_chunk() {
+        let mut push_parser = PushParser::new();
+
+        let result = push_parser.push_bytes("v 1.0 asdf 3.0\n".as_bytes());
+
+        assert!(result.is_err(), "Push parser returns err when a chunk can't be parsed");
+    }
+
+    #[test]
+    fn push_parser_returns_lexer_and_parser_warnings() {
+        let options = ParseOptions {
+            lexer: LexerOptions { encoding: TextEncoding::Utf8WithLatin1Fallback, ..Default::default() },
+            parser: ParserOptions { on_unknown: UnknownKeywords::Collect, ..Default::default() },
+            ..Default::default()
+        };
+        let mut push_parser = PushParser::from_options(&options);
+
+        push_parser.push_bytes(b"o Caf\xe9\nv 1.0 2.0 3.0\n").expect("Push parser accepts valid chunk");
+        assert_eq!(1, push_parser.warnings().len(), "The Latin-1 fallback is reported right away");
+        push_parser.push_bytes(b"bevel on").expect("Push parser accepts valid chunk");
+        let (statements, warnings) = push_parser.finish_with_warnings().expect("Push parser finishes valid input");
+
+        assert_eq!(2, statements.len(), "The unknown keyword line is skipped");
+        assert_eq!(vec!(1, 3), warnings.iter().map(|w| w.line_number).collect::<Vec<_>>(), "Warnings of the unterminated last line are kept");
+    }
+
+    #[test]
+    fn push_parser_rejects_binary_first_chunk() {
+        let mut push_parser = PushParser::new();
+
+        push_parser.push_bytes(b"").expect("Empty chunks aren't sniffed");
+        let result = push_parser.push_bytes(b"\x89PNG\r\n\x1a\n\x00\x00");
+
+        assert_eq!(Some(String::from("Input is not a Wavefront OBJ text file")), result.err().map(|e| e.message()));
+    }
+}
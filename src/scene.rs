@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::diagnostic::Diagnostic;
+use crate::mtl::{AlphaMode, Material};
+use crate::object3d::{FaceSource, Object3d, SubMesh};
+use crate::options::Timings;
+
+// Everything loading an OBJ file produces. Materials are keyed by their newmtl name, which is what
+// Object3d::material and SubMesh::material refer to
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Scene {
+    pub objects: Vec<Object3d>,
+    // objects named like collision hulls (UCX_ and friends), kept out of objects so they aren't
+    // rendered. Object3d::collision_shape tells which kind each one is
+    pub collision_objects: Vec<Object3d>,
+    pub materials: HashMap<String, Material>,
+    // the resolved path of every library named by an mtllib statement, in the order they appear
+    pub mtl_libs: Vec<PathBuf>,
+    // warnings of every stage in line order, including libraries that couldn't be loaded
+    pub warnings: Vec<Diagnostic>,
+    // set when ParseOptions::collect_timings asks for them, loading the libraries isn't included
+    pub timings: Option<Timings>,
+}
+
+// A texture file and the map keywords (map_Kd, bump, ...) that use it
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TextureReference {
+    pub path: String,
+    pub channels: Vec<String>,
+}
+
+impl Scene {
+    // The material an object's faces were assigned first, None when it has none or the libraries
+    // don't define it
+    pub fn material_of(&self, object: &Object3d) -> Option<&Material> {
+        object.material.as_ref().and_then(|name| self.materials.get(name))
+    }
+
+    // Whether a sub mesh should be drawn without backface culling, from its material (see
+    // Material::is_double_sided) and optionally from its shape: an open surface such as a leaf
+    // card or a flag has no inside, so its back faces are visible too
+    pub fn is_double_sided(&self, object: &Object3d, sub_mesh: &SubMesh, detect_open_surfaces: bool) -> bool {
+        let material = sub_mesh.material.as_ref().and_then(|name| self.materials.get(name));
+
+        material.is_some_and(|m| m.is_double_sided()) || (detect_open_surfaces && has_open_edges(object, sub_mesh))
+    }
+
+    // Sub meshes without a material, or whose material isn't defined, are opaque
+    pub fn alpha_mode(&self, sub_mesh: &SubMesh) -> AlphaMode {
+        sub_mesh.material.as_ref()
+            .and_then(|name| self.materials.get(name))
+            .map(|m| m.alpha_mode())
+            .unwrap_or(AlphaMode::Opaque)
+    }
+
+    // Every texture file the materials reference, listed once, for build tools that check the
+    // files exist or schedule conversions. Paths are as written in the libraries. Materials are
+    // visited in name order so the list is stable
+    pub fn texture_references(&self) -> Vec<TextureReference> {
+        let mut materials: Vec<&Material> = self.materials.values().collect();
+        materials.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut references: Vec<TextureReference> = Vec::new();
+        for (keyword, map) in materials.iter().flat_map(|m| m.texture_maps()) {
+            match references.iter_mut().find(|r| r.path == map.path) {
+                Some(reference) if reference.channels.iter().any(|c| c == keyword) => {},
+                Some(reference) => reference.channels.push(String::from(keyword)),
+                None => references.push(TextureReference { path: map.path.clone(), channels: vec!(String::from(keyword)) }),
+            }
+        }
+
+        references
+    }
+
+    // Every (object index, sub mesh index) pair with opaque sub meshes first and blended ones last,
+    // otherwise in scene order
+    pub fn draw_order(&self) -> Vec<(usize, usize)> {
+        let mut order: Vec<(usize, usize)> = self.objects.iter().enumerate()
+            .flat_map(|(o, object)| (0..object.sub_meshes.len()).map(move |s| (o, s)))
+            .collect();
+        order.sort_by_key(|(o, s)| self.alpha_mode(&self.objects[*o].sub_meshes[*s]));
+
+        order
+    }
+
+    // The face a triangle of objects[object] was cut from: its line in the input and the vertex
+    // indices as written there. None if either index is out of range
+    pub fn triangle_source(&self, object: usize, triangle: usize) -> Option<&FaceSource> {
+        self.objects.get(object).and_then(|o| o.triangle_source(triangle))
+    }
+}
+
+// A scene behind an Arc, so worker threads (GPU upload, physics cooking) can share the parsed
+// meshes without copying them. Clones are cheap and all see the same scene
+#[derive(Debug, Clone)]
+pub struct SharedScene(Arc<Scene>);
+
+// Scene and everything in it are plain owned data, keep it that way so SharedScene can cross threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Scene>();
+};
+
+impl From<Scene> for SharedScene {
+    fn from(scene: Scene) -> Self {
+        SharedScene(Arc::new(scene))
+    }
+}
+
+impl SharedScene {
+    pub fn scene(&self) -> &Scene {
+        &self.0
+    }
+
+    pub fn objects(&self) -> &[Object3d] {
+        &self.0.objects
+    }
+
+    pub fn collision_objects(&self) -> &[Object3d] {
+        &self.0.collision_objects
+    }
+
+    pub fn material(&self, name: &str) -> Option<&Material> {
+        self.0.materials.get(name)
+    }
+}
+
+// An edge used by only one triangle. Edges are matched by position, so vertices split along UV or
+// normal seams don't open the surface
+fn has_open_edges(object: &Object3d, sub_mesh: &SubMesh) -> bool {
+    let positions: Vec<[u32; 3]> = object.vertices().map(|v| v.pos().map(f32::to_bits)).collect();
+    let indices = &object.index_buffer[sub_mesh.index_start..sub_mesh.index_start + sub_mesh.index_count];
+    let mut edge_counts: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+
+    for triangle in indices.chunks(3) {
+        for corner in 0..3 {
+            let a = positions[triangle[corner] as usize];
+            let b = positions[triangle[(corner + 1) % 3] as usize];
+            *edge_counts.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+
+    edge_counts.values().any(|count| *count == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mtl::parse_mtl_str;
+    use crate::parse_obj_str;
+    use super::*;
+
+    const TETRAHEDRON: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nusemtl Glass\nf 1 3 2\nf 1 2 4\nusemtl Paint\nf 1 4 3\nf 2 3 4\n";
+
+    fn scene(obj: &str, mtl: &str) -> Scene {
+        let materials = parse_mtl_str(mtl).expect("Library parses");
+
+        Scene {
+            objects: parse_obj_str(obj, "default").expect("Input parses"),
+            materials: materials.into_iter().map(|m| (m.name.clone(), m)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scene_is_double_sided_from_materials_and_open_surfaces() {
+        let scene = scene(TETRAHEDRON, "newmtl Glass\nd 0.4\nnewmtl Paint\nKd 1 0 0\n");
+        let object = &scene.objects[0];
+        let closed = SubMesh { material: Some(String::from("Paint")), index_start: 0, index_count: 12 };
+
+        assert!(scene.is_double_sided(object, &object.sub_meshes[0], false), "Transparent materials are double sided");
+        assert!(!scene.is_double_sided(object, &object.sub_meshes[1], false), "Opaque materials are culled");
+        assert!(scene.is_double_sided(object, &object.sub_meshes[1], true), "Half a tetrahedron is an open surface");
+        assert!(!scene.is_double_sided(object, &closed, true), "The whole tetrahedron is closed");
+    }
+
+    #[test]
+    fn scene_maps_triangles_back_to_their_faces() {
+        let scene = scene(TETRAHEDRON, "");
+
+        assert_eq!(
+            Some(&FaceSource { line_number: 9, indices: vec!((1, 0, 0), (4, 0, 0), (3, 0, 0)) }),
+            scene.triangle_source(0, 2),
+            "The third triangle comes from the third face"
+        );
+        assert_eq!(None, scene.triangle_source(0, 4), "The tetrahedron has four triangles");
+        assert_eq!(None, scene.triangle_source(1, 0), "The scene has one object");
+    }
+
+    #[test]
+    fn scene_draws_blended_sub_meshes_last() {
+        let scene = scene(
+            &format!("{}o Leaves\nusemtl Leaf\nf 1 2 3\nusemtl Missing\nf 1 2 3\n", TETRAHEDRON),
+            "newmtl Glass\nTr 0.6\nnewmtl Paint\nKd 1 0 0\nnewmtl Leaf\nmap_d leaf_mask.png\n"
+        );
+
+        assert_eq!(AlphaMode::Blended, scene.alpha_mode(&scene.objects[0].sub_meshes[0]), "Tr below 1 blends");
+        assert_eq!(AlphaMode::AlphaTested, scene.alpha_mode(&scene.objects[1].sub_meshes[0]), "A map_d mask is alpha tested");
+        assert_eq!(AlphaMode::Opaque, scene.alpha_mode(&scene.objects[1].sub_meshes[1]), "Undefined materials are opaque");
+        assert_eq!(vec!((0, 1), (1, 1), (1, 0), (0, 0)), scene.draw_order());
+    }
+
+    #[test]
+    fn shared_scene_is_read_from_other_threads_without_copying() {
+        let shared = SharedScene::from(scene(TETRAHEDRON, "newmtl Glass\nd 0.4\n"));
+        let clone = shared.clone();
+
+        let triangles = std::thread::spawn(move || clone.objects()[0].index_buffer.len() / 3).join().expect("Worker finishes");
+
+        assert_eq!(4, triangles);
+        assert!(std::ptr::eq(shared.objects(), shared.clone().objects()), "Clones share the meshes");
+        assert!(shared.material("Glass").is_some());
+    }
+
+    #[test]
+    fn scene_lists_each_texture_once_with_its_channels() {
+        let scene = scene(
+            TETRAHEDRON,
+            "newmtl Paint\nmap_Kd paint.png\nbump -bm 2 paint_n.png\n\
+             newmtl Glass\nmap_Kd paint.png\nmap_d paint.png\nrefl -type cube_top sky.png\nrefl -type cube_left sky.png\n"
+        );
+        let reference = |path: &str, channels: &[&str]| TextureReference {
+            path: String::from(path),
+            channels: channels.iter().map(|c| String::from(*c)).collect(),
+        };
+
+        assert_eq!(
+            vec!(reference("paint.png", &["map_Kd", "map_d"]), reference("sky.png", &["refl"]), reference("paint_n.png", &["bump"])),
+            scene.texture_references()
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use crate::geometry::{add, cross, dot, length, scale, sub};
+use crate::object3d::Object3d;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SdfGrid {
+    pub resolution: [usize; 3],
+    pub origin: [f32; 3],
+    pub cell_size: f32,
+    // x varies fastest, then y, then z. Negative inside the mesh
+    pub distances: Vec<f32>,
+}
+
+impl SdfGrid {
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.distances[x + self.resolution[0] * (y + self.resolution[1] * z)]
+    }
+}
+
+// Bakes a signed distance field by brute force, sampling cell centers against every triangle. The
+// grid covers the object's bounds plus padding cells on every side so the zero crossing is
+// captured. Signs come from ray crossing parity, so the object must be watertight for the inside
+// to be meaningful
+pub fn bake_sdf(obj: &Object3d, max_resolution: usize, padding: usize) -> Option<SdfGrid> {
+    let positions: Vec<[f32; 3]> = obj.vertices().map(|v| v.pos()).collect();
+    let triangles: Vec<[[f32; 3]; 3]> = obj.index_buffer.chunks(3)
+        .map(|t| [positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize]])
+        .collect();
+    if triangles.is_empty() || max_resolution == 0 {
+        return None;
+    }
+
+    let mut min = positions[0];
+    let mut max = positions[0];
+    for p in &positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    let extent = sub(max, min);
+    let largest_extent = extent[0].max(extent[1]).max(extent[2]);
+    let cell_size = if largest_extent > 0.0 { largest_extent / max_resolution as f32 } else { 1.0 };
+    let resolution = [
+        (extent[0] / cell_size).ceil() as usize + 2 * padding,
+        (extent[1] / cell_size).ceil() as usize + 2 * padding,
+        (extent[2] / cell_size).ceil() as usize + 2 * padding,
+    ].map(|r| r.max(1));
+    let origin = sub(min, scale([1.0, 1.0, 1.0], padding as f32 * cell_size));
+
+    let mut distances = Vec::with_capacity(resolution[0] * resolution[1] * resolution[2]);
+    for z in 0..resolution[2] {
+        for y in 0..resolution[1] {
+            for x in 0..resolution[0] {
+                let cell_center = add(origin, scale([x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5], cell_size));
+                distances.push(signed_distance(cell_center, &triangles));
+            }
+        }
+    }
+
+    Some(SdfGrid {
+        resolution,
+        origin,
+        cell_size,
+        distances,
+    })
+}
+
+fn signed_distance(p: [f32; 3], triangles: &[[[f32; 3]; 3]]) -> f32 {
+    let distance = triangles.iter()
+        .map(|t| length(sub(p, closest_point_on_triangle(p, t))))
+        .fold(f32::MAX, f32::min);
+    // an odd number of crossings along any ray means the point is enclosed. The ray direction is
+    // skewed so it doesn't run exactly along the edges of axis aligned geometry
+    let crossings = triangles.iter().filter(|t| ray_intersects_triangle(p, [1.0, 0.0123, 0.0071], t)).count();
+
+    if crossings % 2 == 1 {
+        -distance
+    } else {
+        distance
+    }
+}
+
+// Real-Time Collision Detection (Ericson) 5.1.5
+fn closest_point_on_triangle(p: [f32; 3], t: &[[f32; 3]; 3]) -> [f32; 3] {
+    let [a, b, c] = *t;
+    let ab = sub(b, a);
+    let ac = sub(c, a);
+    let ap = sub(p, a);
+    let d1 = dot(ab, ap);
+    let d2 = dot(ac, ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = sub(p, b);
+    let d3 = dot(ab, bp);
+    let d4 = dot(ac, bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return add(a, scale(ab, d1 / (d1 - d3)));
+    }
+
+    let cp = sub(p, c);
+    let d5 = dot(ab, cp);
+    let d6 = dot(ac, cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return add(a, scale(ac, d2 / (d2 - d6)));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return add(b, scale(sub(c, b), (d4 - d3) / ((d4 - d3) + (d5 - d6))));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    add(a, add(scale(ab, vb * denom), scale(ac, vc * denom)))
+}
+
+// Möller–Trumbore, only counting hits in front of the origin
+fn ray_intersects_triangle(origin: [f32; 3], direction: [f32; 3], t: &[[f32; 3]; 3]) -> bool {
+    let edge1 = sub(t[1], t[0]);
+    let edge2 = sub(t[2], t[0]);
+    let h = cross(direction, edge2);
+    let a = dot(edge1, h);
+    if a.abs() < f32::EPSILON {
+        return false;
+    }
+
+    let f = 1.0 / a;
+    let s = sub(origin, t[0]);
+    let u = f * dot(s, h);
+    if !(0.0..=1.0).contains(&u) {
+        return false;
+    }
+
+    let q = cross(s, edge1);
+    let v = f * dot(direction, q);
+    if v < 0.0 || u + v > 1.0 {
+        return false;
+    }
+
+    f * dot(edge2, q) > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::positions;
+    use super::*;
+
+    #[test]
+    fn bake_sdf_is_negative_inside_and_positive_outside() {
+        let grid = bake_sdf(&cube(), 4, 1).expect("SDF bakes for a non-empty object");
+
+        assert_eq!([6, 6, 6], grid.resolution, "Grid covers the object plus padding");
+        assert_eq!(0.5, grid.cell_size, "Cells are sized from the largest extent");
+        assert!(grid.get(2, 2, 2) < 0.0, "Cells inside the cube are negative");
+        assert!(grid.get(0, 0, 0) > 0.0, "Cells outside the cube are positive");
+        assert!((grid.get(2, 2, 2) + 0.75).abs() < 1e-5, "Inside distance is the distance to the nearest face");
+    }
+
+    #[test]
+    fn bake_sdf_returns_none_for_empty_object() {
+        assert_eq!(None, bake_sdf(&Object3d::from(String::from("Empty")), 4, 1));
+    }
+
+    // unit cube from -1 to 1 with outward facing triangles
+    fn cube() -> Object3d {
+        let corners = [
+            [-1.0, -1.0, -1.0], [1.0, -1.0, -1.0], [1.0, 1.0, -1.0], [-1.0, 1.0, -1.0],
+            [-1.0, -1.0, 1.0], [1.0, -1.0, 1.0], [1.0, 1.0, 1.0], [-1.0, 1.0, 1.0],
+        ];
+
+        positions("Cube", &corners, &[
+            0, 2, 1, 0, 3, 2,
+            4, 5, 6, 4, 6, 7,
+            0, 1, 5, 0, 5, 4,
+            3, 7, 6, 3, 6, 2,
+            0, 4, 7, 0, 7, 3,
+            1, 2, 6, 1, 6, 5,
+        ])
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use crate::geometry::{add, dot, normalize};
+use crate::nan_safe_float::Float;
+use crate::object3d::Object3d;
+use crate::vertex::VertexData;
+
+// Why vertices sharing a position were kept apart. Each vertex after the first at a position is
+// counted once, against the earliest vertex there it would have merged with
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct SeamStats {
+    pub positions: usize,
+    // only the uv differs, a texture seam
+    pub uv_splits: usize,
+    // only the normal differs, a hard edge
+    pub normal_splits: usize,
+    // both differ
+    pub uv_and_normal_splits: usize,
+    // uv and normal match but e.g. the second uv set or a w component doesn't
+    pub other_splits: usize,
+}
+
+impl SeamStats {
+    pub fn duplicated(&self) -> usize {
+        self.uv_splits + self.normal_splits + self.uv_and_normal_splits + self.other_splits
+    }
+}
+
+impl Object3d {
+    pub fn seam_stats(&self) -> SeamStats {
+        let mut stats = SeamStats::default();
+        let mut at_position: HashMap<(Float, Float, Float), Vec<&VertexData>> = HashMap::new();
+
+        for vertex in &self.vertex_buffer {
+            let earlier = at_position.entry(vertex.pos).or_default();
+            if earlier.is_empty() {
+                stats.positions += 1;
+            } else if earlier.iter().any(|e| e.tex_coord != vertex.tex_coord && e.normal == vertex.normal && same_except_normal_and_uv(e, vertex)) {
+                stats.uv_splits += 1;
+            } else if earlier.iter().any(|e| e.normal != vertex.normal && e.tex_coord == vertex.tex_coord && same_except_normal_and_uv(e, vertex)) {
+                stats.normal_splits += 1;
+            } else if earlier.iter().any(|e| e.normal == vertex.normal && e.tex_coord == vertex.tex_coord) {
+                stats.other_splits += 1;
+            } else {
+                stats.uv_and_normal_splits += 1;
+            }
+            earlier.push(vertex);
+        }
+
+        stats
+    }
+
+    // Trades shading accuracy for size: vertices that only differ by a normal within
+    // min_cosine of the first such vertex's normal are merged into it and given the average normal.
+    // Hard edges sharper than that stay split. Returns how many vertices were removed
+    pub fn weld_normal_splits(&mut self, min_cosine: f32) -> usize {
+        let mut remap: Vec<u64> = Vec::with_capacity(self.vertex_buffer.len());
+        let mut kept: Vec<usize> = Vec::new();
+        let mut normal_sums: Vec<[f32; 3]> = Vec::new();
+        let mut at_position: HashMap<(Float, Float, Float), Vec<usize>> = HashMap::new();
+
+        for (i, vertex) in self.vertex_buffer.iter().enumerate() {
+            let normal = vertex.normal.map(to_array);
+            let candidates = at_position.entry(vertex.pos).or_default();
+            let target = normal.and_then(|n| candidates.iter().copied().find(|k| {
+                let other = &self.vertex_buffer[kept[*k]];
+                let other_normal = normalize(other.normal.map(to_array).unwrap_or([0.0; 3]));
+                other.tex_coord == vertex.tex_coord && same_except_normal_and_uv(other, vertex)
+                    && self.same_side_data(kept[*k], i) && dot(other_normal, normalize(n)) >= min_cosine
+            }));
+
+            match (target, normal) {
+                (Some(k), Some(n)) => {
+                    normal_sums[k] = add(normal_sums[k], normalize(n));
+                    remap.push(k as u64);
+                },
+                _ => {
+                    candidates.push(kept.len());
+                    remap.push(kept.len() as u64);
+                    normal_sums.push(normal.map(normalize).unwrap_or([0.0; 3]));
+                    kept.push(i);
+                },
+            }
+        }
+
+        let removed = self.vertex_buffer.len() - kept.len();
+        if removed == 0 {
+            return 0;
+        }
+        let pick = |len: usize| kept.iter().copied().filter(move |i| *i < len);
+        let mut vertex_buffer: Vec<VertexData> = kept.iter().map(|i| self.vertex_buffer[*i].clone()).collect();
+        for (vertex, sum) in vertex_buffer.iter_mut().zip(&normal_sums) {
+            if vertex.normal.is_some() {
+                let n = normalize(*sum);
+                vertex.normal = Some((float(n[0]), float(n[1]), float(n[2])));
+            }
+        }
+        self.vertex_sources = pick(self.vertex_sources.len()).map(|i| self.vertex_sources[i]).collect();
+        self.lightmap_tex_coords = pick(self.lightmap_tex_coords.len()).map(|i| self.lightmap_tex_coords[i]).collect();
+        self.joint_indices = pick(self.joint_indices.len()).map(|i| self.joint_indices[i]).collect();
+        self.joint_weights = pick(self.joint_weights.len()).map(|i| self.joint_weights[i]).collect();
+        self.vertex_buffer = vertex_buffer;
+        for index in self.index_buffer.iter_mut() {
+            *index = remap[*index as usize];
+        }
+
+        removed
+    }
+
+    // Lightmap uvs and skin weights live beside the vertex buffer, so two vertices that look alike
+    // may still have been split on purpose, e.g. along a lightmap seam
+    fn same_side_data(&self, a: usize, b: usize) -> bool {
+        self.lightmap_tex_coords.get(a) == self.lightmap_tex_coords.get(b)
+            && self.joint_indices.get(a) == self.joint_indices.get(b)
+            && self.joint_weights.get(a) == self.joint_weights.get(b)
+    }
+}
+
+fn same_except_normal_and_uv(a: &VertexData, b: &VertexData) -> bool {
+    a.format == b.format && a.tex_coord2 == b.tex_coord2 && a.pos_w == b.pos_w && a.tex_coord_w == b.tex_coord_w
+}
+
+fn to_array(t: (Float, Float, Float)) -> [f32; 3] {
+    [*t.0 as f32, *t.1 as f32, *t.2 as f32]
+}
+
+fn float(x: f32) -> Float {
+    Float::new(x as f64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::test_fixtures::object;
+    use super::*;
+
+    fn pnt(x: Float, normal: (Float, Float, Float), u: Float) -> VertexData {
+        VertexData::vertex_pnt_from_floats(x, f!(0.0), f!(0.0), normal.0, normal.1, normal.2, u, f!(0.0))
+    }
+
+    fn seamed() -> Object3d {
+        let up = (f!(0.0), f!(1.0), f!(0.0));
+        let side = (f!(1.0), f!(0.0), f!(0.0));
+        let tilted = (f!(0.0), f!(0.999), f!(0.0447));
+        object("Seamed", vec!(
+            pnt(f!(0.0), up, f!(0.0)),
+            pnt(f!(0.0), up, f!(1.0)),
+            pnt(f!(0.0), side, f!(0.0)),
+            pnt(f!(0.0), side, f!(1.0)),
+            pnt(f!(0.0), tilted, f!(0.0)),
+            pnt(f!(1.0), up, f!(0.0)),
+        ), &[0, 1, 5, 2, 3, 5, 4, 0, 5])
+    }
+
+    #[test]
+    fn seam_stats_classifies_duplicated_vertices() {
+        let stats = seamed().seam_stats();
+
+        assert_eq!(
+            SeamStats { positions: 2, uv_splits: 2, normal_splits: 2, uv_and_normal_splits: 0, other_splits: 0 },
+            stats
+        );
+        assert_eq!(4, stats.duplicated());
+    }
+
+    #[test]
+    fn weld_normal_splits_averages_nearly_equal_normals() {
+        let mut obj = seamed();
+
+        assert_eq!(1, obj.weld_normal_splits(0.99), "Only the slightly tilted normal is within tolerance");
+        assert_eq!(5, obj.vertex_buffer.len());
+        assert_eq!(vec!(0u64, 1, 4, 2, 3, 4, 0, 0, 4), obj.index_buffer);
+        let normal = obj.vertices().next().unwrap().normal().unwrap();
+        assert!(normal[2] > 0.0 && normal[2] < 0.0447, "The kept vertex gets the average normal: {:?}", normal);
+        assert_eq!(0, obj.weld_normal_splits(0.5), "Hard 90 degree edges stay split");
+    }
+
+    #[test]
+    fn weld_normal_splits_keeps_lightmap_seams() {
+        let text = "v -1 0 -1\nv -1 0 1\nv 1 0 1\nv 1 0 -1\nvn 0 1 0\nf 1//1 2//1 3//1\nf 3//1 4//1 1//1\n";
+        let mut obj = crate::parse_obj_str(text, "Quad").expect("Quad should parse").remove(0);
+        obj.generate_lightmap_uvs(&crate::TriangleGridGenerator::default()).expect("Quad should unwrap");
+        assert_eq!(6, obj.vertex_buffer.len(), "Each triangle gets its own lightmap corners");
+
+        assert_eq!(0, obj.weld_normal_splits(0.99), "Vertices on a lightmap seam must not be welded");
+        assert_eq!(6, obj.vertex_buffer.len());
+        assert_eq!(6, obj.lightmap_tex_coords.len());
+    }
+
+    #[test]
+    fn weld_normal_splits_keeps_different_joint_weights() {
+        let mut obj = seamed();
+        obj.joint_indices = vec!([0, 0, 0, 0]; 6);
+        obj.joint_weights = vec!([1.0, 0.0, 0.0, 0.0]; 6);
+        obj.joint_weights[4] = [0.5, 0.5, 0.0, 0.0];
+
+        assert_eq!(0, obj.weld_normal_splits(0.99), "Differently skinned vertices must not be welded");
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use crate::object3d::Object3d;
+
+// Per vertex bone influences shipped next to an OBJ file, keyed by the 1 based `v` index they
+// apply to
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SkinWeights {
+    pub influences: HashMap<u64, Vec<(u16, f32)>>,
+}
+
+impl SkinWeights {
+    // Reads lines of `position_index,joint,weight[,joint,weight...]`. Blank lines and lines
+    // starting with '#' are skipped
+    pub fn from_csv<R: Read>(reader: R) -> Result<Self, String> {
+        let mut weights = SkinWeights::default();
+
+        for (line_index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = || format!("Malformed skin weight on line {}", line_index + 1);
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() < 3 || fields.len().is_multiple_of(2) {
+                return Err(error());
+            }
+            let position_index = fields[0].parse::<u64>().map_err(|_| error())?;
+            let mut influences = Vec::new();
+            for pair in fields[1..].chunks(2) {
+                let joint = pair[0].parse::<u16>().map_err(|_| error())?;
+                let weight = pair[1].parse::<f32>().map_err(|_| error())?;
+                influences.push((joint, weight));
+            }
+            weights.influences.insert(position_index, influences);
+        }
+
+        Ok(weights)
+    }
+}
+
+impl Object3d {
+    // Fills joint_indices and joint_weights in vertex buffer order using each vertex's source
+    // position. The four strongest influences are kept and renormalized. Vertices without an
+    // entry are bound to joint 0 with no weight
+    pub fn attach_skin_weights(&mut self, weights: &SkinWeights) -> Result<(), String> {
+        if self.vertex_sources.len() != self.vertex_buffer.len() {
+            return Err(String::from("Skin weights need the source of every vertex"));
+        }
+
+        self.joint_indices.clear();
+        self.joint_weights.clear();
+        for source in &self.vertex_sources {
+            let mut influences = weights.influences.get(&source.pos_index).cloned().unwrap_or_default();
+            influences.sort_by(|a, b| b.1.total_cmp(&a.1));
+            influences.truncate(4);
+            let total: f32 = influences.iter().map(|i| i.1).sum();
+
+            let mut joints = [0u16; 4];
+            let mut joint_weights = [0f32; 4];
+            for (slot, (joint, weight)) in influences.iter().enumerate() {
+                joints[slot] = *joint;
+                joint_weights[slot] = if total > 0.0 { weight / total } else { 0.0 };
+            }
+            self.joint_indices.push(joints);
+            self.joint_weights.push(joint_weights);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::vertex::{VertexData, VertexSource};
+    use super::*;
+
+    #[test]
+    fn skin_weights_read_csv_rows() {
+        let weights = SkinWeights::from_csv("# index,joint,weight\n1,0,1.0\n\n2, 1, 0.25, 2, 0.75\n".as_bytes())
+            .expect("Valid csv is read");
+
+        assert_eq!(Some(&vec!((0, 1.0))), weights.influences.get(&1));
+        assert_eq!(Some(&vec!((1, 0.25), (2, 0.75))), weights.influences.get(&2));
+    }
+
+    #[test]
+    fn skin_weights_reject_malformed_rows() {
+        assert_eq!(
+            Err(String::from("Malformed skin weight on line 2")),
+            SkinWeights::from_csv("1,0,1.0\n2,0\n".as_bytes())
+        );
+    }
+
+    #[test]
+    fn attach_skin_weights_follows_compiled_vertex_order() {
+        let mut obj = Object3d::from(String::from("Test"));
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(0.0), f!(0.0), f!(0.0)), source(3)).unwrap();
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(1.0), f!(0.0), f!(0.0)), source(1)).unwrap();
+        obj.add_vertex(VertexData::vertex_p_from_floats(f!(2.0), f!(0.0), f!(0.0)), source(2)).unwrap();
+        let weights = SkinWeights::from_csv("1,4,1.0\n3,0,0.1,1,0.2,2,0.3,3,0.4,4,1.0\n".as_bytes()).unwrap();
+
+        obj.attach_skin_weights(&weights).expect("Weights attach to compiled vertices");
+
+        assert_eq!(vec!([4, 3, 2, 1], [4, 0, 0, 0], [0, 0, 0, 0]), obj.joint_indices, "Strongest four influences are kept");
+        assert!((1.0 - obj.joint_weights[0].iter().sum::<f32>()).abs() < 1e-6, "Kept weights are renormalized");
+        assert_eq!([0.0; 4], obj.joint_weights[2], "Vertices without weights have none");
+    }
+
+    fn source(pos_index: u64) -> VertexSource {
+        VertexSource { pos_index, tex_coord_index: 0, normal_index: 0, line_number: 1 }
+    }
+}
@@ -10,18 +10,25 @@ pub enum StatementType {
     VERTEX,
     NORMAL,
     TEXCOORD,
+    TEXCOORD2,
     USEMTL,
     FACE,
-    ILLUM,
+    SMOOTHING,
+    GROUP,
+    LINE,
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub(crate) enum StatementDataType {
     String(String),
+    Number4D(Float, Float, Float, Float),
     Number3D(Float, Float, Float),
     Number2D(Float, Float),
     Number(Float),
     FacePTN(u64, u64, u64, u64, u64, u64, u64, u64, u64),
+    QuadPTN(u64, u64, u64, u64, u64, u64, u64, u64, u64, u64, u64, u64),
+    // position indices of a polyline
+    Polyline(Vec<u64>),
     None(),
 }
 
@@ -43,16 +50,34 @@ impl StatementDataType {
     }
     
     pub(crate) fn face_as_index_tuples(&self) -> Option<Vec<VertexDataIndex>> {
-        if let StatementDataType::FacePTN(xp, xn, xt, yp, yn, yt, zp, zn, zt) = self {
-            let mut ret = Vec::new();
-            ret.push(VertexDataIndex::from_indices(&(*xp, *xn, *xt)));
-            ret.push(VertexDataIndex::from_indices(&(*yp, *yn, *yt)));
-            ret.push(VertexDataIndex::from_indices(&(*zp, *zn, *zt)));
-            
-            return Some(ret);
-        }
+        let corners = self.face_corners()?;
         
-        None
+        Some(corners.iter().map(|c| VertexDataIndex::from_indices(&(c[0], c[1], c[2]))).collect())
+    }
+    
+    // The (v, vt, vn) indices of each corner of a triangle or quad face
+    pub(crate) fn face_corners(&self) -> Option<Vec<[u64; 3]>> {
+        match *self {
+            StatementDataType::FacePTN(xp, xt, xn, yp, yt, yn, zp, zt, zn) => {
+                Some(vec!([xp, xt, xn], [yp, yt, yn], [zp, zt, zn]))
+            },
+            StatementDataType::QuadPTN(xp, xt, xn, yp, yt, yn, zp, zt, zn, wp, wt, wn) => {
+                Some(vec!([xp, xt, xn], [yp, yt, yn], [zp, zt, zn], [wp, wt, wn]))
+            },
+            _ => None,
+        }
+    }
+    
+    pub(crate) fn from_face_corners(corners: &[[u64; 3]]) -> Option<Self> {
+        match corners {
+            [x, y, z] => Some(StatementDataType::FacePTN(
+                x[0], x[1], x[2], y[0], y[1], y[2], z[0], z[1], z[2]
+            )),
+            [x, y, z, w] => Some(StatementDataType::QuadPTN(
+                x[0], x[1], x[2], y[0], y[1], y[2], z[0], z[1], z[2], w[0], w[1], w[2]
+            )),
+            _ => None,
+        }
     }
 }
 
@@ -65,18 +90,25 @@ impl fmt::Display for StatementType {
             StatementType::VERTEX => "vertex",
             StatementType::NORMAL => "normal",
             StatementType::TEXCOORD => "texcoord",
+            StatementType::TEXCOORD2 => "texcoord2",
             StatementType::USEMTL => "usemtl",
             StatementType::FACE => "face",
-            StatementType::ILLUM => "illum",
+            StatementType::SMOOTHING => "smoothing",
+            StatementType::GROUP => "group",
+            StatementType::LINE => "line",
         })
     }
 }
 
-pub(crate) struct Statement {
+// One parsed line. Its data stays internal, the compiler turns it into objects
+pub struct Statement {
     pub(crate) statement_type: StatementType,
     pub(crate) data: StatementDataType,
     pub(crate) line_number: u64,
     pub(crate) line_position: u64,
+    // which of the data's indices the parser resolved from relative ones, in the order they
+    // appear. Those are 1 based whatever base the rest of the file uses. Empty when none were
+    pub(crate) relative_indices: Vec<bool>,
 }
 
 impl Statement {
@@ -91,6 +123,23 @@ impl Statement {
             data,
             line_number,
             line_position,
+            relative_indices: Vec::new(),
         }
     }
+
+    pub(crate) fn is_relative_index(&self, i: usize) -> bool {
+        self.relative_indices.get(i).copied().unwrap_or(false)
+    }
+
+    pub fn statement_type(&self) -> StatementType {
+        self.statement_type
+    }
+
+    pub fn line_number(&self) -> u64 {
+        self.line_number
+    }
+
+    pub fn line_position(&self) -> u64 {
+        self.line_position
+    }
 }
\ No newline at end of file
@@ -0,0 +1,238 @@
+use crate::geometry::{length, triangle_cross};
+use crate::nan_safe_float::Float;
+use crate::object3d::{Object3d, TexCoordBounds};
+
+// Counts of values falling into bucket_count equal width buckets spanning [min, max]
+#[derive(PartialEq, Debug, Clone)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn from_values(values: &[f64], bucket_count: usize) -> Self {
+        let bucket_count = bucket_count.max(1);
+        let mut counts = vec![0; bucket_count];
+        if values.is_empty() {
+            return Histogram { min: 0.0, max: 0.0, counts };
+        }
+
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let width = (max - min) / bucket_count as f64;
+        for value in values {
+            let bucket = if width > 0.0 { ((value - min) / width) as usize } else { 0 };
+            counts[bucket.min(bucket_count - 1)] += 1;
+        }
+
+        Histogram { min, max, counts }
+    }
+
+    pub fn bucket_width(&self) -> f64 {
+        (self.max - self.min) / self.counts.len() as f64
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl Object3d {
+    pub fn triangle_count(&self) -> usize {
+        self.index_buffer.len() / 3
+    }
+
+    pub fn triangle_areas(&self) -> Vec<f64> {
+        let positions: Vec<[f32; 3]> = self.vertices().map(|v| v.pos()).collect();
+
+        self.index_buffer.chunks_exact(3)
+            .map(|t| {
+                let cross = triangle_cross(positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize]);
+                length(cross) as f64 / 2.0
+            })
+            .collect()
+    }
+
+    // Texels per world unit along an edge when the object's UVs are mapped onto a texture of
+    // texture_size pixels. None without tex coords or surface area
+    pub fn texel_density(&self, texture_size: (u32, u32)) -> Option<f64> {
+        let uvs: Vec<[f32; 2]> = self.vertices().map(|v| v.uv()).collect::<Option<_>>()?;
+        let uv_area: f64 = self.index_buffer.chunks_exact(3)
+            .map(|t| {
+                let (a, b, c) = (uvs[t[0] as usize], uvs[t[1] as usize], uvs[t[2] as usize]);
+                let doubled = (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]);
+                doubled.abs() as f64 / 2.0
+            })
+            .sum();
+        let world_area: f64 = self.triangle_areas().iter().sum();
+        if world_area <= 0.0 || uvs.is_empty() {
+            return None;
+        }
+
+        let texel_area = uv_area * texture_size.0 as f64 * texture_size.1 as f64;
+        Some((texel_area / world_area).sqrt())
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct TexelDensityAdjustment {
+    pub object: String,
+    pub density: f64,
+    // UV scale that brings the object to the target density
+    pub scale: f64,
+}
+
+// Reports how far each textured object is from target_density. With apply set, UVs are also
+// scaled about the minimum corner of the object's UV bounds so every object ends up at the target
+pub fn normalize_texel_density(
+    objects: &mut [Object3d],
+    target_density: f64,
+    texture_size: (u32, u32),
+    apply: bool,
+) -> Vec<TexelDensityAdjustment> {
+    let mut adjustments = Vec::new();
+
+    for object in objects.iter_mut() {
+        let density = match object.texel_density(texture_size) {
+            Some(x) if x > 0.0 => x,
+            _ => continue,
+        };
+        let scale = target_density / density;
+        if apply {
+            object.scale_tex_coords(scale);
+        }
+        adjustments.push(TexelDensityAdjustment { object: object.name.clone(), density, scale });
+    }
+
+    adjustments
+}
+
+impl Object3d {
+    fn scale_tex_coords(&mut self, scale: f64) {
+        let mut tex_coords = self.vertex_buffer.iter().filter_map(|v| v.tex_coord);
+        let mut bounds = match tex_coords.next() {
+            Some(x) => TexCoordBounds::from(x),
+            None => return,
+        };
+        tex_coords.for_each(|x| bounds.extend(x));
+        let origin = bounds.min;
+        let scale_component = |value: Float, origin: Float| {
+            Float::new(*origin + (*value - *origin) * scale).expect("Scaled tex coord is a number")
+        };
+
+        self.tex_coord_bounds = None;
+        for vertex in self.vertex_buffer.iter_mut() {
+            if let Some((u, v)) = vertex.tex_coord {
+                let scaled = (scale_component(u, origin.0), scale_component(v, origin.1));
+                vertex.tex_coord = Some(scaled);
+                match self.tex_coord_bounds.as_mut() {
+                    Some(bounds) => bounds.extend(scaled),
+                    None => self.tex_coord_bounds = Some(TexCoordBounds::from(scaled)),
+                }
+            }
+        }
+    }
+}
+
+pub fn triangle_count_histogram(objects: &[Object3d], bucket_count: usize) -> Histogram {
+    let counts: Vec<f64> = objects.iter().map(|o| o.triangle_count() as f64).collect();
+
+    Histogram::from_values(&counts, bucket_count)
+}
+
+pub fn triangle_area_histogram(objects: &[Object3d], bucket_count: usize) -> Histogram {
+    let areas: Vec<f64> = objects.iter().flat_map(|o| o.triangle_areas()).collect();
+
+    Histogram::from_values(&areas, bucket_count)
+}
+
+// Objects without tex coords are left out
+pub fn texel_density_histogram(objects: &[Object3d], texture_size: (u32, u32), bucket_count: usize) -> Histogram {
+    let densities: Vec<f64> = objects.iter().filter_map(|o| o.texel_density(texture_size)).collect();
+
+    Histogram::from_values(&densities, bucket_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use crate::nan_safe_float::Float;
+    use crate::test_fixtures::{float, object};
+    use crate::vertex::VertexData;
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_values_by_equal_width() {
+        let histogram = Histogram::from_values(&[0.0, 1.0, 2.0, 9.0, 10.0], 5);
+
+        assert_eq!(vec!(2, 1, 0, 0, 2), histogram.counts, "Max value lands in the last bucket");
+        assert_eq!(2.0, histogram.bucket_width());
+        assert_eq!(5, histogram.total());
+    }
+
+    #[test]
+    fn histogram_of_identical_values_uses_first_bucket() {
+        let histogram = Histogram::from_values(&[3.0, 3.0], 4);
+
+        assert_eq!(vec!(2, 0, 0, 0), histogram.counts);
+    }
+
+    #[test]
+    fn triangle_statistics_cover_every_object() {
+        let objects = vec!(square(2.0, 1.0), square(1.0, 1.0));
+
+        assert_eq!(vec!(2, 0), triangle_count_histogram(&objects, 2).counts, "Both objects have 2 triangles");
+        let areas = triangle_area_histogram(&objects, 2);
+        assert_eq!((0.5, 2.0), (areas.min, areas.max), "Triangle areas are half the square areas");
+        assert_eq!(vec!(2, 2), areas.counts);
+    }
+
+    #[test]
+    fn texel_density_relates_uv_area_to_world_area() {
+        // a 2x2 unit square mapped onto the whole of a 256x256 texture
+        assert_eq!(Some(128.0), square(2.0, 1.0).texel_density((256, 256)));
+        assert_eq!(vec!(1, 1), texel_density_histogram(&[square(2.0, 1.0), square(1.0, 1.0)], (256, 256), 2).counts);
+    }
+
+    #[test]
+    fn normalize_texel_density_reports_scale_to_target() {
+        let mut objects = vec!(square(2.0, 1.0), square(1.0, 1.0));
+
+        let adjustments = normalize_texel_density(&mut objects, 64.0, (256, 256), false);
+
+        assert_eq!(
+            vec!(
+                TexelDensityAdjustment { object: String::from("Square"), density: 128.0, scale: 0.5 },
+                TexelDensityAdjustment { object: String::from("Square"), density: 256.0, scale: 0.25 },
+            ),
+            adjustments
+        );
+        assert_eq!(Some(128.0), objects[0].texel_density((256, 256)), "UVs are untouched unless applied");
+    }
+
+    #[test]
+    fn normalize_texel_density_rescales_uvs_when_applied() {
+        let mut objects = vec!(square(2.0, 1.0), square(1.0, 1.0));
+
+        normalize_texel_density(&mut objects, 64.0, (256, 256), true);
+
+        assert_eq!(Some(64.0), objects[0].texel_density((256, 256)));
+        assert_eq!(Some(64.0), objects[1].texel_density((256, 256)));
+        assert_eq!(
+            Some(TexCoordBounds { min: (f!(0.0), f!(0.0)), max: (f!(0.25), f!(0.25)) }),
+            objects[1].tex_coord_bounds,
+            "UV bounds follow the rescaled tex coords"
+        );
+    }
+
+    fn square(size: f64, uv_size: f64) -> Object3d {
+        let (s, u) = (float(size), float(uv_size));
+        object("Square", vec!(
+            VertexData::vertex_pt_from_floats(f!(0.0), f!(0.0), f!(0.0), f!(0.0), f!(0.0)),
+            VertexData::vertex_pt_from_floats(s, f!(0.0), f!(0.0), u, f!(0.0)),
+            VertexData::vertex_pt_from_floats(s, s, f!(0.0), u, u),
+            VertexData::vertex_pt_from_floats(f!(0.0), s, f!(0.0), f!(0.0), u),
+        ), &[0, 1, 2, 0, 2, 3])
+    }
+}
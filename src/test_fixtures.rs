@@ -0,0 +1,31 @@
+// Meshes shared by the unit tests of the mesh processing modules, so each test module doesn't
+// hand-assemble its own copy of the same quad or triangle
+use crate::nan_safe_float::Float;
+use crate::object3d::Object3d;
+use crate::vertex::{VertexData, VertexFormat};
+
+// Object whose vertices all share the first vertex's format, VertexP when there are none
+pub(crate) fn object(name: &str, vertex_buffer: Vec<VertexData>, index_buffer: &[u64]) -> Object3d {
+    Object3d {
+        format: vertex_buffer.first().map(|v| v.format).unwrap_or(VertexFormat::VertexP),
+        vertex_buffer,
+        index_buffer: index_buffer.to_vec(),
+        ..Object3d::from(String::from(name))
+    }
+}
+
+// VertexP object, the common case for tests that only look at positions
+pub(crate) fn positions(name: &str, points: &[[f64; 3]], index_buffer: &[u64]) -> Object3d {
+    let vertex_buffer = points.iter().map(|p| VertexData::vertex_p_from_floats(float(p[0]), float(p[1]), float(p[2]))).collect();
+
+    object(name, vertex_buffer, index_buffer)
+}
+
+pub(crate) fn float(x: f64) -> Float {
+    Float::new(x).expect("Fixture coordinates are never NaN")
+}
+
+// The square from -1 to 1 in the xz plane as two triangles sharing the 0-2 diagonal
+pub(crate) fn quad() -> Object3d {
+    positions("Test", &[[-1.0, 0.0, -1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 0.0, -1.0]], &[0, 1, 2, 2, 3, 0])
+}
@@ -10,9 +10,12 @@ pub enum TokenType {
     VERTEX,
     NORMAL,
     TEXCOORD,
+    TEXCOORD2,
     USEMTL,
     FACE,
-    ILLUM,
+    SMOOTHING,
+    GROUP,
+    LINE,
     NUMBER,
     STRING,
     POLYGON,
@@ -29,9 +32,12 @@ impl fmt::Display for TokenType {
             TokenType::VERTEX => { f.write_str("VERTEX") },
             TokenType::NORMAL => { f.write_str("NORMAL") },
             TokenType::TEXCOORD => { f.write_str("TEXCOORD") },
+            TokenType::TEXCOORD2 => { f.write_str("TEXCOORD2") },
             TokenType::USEMTL => { f.write_str("USEMTL") },
             TokenType::FACE => { f.write_str("FACE") },
-            TokenType::ILLUM => { f.write_str("ILLUM") },
+            TokenType::SMOOTHING => { f.write_str("SMOOTHING") },
+            TokenType::GROUP => { f.write_str("GROUP") },
+            TokenType::LINE => { f.write_str("LINE") },
             TokenType::NUMBER => { f.write_str("NUMBER") },
             TokenType::STRING => { f.write_str("STRING") },
             TokenType::POLYGON => { f.write_str("POLYGON") },
@@ -50,9 +56,12 @@ impl TokenType {
             "v" => Some(TokenType::VERTEX),
             "vn" => Some(TokenType::NORMAL),
             "vt" => Some(TokenType::TEXCOORD),
+            "vt2" => Some(TokenType::TEXCOORD2),
             "usemtl" => Some(TokenType::USEMTL),
             "f" => Some(TokenType::FACE),
-            "s" => Some(TokenType::ILLUM),
+            "s" => Some(TokenType::SMOOTHING),
+            "g" => Some(TokenType::GROUP),
+            "l" => Some(TokenType::LINE),
             _ => None
         }
     }
@@ -62,7 +71,8 @@ impl TokenType {
 pub(crate) enum TokenDataType {
     String(String),
     Number(Float),
-    VertexPTN(u64, u64, u64),
+    // negative indices count back from the most recently declared v/vt/vn
+    VertexPTN(i64, i64, i64),
     None()
 }
 
@@ -0,0 +1,160 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use crate::diagnostic::Diagnostic;
+use crate::error::WfoError;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::statement::{Statement, StatementDataType, StatementType};
+
+// Everything validate_only found. Diagnostics cover lexing, parsing and the index and format
+// checks compile would fail on
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub statement_count: usize,
+    pub object_count: usize,
+    pub face_count: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+// Checks a file the way parse_obj_file would load it but stops short of compiling, so no vertex
+// or index buffers are built. Only failing to open or read the file, or a file that isn't OBJ
+// text at all, is an Err
+pub fn validate_only<P: AsRef<Path>>(path: P) -> Result<ValidationReport, WfoError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|source| WfoError::IoError { path: Some(path.to_path_buf()), source })?;
+
+    validate_reader(&mut BufReader::new(file))
+}
+
+pub(crate) fn validate_reader<R: Read>(reader: &mut R) -> Result<ValidationReport, WfoError> {
+    let mut lexer = Lexer::new();
+    let tokens = lexer.lex_input(reader)?;
+    let (statements, mut diagnostics) = Parser::new().parse_tokens_with_recovery(&tokens);
+    diagnostics.extend(lexer.warnings().iter().cloned());
+    diagnostics.extend(check_statements(&statements));
+    diagnostics.sort_by_key(|d| (d.line_number, d.line_position));
+
+    let count = |statement_type| statements.iter().filter(|s| s.statement_type == statement_type).count();
+    let faces = count(StatementType::FACE);
+    // faces before the first `o` go into the default object
+    let leading_faces = statements.iter()
+        .take_while(|s| s.statement_type != StatementType::OBJECT)
+        .any(|s| s.statement_type == StatementType::FACE);
+
+    Ok(ValidationReport {
+        statement_count: statements.len(),
+        object_count: count(StatementType::OBJECT) + leading_faces as usize,
+        face_count: faces,
+        diagnostics,
+    })
+}
+
+// The checks compile makes with its default options, run over the statements alone
+fn check_statements(statements: &[Statement]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut lengths = [0u64; 3];
+    // whether faces of the current object have texture coordinates and normals
+    let mut object_format: Option<(bool, bool)> = None;
+
+    for statement in statements {
+        match statement.statement_type {
+            StatementType::VERTEX => lengths[0] += 1,
+            StatementType::TEXCOORD => lengths[1] += 1,
+            StatementType::NORMAL => lengths[2] += 1,
+            StatementType::OBJECT => object_format = None,
+            _ => {},
+        }
+
+        let corners = match (&statement.data, statement.data.face_corners()) {
+            (_, Some(x)) => x,
+            (StatementDataType::Polyline(x), None) => x.iter().map(|p| [*p, 0, 0]).collect(),
+            _ => continue,
+        };
+        let names = ["position", "texture coordinate", "normal"];
+        let out_of_range = corners.iter()
+            .flat_map(|c| (0..3).map(move |i| (c, i)))
+            .find(|(c, i)| if *i == 0 { !(1..=lengths[0]).contains(&c[0]) } else { c[*i] > lengths[*i] });
+        if let Some((corner, component)) = out_of_range {
+            diagnostics.push(Diagnostic::from(
+                format!(
+                    "{} references {} index {} but only {} are defined",
+                    if statement.statement_type == StatementType::LINE { "Line" } else { "Face" },
+                    names[component], corner[component], lengths[component]
+                ),
+                statement.line_number,
+                statement.line_position
+            ));
+            continue;
+        }
+
+        if statement.statement_type != StatementType::FACE {
+            continue;
+        }
+        for corner in &corners {
+            let format = (corner[1] != 0, corner[2] != 0);
+            match object_format {
+                None => object_format = Some(format),
+                Some(x) if x != format => {
+                    diagnostics.push(Diagnostic::from(
+                        String::from("Face changes the vertex format of its object"),
+                        statement.line_number,
+                        statement.line_position
+                    ));
+                    break;
+                },
+                _ => {},
+            }
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_reader_accepts_valid_input() {
+        let report = validate_reader(&mut "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\no Named\nf 3 2 1\n".as_bytes()).expect("Reading a string can't fail");
+
+        assert!(report.is_valid(), "Valid input has no diagnostics: {:?}", report.diagnostics);
+        assert_eq!(6, report.statement_count);
+        assert_eq!(2, report.object_count, "The default object and Named");
+        assert_eq!(2, report.face_count);
+    }
+
+    #[test]
+    fn validate_reader_reports_parse_and_semantic_errors_together() {
+        let report = validate_reader(&mut "v 0 0 0\nv 1.0\nf 1 2 3\nl 1 1\n".as_bytes()).expect("Reading a string can't fail");
+
+        let messages: Vec<&str> = report.diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(2, messages.len(), "One parse error and one range error: {:?}", messages);
+        assert_eq!(2, report.diagnostics[0].line_number, "The incomplete vertex");
+        assert_eq!("Face references position index 2 but only 1 are defined", messages[1]);
+    }
+
+    #[test]
+    fn validate_reader_reports_vertex_format_changes() {
+        let report = validate_reader(&mut "v 0 0 0\nvt 0 0\nf 1 1 1\nf 1/1 1/1 1/1\n".as_bytes()).expect("Reading a string can't fail");
+
+        assert_eq!(
+            vec!(String::from("Face changes the vertex format of its object")),
+            report.diagnostics.iter().map(|d| d.message.clone()).collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn validate_only_reports_missing_files() {
+        let result = validate_only("/nonexistent/missing.obj");
+
+        assert!(result.expect_err("Missing file is an error").to_string().starts_with("Could not open /nonexistent/missing.obj"));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::nan_safe_float::Float;
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
-pub(crate) enum VertexFormat {
+pub enum VertexFormat {
     Unknown,
     VertexP,
     VertexPN,
@@ -9,7 +9,67 @@ pub(crate) enum VertexFormat {
     VertexPNT,
 }
 
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    TexCoord,
+    // the second UV set from vt2, not part of any VertexFormat since only some vertices may have it
+    TexCoord2,
+}
+
+// Layout of one attribute inside an interleaved f32 vertex of a given VertexFormat
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct AttributeDesc {
+    pub semantic: VertexAttribute,
+    pub component_count: u32,
+    pub byte_offset: u32,
+    pub byte_size: u32,
+}
+
+impl AttributeDesc {
+    pub(crate) const fn from(semantic: VertexAttribute, component_count: u32, byte_offset: u32) -> Self {
+        Self {
+            semantic,
+            component_count,
+            byte_offset,
+            byte_size: component_count * 4,
+        }
+    }
+}
+
+const VERTEX_P_ATTRIBUTES: [AttributeDesc; 1] = [
+    AttributeDesc::from(VertexAttribute::Position, 3, 0),
+];
+const VERTEX_PN_ATTRIBUTES: [AttributeDesc; 2] = [
+    AttributeDesc::from(VertexAttribute::Position, 3, 0),
+    AttributeDesc::from(VertexAttribute::Normal, 3, 12),
+];
+const VERTEX_PT_ATTRIBUTES: [AttributeDesc; 2] = [
+    AttributeDesc::from(VertexAttribute::Position, 3, 0),
+    AttributeDesc::from(VertexAttribute::TexCoord, 2, 12),
+];
+const VERTEX_PNT_ATTRIBUTES: [AttributeDesc; 3] = [
+    AttributeDesc::from(VertexAttribute::Position, 3, 0),
+    AttributeDesc::from(VertexAttribute::Normal, 3, 12),
+    AttributeDesc::from(VertexAttribute::TexCoord, 2, 24),
+];
+
 impl VertexFormat {
+    pub fn attributes(&self) -> &'static [AttributeDesc] {
+        match self {
+            VertexFormat::Unknown => &[],
+            VertexFormat::VertexP => &VERTEX_P_ATTRIBUTES,
+            VertexFormat::VertexPN => &VERTEX_PN_ATTRIBUTES,
+            VertexFormat::VertexPT => &VERTEX_PT_ATTRIBUTES,
+            VertexFormat::VertexPNT => &VERTEX_PNT_ATTRIBUTES,
+        }
+    }
+    
+    pub fn stride(&self) -> u32 {
+        self.attributes().iter().map(|a| a.byte_size).sum()
+    }
+    
     pub(crate) fn from_indices(indices: &(u64, u64, u64)) -> Self {
         match indices {
             (0, _tc, _n) => { panic!("Vertex format must have position index"); },
@@ -39,12 +99,38 @@ impl VertexDataIndex {
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
-pub(crate) struct VertexData {
+// Where a compiled vertex came from: its original 1 based v/vt/vn indices (0 when absent) and
+// the line of the face statement that first referenced it
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct VertexSource {
+    pub pos_index: u64,
+    pub tex_coord_index: u64,
+    pub normal_index: u64,
+    pub line_number: u64,
+}
+
+impl VertexSource {
+    pub(crate) fn from(index: &VertexDataIndex, line_number: u64) -> Self {
+        Self {
+            pos_index: index.pos,
+            tex_coord_index: index.tex_coord,
+            normal_index: index.normal,
+            line_number,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct VertexData {
     pub(crate) format: VertexFormat,
-    pos: (Float, Float, Float),
-    normal: Option<(Float, Float, Float)>,
-    tex_coord: Option<(Float, Float)>,
+    pub(crate) pos: (Float, Float, Float),
+    pub(crate) normal: Option<(Float, Float, Float)>,
+    pub(crate) tex_coord: Option<(Float, Float)>,
+    // second UV set from the non standard vt2 statement, indexed by the face's vt index
+    pub(crate) tex_coord2: Option<(Float, Float)>,
+    // the optional w of the `v` and `vt` statements, none when the file leaves it out
+    pub(crate) pos_w: Option<Float>,
+    pub(crate) tex_coord_w: Option<Float>,
 }
 
 impl VertexData {
@@ -54,6 +140,9 @@ impl VertexData {
             pos: (x, y, z),
             normal: None,
             tex_coord: None,
+            tex_coord2: None,
+            pos_w: None,
+            tex_coord_w: None,
         }
     }
     
@@ -66,6 +155,9 @@ impl VertexData {
             pos: (px, py, pz),
             normal: Some((nx, ny, nz)),
             tex_coord: None,
+            tex_coord2: None,
+            pos_w: None,
+            tex_coord_w: None,
         }
     }
     
@@ -77,7 +169,10 @@ impl VertexData {
             format: VertexFormat::VertexPT,
             pos: (px, py, pz),
             normal: None,
-            tex_coord: Some((tx, ty))
+            tex_coord: Some((tx, ty)),
+            tex_coord2: None,
+            pos_w: None,
+            tex_coord_w: None,
         }
     }
     
@@ -90,7 +185,10 @@ impl VertexData {
             format: VertexFormat::VertexPNT,
             pos: (px, py, pz),
             normal: Some((nx, ny, nz)),
-            tex_coord: Some((tx, ty))
+            tex_coord: Some((tx, ty)),
+            tex_coord2: None,
+            pos_w: None,
+            tex_coord_w: None,
         }
     }
     
@@ -115,13 +213,16 @@ impl VertexData {
     ) -> Result<Self, String> {
         let position = position_buffer.get(index.pos as usize - 1);
         if let None = position {
-            Err(String::from("Bad position index"))
+            Err(format!("Bad position index {}, only {} are defined", index.pos, position_buffer.len()))
         } else {
             Ok(Self {
                 format: VertexFormat::VertexP,
                 pos: *position.unwrap(),
                 normal: None,
-                tex_coord: None
+                tex_coord: None,
+                tex_coord2: None,
+                pos_w: None,
+                tex_coord_w: None,
             })
         }
     }
@@ -133,12 +234,12 @@ impl VertexData {
     ) -> Result<Self, String> {
         let position = position_buffer.get(index.pos as usize - 1);
         if let None = position {
-            return Err(String::from("Bad position index"));
+            return Err(format!("Bad position index {}, only {} are defined", index.pos, position_buffer.len()));
         }
         
         let normal = normal_buffer.get(index.normal as usize - 1);
         if let None = normal {
-            return Err(String::from("Bad normal index"));
+            return Err(format!("Bad normal index {}, only {} are defined", index.normal, normal_buffer.len()));
         }
         
         Ok(
@@ -146,7 +247,10 @@ impl VertexData {
                 format: VertexFormat::VertexPN,
                 pos: *position.unwrap(),
                 normal: normal.copied(),
-                tex_coord: None
+                tex_coord: None,
+                tex_coord2: None,
+                pos_w: None,
+                tex_coord_w: None,
             }
         )
     }
@@ -158,12 +262,12 @@ impl VertexData {
     ) -> Result<Self, String> {
         let position = position_buffer.get(index.pos as usize - 1);
         if let None = position {
-            return Err(String::from("Bad position index"));
+            return Err(format!("Bad position index {}, only {} are defined", index.pos, position_buffer.len()));
         }
         
         let tex_coord = tex_coord_buffer.get(index.tex_coord as usize - 1);
         if let None = tex_coord {
-            return Err(String::from("Bad texture coordinate index"));
+            return Err(format!("Bad texture coordinate index {}, only {} are defined", index.tex_coord, tex_coord_buffer.len()));
         }
         
         Ok(
@@ -171,7 +275,10 @@ impl VertexData {
                 format: VertexFormat::VertexPT,
                 pos: *position.unwrap(),
                 normal: None,
-                tex_coord: tex_coord.copied()
+                tex_coord: tex_coord.copied(),
+                tex_coord2: None,
+                pos_w: None,
+                tex_coord_w: None,
             }
         )
     }
@@ -184,17 +291,17 @@ impl VertexData {
     ) -> Result<Self, String> {
         let position = position_buffer.get(index.pos as usize - 1);
         if let None = position {
-            return Err(String::from("Bad position index"));
+            return Err(format!("Bad position index {}, only {} are defined", index.pos, position_buffer.len()));
         }
         
         let normal = normal_buffer.get(index.normal as usize - 1);
         if let None = normal {
-            return Err(String::from("Bad normal index"));
+            return Err(format!("Bad normal index {}, only {} are defined", index.normal, normal_buffer.len()));
         }
         
         let tex_coord = tex_coord_buffer.get(index.tex_coord as usize - 1);
         if let None = tex_coord {
-            return Err(String::from("Bad texture coordinate index"));
+            return Err(format!("Bad texture coordinate index {}, only {} are defined", index.tex_coord, tex_coord_buffer.len()));
         }
         
         Ok(
@@ -202,8 +309,164 @@ impl VertexData {
                 format: VertexFormat::VertexPNT,
                 pos: *position.unwrap(),
                 normal: normal.copied(),
-                tex_coord: tex_coord.copied()
+                tex_coord: tex_coord.copied(),
+                tex_coord2: None,
+                pos_w: None,
+                tex_coord_w: None,
             }
         )
     }
-}
\ No newline at end of file
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum AttributeValue {
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+}
+
+impl AttributeValue {
+    pub fn as_slice(&self) -> &[f32] {
+        match self {
+            AttributeValue::Vec2(x) => x,
+            AttributeValue::Vec3(x) => x,
+        }
+    }
+}
+
+// Read only view of a compiled vertex with components converted to the f32 arrays graphics
+// APIs expect
+#[derive(Copy, Clone)]
+pub struct VertexView<'a> {
+    vertex: &'a VertexData,
+}
+
+impl<'a> VertexView<'a> {
+    pub(crate) fn from(vertex: &'a VertexData) -> Self {
+        Self { vertex }
+    }
+    
+    pub fn format(&self) -> VertexFormat {
+        self.vertex.format
+    }
+    
+    pub fn pos(&self) -> [f32; 3] {
+        to_f32_3d(self.vertex.pos)
+    }
+    
+    pub fn normal(&self) -> Option<[f32; 3]> {
+        self.vertex.normal.map(to_f32_3d)
+    }
+    
+    pub fn uv(&self) -> Option<[f32; 2]> {
+        self.vertex.tex_coord.map(|(u, v)| [*u as f32, *v as f32])
+    }
+    
+    pub fn uv2(&self) -> Option<[f32; 2]> {
+        self.vertex.tex_coord2.map(|(u, v)| [*u as f32, *v as f32])
+    }
+    
+    // The optional w of the `v` statement, none when the file leaves it out
+    pub fn pos_w(&self) -> Option<f32> {
+        self.vertex.pos_w.map(|w| *w as f32)
+    }
+    
+    // The optional w of the `vt` statement
+    pub fn uv_w(&self) -> Option<f32> {
+        self.vertex.tex_coord_w.map(|w| *w as f32)
+    }
+    
+    pub fn get(&self, attribute: VertexAttribute) -> Option<AttributeValue> {
+        match attribute {
+            VertexAttribute::Position => Some(AttributeValue::Vec3(self.pos())),
+            VertexAttribute::Normal => self.normal().map(AttributeValue::Vec3),
+            VertexAttribute::TexCoord => self.uv().map(AttributeValue::Vec2),
+            VertexAttribute::TexCoord2 => self.uv2().map(AttributeValue::Vec2),
+        }
+    }
+}
+
+fn to_f32_3d(t: (Float, Float, Float)) -> [f32; 3] {
+    [*t.0 as f32, *t.1 as f32, *t.2 as f32]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::f;
+    use super::*;
+    
+    #[test]
+    fn vertex_data_compile_reports_the_bad_index() {
+        let positions = vec!((f!(0.0), f!(0.0), f!(0.0)));
+        let normals = vec!((f!(0.0), f!(1.0), f!(0.0)));
+        
+        let result = VertexData::compile(VertexDataIndex::from_indices(&(1, 0, 3)), &positions, &normals, &Vec::new());
+        
+        assert_eq!(Err(String::from("Bad normal index 3, only 1 are defined")), result);
+    }
+    
+    #[test]
+    fn vertex_format_describes_interleaved_attributes() {
+        assert_eq!(
+            &[
+                AttributeDesc { semantic: VertexAttribute::Position, component_count: 3, byte_offset: 0, byte_size: 12 },
+                AttributeDesc { semantic: VertexAttribute::Normal, component_count: 3, byte_offset: 12, byte_size: 12 },
+                AttributeDesc { semantic: VertexAttribute::TexCoord, component_count: 2, byte_offset: 24, byte_size: 8 },
+            ],
+            VertexFormat::VertexPNT.attributes(),
+            "VertexPNT has position, normal, and tex coord attributes"
+        );
+        assert_eq!(
+            &[
+                AttributeDesc { semantic: VertexAttribute::Position, component_count: 3, byte_offset: 0, byte_size: 12 },
+                AttributeDesc { semantic: VertexAttribute::TexCoord, component_count: 2, byte_offset: 12, byte_size: 8 },
+            ],
+            VertexFormat::VertexPT.attributes(),
+            "VertexPT packs tex coords directly after positions"
+        );
+        assert!(VertexFormat::Unknown.attributes().is_empty(), "Unknown format has no attributes");
+    }
+    
+    #[test]
+    fn vertex_format_stride_is_sum_of_attribute_sizes() {
+        assert_eq!(12, VertexFormat::VertexP.stride());
+        assert_eq!(24, VertexFormat::VertexPN.stride());
+        assert_eq!(20, VertexFormat::VertexPT.stride());
+        assert_eq!(32, VertexFormat::VertexPNT.stride());
+    }
+    
+    #[test]
+    fn vertex_view_converts_components_to_f32_arrays() {
+        let vertex = VertexData::vertex_pnt_from_floats(
+            f!(1.0), f!(2.0), f!(3.0), f!(0.0), f!(1.0), f!(0.0), f!(0.25), f!(0.75)
+        );
+        let view = VertexView::from(&vertex);
+        
+        assert_eq!([1.0, 2.0, 3.0], view.pos(), "Vertex view returns position");
+        assert_eq!(Some([0.0, 1.0, 0.0]), view.normal(), "Vertex view returns normal");
+        assert_eq!(Some([0.25, 0.75]), view.uv(), "Vertex view returns texture coordinate");
+    }
+    
+    #[test]
+    fn vertex_view_returns_none_for_absent_attributes() {
+        let vertex = VertexData::vertex_p_from_floats(f!(1.0), f!(2.0), f!(3.0));
+        let view = VertexView::from(&vertex);
+        
+        assert_eq!(
+            Some(AttributeValue::Vec3([1.0, 2.0, 3.0])),
+            view.get(VertexAttribute::Position),
+            "Vertex view gets position by attribute"
+        );
+        assert_eq!(None, view.get(VertexAttribute::Normal), "Vertex view has no normal for VertexP");
+        assert_eq!(None, view.get(VertexAttribute::TexCoord), "Vertex view has no tex coord for VertexP");
+    }
+    
+    #[test]
+    fn vertex_view_returns_optional_w_components() {
+        let mut vertex = VertexData::vertex_pt_from_floats(f!(1.0), f!(2.0), f!(3.0), f!(0.25), f!(0.75));
+        vertex.pos_w = Some(f!(0.5));
+        let view = VertexView::from(&vertex);
+        
+        assert_eq!(Some(0.5), view.pos_w(), "Vertex view returns position w");
+        assert_eq!(None, view.uv_w(), "Vertex view has no tex coord w unless the file gives one");
+    }
+}